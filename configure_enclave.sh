@@ -23,7 +23,8 @@ show_help() {
     echo "  export KEY_PAIR=<your-key-pair-name>"
     echo "  # optional: export REGION=<your-region>  (defaults to us-east-1)"
     echo "  # optional: export AMI_ID=<your-ami-id>  (defaults to ami-085ad6ae776d8f09c)"
-    echo "  # optional: export API_ENV_VAR_NAME=<env-var-name> (defaults to 'API_KEY')"
+    echo "  # optional: export API_ENV_VAR_NAME=<env-var-name> (defaults to the variable read by the app,"
+    echo "  #           WEATHER_API_KEY for weather-example and TWITTER_API_KEY for twitter-example)"
     echo "  ./configure_enclave.sh <APP>"
     echo ""
     echo "Options:"
@@ -57,10 +58,15 @@ export AWS_DEFAULT_REGION="$REGION"
 # The default AMI for us-east-1. Change this if your region is different.
 AMI_ID="${AMI_ID:-ami-085ad6ae776d8f09c}"
 
-# Environment variable name for our secret; default is 'API_KEY'
-API_ENV_VAR_NAME="${API_ENV_VAR_NAME:-API_KEY}"
-
 ENCLAVE_APP="${1}"
+
+# Environment variable name for our secret; defaults to the variable the app reads.
+case "$ENCLAVE_APP" in
+    weather-example) DEFAULT_API_ENV_VAR_NAME="WEATHER_API_KEY" ;;
+    twitter-example) DEFAULT_API_ENV_VAR_NAME="TWITTER_API_KEY" ;;
+    *) DEFAULT_API_ENV_VAR_NAME="API_KEY" ;;
+esac
+API_ENV_VAR_NAME="${API_ENV_VAR_NAME:-$DEFAULT_API_ENV_VAR_NAME}"
 ALLOWLIST_PATH="src/nautilus-server/src/apps/${ENCLAVE_APP}/allowed_endpoints.yaml"
 
 ############################
@@ -77,14 +83,16 @@ if [ -z "$KEY_PAIR" ]; then
     echo "Error: Environment variable KEY_PAIR is not set. Please export KEY_PAIR=<your-key-name>."
     exit 1
 fi
-# Check if yq is available
-if ! command -v yq >/dev/null 2>&1; then
-  echo "Error: yq is not installed."
-  echo "Please install yq (for example: 'brew install yq' on macOS or 'sudo apt-get install yq' on Ubuntu) and try again."
+# Check if cargo is available, to run nautilus-endpoints
+if ! command -v cargo >/dev/null 2>&1; then
+  echo "Error: cargo is not installed."
+  echo "Please install Rust (for example from https://rustup.rs) and try again."
   exit 1
 fi
 
-#echo "yq is installed. Proceeding..."
+# Generates the forwarding of the allowed endpoints for both run.sh and the
+# parent instance, see src/nautilus-endpoints. A small crate, it does not build the server.
+NAUTILUS_ENDPOINTS="cargo run --quiet --manifest-path src/nautilus-endpoints/Cargo.toml --"
 
 ############################
 # Set the EC2 Instance Name
@@ -107,25 +115,26 @@ echo "Instance will be named: $FINAL_INSTANCE_NAME"
 # Read endpoints from allowed_endpoints.yaml
 #########################################
 if [ -f "$ALLOWLIST_PATH" ]; then
-    # Use a small Python snippet to parse the YAML and emit space-separated endpoints
-    ENDPOINTS=$(yq e '.endpoints | join(" ")' $ALLOWLIST_PATH 2>/dev/null)
-    if [ -n "$ENDPOINTS" ]; then
-        echo "Endpoints found in $ALLOWLIST_PATH (before region patching):"
-        echo "$ENDPOINTS"
-
-        # Replace any existing region (like us-east-1, us-west-2, etc.) in kms.* / secretsmanager.* with the user-provided $REGION.
-        # This way, if $REGION=us-west-2, you'll get kms.us-west-2.amazonaws.com etc.
-        ENDPOINTS=$(echo "$ENDPOINTS" \
-          | sed "s|kms\.[^.]*\.amazonaws\.com|kms.$REGION.amazonaws.com|g" \
-          | sed "s|secretsmanager\.[^.]*\.amazonaws\.com|secretsmanager.$REGION.amazonaws.com|g")
-        echo "Endpoints after region patching:"
-        echo "$ENDPOINTS"
-    else
-        echo "No endpoints found in $ALLOWLIST_PATH. Continuing without additional endpoints."
+    # Replace any existing region (like us-east-1, us-west-2, etc.) in kms.* / secretsmanager.* with the user-provided $REGION.
+    # This way, if $REGION=us-west-2, you'll get kms.us-west-2.amazonaws.com etc. The patched allowlist is written to a
+    # temporary copy, the committed one is left untouched.
+    ALLOWLIST_COPY=$(mktemp)
+    trap 'rm -f "$ALLOWLIST_COPY"' EXIT
+    sed -E \
+      -e "s/kms\.[a-z0-9-]+\.amazonaws\.com/kms.$REGION.amazonaws.com/g" \
+      -e "s/secretsmanager\.[a-z0-9-]+\.amazonaws\.com/secretsmanager.$REGION.amazonaws.com/g" \
+      "$ALLOWLIST_PATH" > "$ALLOWLIST_COPY" || exit 1
+    if ! cmp -s "$ALLOWLIST_PATH" "$ALLOWLIST_COPY"; then
+        # The enclave image bakes in the committed allowlist, its egress only allows the hosts listed there.
+        echo "Warning: $ALLOWLIST_PATH lists AWS hosts of another region than $REGION."
+        echo "The hosts of $REGION are forwarded, update the allowlist to $REGION before building the enclave image."
     fi
+    ALLOWLIST_ARGS="$ALLOWLIST_COPY"
+    echo "Endpoints found in $ALLOWLIST_PATH:"
+    $NAUTILUS_ENDPOINTS hosts $ALLOWLIST_ARGS || exit 1
 else
     echo "$ALLOWLIST_PATH not found. Continuing without additional endpoints."
-    ENDPOINTS=""
+    ALLOWLIST_ARGS=""
 fi
 
 #########################################
@@ -399,27 +408,12 @@ else
 # Seal example: create empty secrets.json (required by run.sh)\\
 echo 'Creating empty secrets.json for seal example...'\\
 echo '{}' > secrets.json\\
-" expose_enclave.sh
-            
-            # Expose port 3001 for localhost-only access to seal init endpoint
-            sed -i '' "/socat TCP4-LISTEN:3000,reuseaddr,fork VSOCK-CONNECT:\$ENCLAVE_CID:3000 &/a\\
-\\
-# Seal example: Expose port 3001 for localhost-only access to init endpoint\\
-echo \"Exposing seal init endpoint on localhost:3001...\"\\
-socat TCP4-LISTEN:3001,bind=127.0.0.1,reuseaddr,fork VSOCK-CONNECT:\$ENCLAVE_CID:3001 &\\
 " expose_enclave.sh
         else
             sed -i "/# Secrets-block/a\\
 # Seal example: create empty secrets.json (required by run.sh)\\
 echo 'Creating empty secrets.json for seal example...'\\
 echo '{}' > secrets.json" expose_enclave.sh
-            
-            # Expose port 3001 for localhost-only access to seal init endpoint
-            sed -i "/socat TCP4-LISTEN:3000,reuseaddr,fork VSOCK-CONNECT:\$ENCLAVE_CID:3000 &/a\\
-\\
-# Seal example: Expose port 3001 for localhost-only access to init endpoint\\
-echo \"Exposing seal init endpoint on localhost:3001...\"\\
-socat TCP4-LISTEN:3001,bind=127.0.0.1,reuseaddr,fork VSOCK-CONNECT:\$ENCLAVE_CID:3001 &" expose_enclave.sh
         fi
     else
         # Regular no-secret configuration
@@ -469,13 +463,6 @@ echo 'source $HOME/.cargo/env' >> /home/ec2-user/.bashrc
 EOF
 fi
 
-# Append endpoint configuration to the vsock-proxy YAML if endpoints were provided.
-if [ -n "$ENDPOINTS" ]; then
-    for ep in $ENDPOINTS; do
-        echo "echo \"- {address: $ep, port: 443}\" | sudo tee -a /etc/nitro_enclaves/vsock-proxy.yaml" >> user-data.sh
-    done
-fi
-
 # Continue the user-data script
 cat <<'EOF' >> user-data.sh
 # Stop the allocator so we can modify its configuration
@@ -493,106 +480,17 @@ sudo systemctl start nitro-enclaves-allocator.service && sudo systemctl enable n
 # Restart vsock-proxy processes for various endpoints.
 EOF
 
-# Append additional vsock-proxy commands for each extra endpoint.
-if [ -n "$ENDPOINTS" ]; then
-    PORT=8101
-    for ep in $ENDPOINTS; do
-        echo "vsock-proxy $PORT $ep 443 --config /etc/nitro_enclaves/vsock-proxy.yaml &" >> user-data.sh
-        PORT=$((PORT+1))
-    done
-fi
+# Allow each endpoint in the vsock-proxy YAML and start a vsock-proxy for it,
+# on the vsock port its traffic forwarder in run.sh connects to.
+$NAUTILUS_ENDPOINTS vsock-proxy $ALLOWLIST_ARGS >> user-data.sh || exit 1
 
 ###################################################################
 # Fix src/nautilus-server/run.sh to add endpoint + forwarders
 ###################################################################
-ip=64
-endpoints_config=""
-for ep in $ENDPOINTS; do
-    endpoints_config="${endpoints_config}echo \"127.0.0.${ip}   ${ep}\" >> /etc/hosts"$'\n'
-    ip=$((ip+1))
-done
-
-echo "Adding the following endpoint configuration to src/nautilus-server/run.sh:"
-echo "$endpoints_config"
-
-# Remove any existing endpoint lines (except the first localhost line)
-if [[ "$(uname)" == "Darwin" ]]; then
-    # Remove only the IP mapping lines, preserving comments
-    sed -i '' '/echo "127.0.0.[0-9]*   .*" >> \/etc\/hosts/d' src/nautilus-server/run.sh
-    # Restore the localhost line if it was removed
-    if ! grep -q "echo \"127.0.0.1   localhost\" > /etc/hosts" src/nautilus-server/run.sh; then
-        sed -i '' '/# Add a hosts record/a\
-echo "127.0.0.1   localhost" > /etc/hosts' src/nautilus-server/run.sh
-    fi
-else
-    # Remove only the IP mapping lines, preserving comments
-    sed -i '/echo "127.0.0.[0-9]*   .*" >> \/etc\/hosts/d' src/nautilus-server/run.sh
-    # Restore the localhost line if it was removed
-    if ! grep -q "echo \"127.0.0.1   localhost\" > /etc/hosts" src/nautilus-server/run.sh; then
-        sed -i '/# Add a hosts record/a\echo "127.0.0.1   localhost" > /etc/hosts' src/nautilus-server/run.sh
-    fi
-fi
-
-# Add the new endpoint configuration
-tmp_hosts="/tmp/endpoints_config.txt"
-echo "$endpoints_config" > "$tmp_hosts"
-
-# Insert after the localhost line
-if [[ "$(uname)" == "Darwin" ]]; then
-    sed -i '' "/echo \"127.0.0.1   localhost\" > \/etc\/hosts/ r $tmp_hosts" src/nautilus-server/run.sh
-else
-    sed -i "/echo \"127.0.0.1   localhost\" > \/etc\/hosts/ r $tmp_hosts" src/nautilus-server/run.sh
-fi
-rm "$tmp_hosts"
-
-ip_forwarder=64
-port_forwarder=8101
-traffic_config=""
-for ep in $ENDPOINTS; do
-    traffic_config="${traffic_config}python3 /traffic_forwarder.py 127.0.0.${ip_forwarder} 443 3 ${port_forwarder} &"$'\n'
-    ip_forwarder=$((ip_forwarder+1))
-    port_forwarder=$((port_forwarder+1))
-done
-
-echo "Adding the following traffic forwarder configuration to src/nautilus-server/run.sh:"
-echo "$traffic_config"
-
-# Remove any existing traffic forwarder lines
-if [[ "$(uname)" == "Darwin" ]]; then
-    sed -i '' '/python3 \/traffic_forwarder.py/d' src/nautilus-server/run.sh
-else
-    sed -i '/python3 \/traffic_forwarder.py/d' src/nautilus-server/run.sh
-fi
+$NAUTILUS_ENDPOINTS run-sh src/nautilus-server/run.sh $ALLOWLIST_ARGS || exit 1
 
-# Add the new traffic forwarder configuration
-tmp_traffic="/tmp/traffic_config.txt"
-echo "$traffic_config" > "$tmp_traffic"
-
-if [[ "$(uname)" == "Darwin" ]]; then
-    sed -i '' "/# Traffic-forwarder-block/ r $tmp_traffic" src/nautilus-server/run.sh
-else
-    sed -i "/# Traffic-forwarder-block/ r $tmp_traffic" src/nautilus-server/run.sh
-fi
-rm "$tmp_traffic"
-
-echo "updated run.sh"
-
-# Add seal-specific vsock listener for port 3001
-if [ "$IS_SEAL_EXAMPLE" = true ]; then
-    echo "Adding seal-specific port 3001 vsock listener to run.sh..."
-    if [[ "$(uname)" == "Darwin" ]]; then
-        sed -i '' '/socat VSOCK-LISTEN:3000,reuseaddr,fork TCP:localhost:3000 &/a\
-\
-# For seal-example: Listen on VSOCK Port 3001 and forward to localhost 3001\
-socat VSOCK-LISTEN:3001,reuseaddr,fork TCP:localhost:3001 &' src/nautilus-server/run.sh
-    else
-        sed -i '/socat VSOCK-LISTEN:3000,reuseaddr,fork TCP:localhost:3000 &/a\
-\
-# For seal-example: Listen on VSOCK Port 3001 and forward to localhost 3001\
-socat VSOCK-LISTEN:3001,reuseaddr,fork TCP:localhost:3001 &' src/nautilus-server/run.sh
-    fi
-    echo "Added port 3001 vsock listener for seal example"
-fi
+# Port 3001 (host-only server for metrics and the seal bootstrap) is always
+# forwarded by run.sh and exposed on localhost by expose_enclave.sh.
 
 ############################
 # Create or Use Security Group
@@ -24,4 +24,7 @@ echo '{}' > secrets.json
 cat secrets.json | socat - VSOCK-CONNECT:$ENCLAVE_CID:7777
 socat TCP4-LISTEN:3000,reuseaddr,fork VSOCK-CONNECT:$ENCLAVE_CID:3000 &
 
+# Expose the host-only server (metrics, bootstrap routes) on localhost:3001 only
+socat TCP4-LISTEN:3001,bind=127.0.0.1,reuseaddr,fork VSOCK-CONNECT:$ENCLAVE_CID:3001 &
+
 # Additional port configurations will be added here by configure_enclave.sh if needed
@@ -0,0 +1,411 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+// Verification of the aggregate BLS12-381 signatures of enclave replicas, so
+// that an app requires k-of-n replicas to agree with a single signature check.
+// Replicas run with `bls_signing` and their signatures are aggregated by
+// `nautilus-aggregator`, see `src/nautilus-server/src/aggregator.rs`.
+
+module enclave::bls_committee;
+
+use enclave::enclave::{Self, Cap, Enclave};
+use std::bcs;
+use sui::bls12381::{Self, bls12381_min_sig_verify};
+use sui::group_ops;
+
+const EInvalidThreshold: u64 = 0;
+const EInvalidMembers: u64 = 1;
+const EInvalidBlsKey: u64 = 2;
+
+// Intent scope the enclave signs its BLS public key under.
+const BLS_KEY_INTENT: u8 = 3;
+
+// BLS12-381 public key of a replica, signed by its enclave key with timestamp
+// 0. Matches `BlsKey` in `src/nautilus-types/src/lib.rs`.
+public struct BlsKey has copy, drop {
+    public_key: vector<u8>,
+}
+
+// Deployment the replicas sign for, signed before the intent message when a
+// domain is configured. Names the committee rather than the `Enclave` of each
+// replica, so that all replicas sign the same bytes. Matches
+// `CommitteeSeparator` in `src/nautilus-types/src/lib.rs`.
+public struct CommitteeSeparator has copy, drop {
+    chain_id: vector<u8>,
+    package_id: address,
+    committee_id: address,
+}
+
+// BLS12-381 public keys of the replicas, in the order of the signer bitmap.
+// Each key is signed by the key of a registered `Enclave`, see `add_member`.
+public struct Committee<phantom T> has key {
+    id: UID,
+    members: vector<vector<u8>>,
+    threshold: u64,
+    version: u64, // Incremented when members change.
+}
+
+/// Create a committee without members. Aggregate signatures verify once at
+/// least `threshold` members are added with `add_member`.
+public fun create_committee<T>(_: &Cap<T>, threshold: u64, ctx: &mut TxContext) {
+    assert!(threshold > 0, EInvalidThreshold);
+    transfer::share_object(Committee<T> {
+        id: object::new(ctx),
+        members: vector[],
+        threshold,
+        version: 0,
+    });
+}
+
+/// Add the BLS public key of a replica as the last member. The key must be
+/// signed by the registered enclave, see `BlsKey`, so that only keys generated
+/// in attested enclaves are aggregated. This rules out rogue keys chosen from
+/// the keys of the other members.
+public fun add_member<T>(
+    committee: &mut Committee<T>,
+    _: &Cap<T>,
+    enclave: &Enclave<T>,
+    public_key: vector<u8>,
+    signature: &vector<u8>,
+) {
+    assert!(
+        enclave.verify_signature(BLS_KEY_INTENT, 0, BlsKey { public_key }, signature),
+        EInvalidBlsKey,
+    );
+    // Aborts if the key is not a valid G2 element.
+    bls12381::g2_from_bytes(&public_key);
+    assert!(!committee.members.contains(&public_key), EInvalidMembers);
+    committee.members.push_back(public_key);
+    committee.version = committee.version + 1;
+}
+
+/// Remove a member, e.g. once its replica stopped or rotated its key. The
+/// following members move down one position in the signer bitmap.
+public fun remove_member<T>(committee: &mut Committee<T>, _: &Cap<T>, public_key: vector<u8>) {
+    let (found, index) = committee.members.index_of(&public_key);
+    assert!(found, EInvalidMembers);
+    committee.members.remove(index);
+    committee.version = committee.version + 1;
+}
+
+public fun set_threshold<T>(committee: &mut Committee<T>, _: &Cap<T>, threshold: u64) {
+    assert!(threshold > 0, EInvalidThreshold);
+    committee.threshold = threshold;
+    committee.version = committee.version + 1;
+}
+
+/// Verify the aggregate signature of a response of replicas without domain
+/// over the bytes they sign, see `enclave::signing_payload`. Pass
+/// `option::none()` for the nonce and caller of unbound requests.
+public fun verify_aggregate<T, P: drop>(
+    committee: &Committee<T>,
+    intent_scope: u8,
+    timestamp_ms: u64,
+    payload: P,
+    nonce: Option<vector<u8>>,
+    caller: Option<address>,
+    signers: &vector<u8>,
+    signature: &vector<u8>,
+): bool {
+    let message = enclave::signing_payload(intent_scope, timestamp_ms, payload, nonce, caller);
+    committee.verify_aggregate_bytes(&message, signers, signature)
+}
+
+/// Verify the aggregate signature of a response of replicas configured with a
+/// domain and this committee as `committee_id`, see `committee_signing_payload`.
+public fun verify_domain_aggregate<T, P: drop>(
+    committee: &Committee<T>,
+    chain_id: vector<u8>,
+    package_id: address,
+    intent_scope: u8,
+    timestamp_ms: u64,
+    payload: P,
+    nonce: Option<vector<u8>>,
+    caller: Option<address>,
+    signers: &vector<u8>,
+    signature: &vector<u8>,
+): bool {
+    let message = committee_signing_payload(
+        chain_id,
+        package_id,
+        committee.id.to_address(),
+        intent_scope,
+        timestamp_ms,
+        payload,
+        nonce,
+        caller,
+    );
+    committee.verify_aggregate_bytes(&message, signers, signature)
+}
+
+/// Bytes signed by the BLS keys of replicas configured with a domain: the
+/// committee separator followed by `enclave::signing_payload`.
+public fun committee_signing_payload<P: drop>(
+    chain_id: vector<u8>,
+    package_id: address,
+    committee_id: address,
+    intent_scope: u8,
+    timestamp_ms: u64,
+    payload: P,
+    nonce: Option<vector<u8>>,
+    caller: Option<address>,
+): vector<u8> {
+    let separator = CommitteeSeparator { chain_id, package_id, committee_id };
+    let mut bytes = bcs::to_bytes(&separator);
+    bytes.append(enclave::signing_payload(intent_scope, timestamp_ms, payload, nonce, caller));
+    bytes
+}
+
+/// Verify that at least `threshold` members signed `message`. Bit `i % 8` of
+/// byte `i / 8` of `signers` is set if member `i` signed.
+public fun verify_aggregate_bytes<T>(
+    committee: &Committee<T>,
+    message: &vector<u8>,
+    signers: &vector<u8>,
+    signature: &vector<u8>,
+): bool {
+    let n = committee.members.length();
+    if (signers.length() != (n + 7) / 8) {
+        return false
+    };
+    let mut aggregate = bls12381::g2_identity();
+    let mut count = 0;
+    let mut i = 0;
+    while (i < signers.length() * 8) {
+        if ((signers[i / 8] >> ((i % 8) as u8)) & 1 == 1) {
+            // Bits past the last member must be unset.
+            if (i >= n) {
+                return false
+            };
+            let pk = bls12381::g2_from_bytes(&committee.members[i]);
+            aggregate = bls12381::g2_add(&aggregate, &pk);
+            count = count + 1;
+        };
+        i = i + 1;
+    };
+    count >= committee.threshold &&
+        bls12381_min_sig_verify(signature, group_ops::bytes(&aggregate), message)
+}
+
+public fun members<T>(committee: &Committee<T>): &vector<vector<u8>> {
+    &committee.members
+}
+
+public fun threshold<T>(committee: &Committee<T>): u64 {
+    committee.threshold
+}
+
+public fun version<T>(committee: &Committee<T>): u64 {
+    committee.version
+}
+
+#[test_only]
+public struct TEST_COMMITTEE has drop {}
+
+#[test]
+fun test_verify_aggregate() {
+    use sui::test_utils::destroy;
+
+    // Members with secret keys 1, 2 and 3, the signature of secret key k is k * H(m).
+    let g2 = bls12381::g2_generator();
+    let mut members = vector[];
+    let mut k = 1;
+    while (k <= 3) {
+        let pk = bls12381::g2_mul(&bls12381::scalar_from_u64(k), &g2);
+        members.push_back(*group_ops::bytes(&pk));
+        k = k + 1;
+    };
+    let mut ctx = tx_context::dummy();
+    let cap = enclave::new_cap(TEST_COMMITTEE {}, &mut ctx);
+    let committee = Committee<TEST_COMMITTEE> {
+        id: object::new(&mut ctx),
+        members,
+        threshold: 2,
+        version: 0,
+    };
+
+    let message = enclave::signing_payload(0, 1744038900000, 7u64, option::none(), option::none());
+    let h = bls12381::hash_to_g1(&message);
+    // Members 0 and 2 signed, 1 * H(m) + 3 * H(m).
+    let signature = *group_ops::bytes(&bls12381::g1_mul(&bls12381::scalar_from_u64(4), &h));
+    assert!(
+        committee.verify_aggregate(
+            0,
+            1744038900000,
+            7u64,
+            option::none(),
+            option::none(),
+            &x"05",
+            &signature,
+        ),
+        0,
+    );
+    // Wrong signers, message, bitmap length or bits past the last member.
+    assert!(!committee.verify_aggregate_bytes(&message, &x"03", &signature), 1);
+    assert!(!committee.verify_aggregate_bytes(&b"other", &x"05", &signature), 2);
+    assert!(!committee.verify_aggregate_bytes(&message, &x"0500", &signature), 3);
+    assert!(!committee.verify_aggregate_bytes(&message, &x"0d", &signature), 4);
+    // Below the threshold.
+    let signature = *group_ops::bytes(&h);
+    assert!(!committee.verify_aggregate_bytes(&message, &x"01", &signature), 5);
+
+    destroy(committee);
+    destroy(cap);
+}
+
+#[test]
+fun test_serde_bls_key() {
+    // Generated by `MovePayload::move_test` for `BlsKey` in nautilus-types.
+    let mut bytes = vector[3u8];
+    bytes.append(std::bcs::to_bytes(&0u64));
+    bytes.append(std::bcs::to_bytes(&BlsKey {
+        public_key: x"93e02b6052719f607dacd3a088274f65596bd0d09920b61ab5da61bbdc7f5049334cf11213945d57e5ac7d055d042b7e024aa2b2f08f0a91260805272dc51051c6e47ad4fa403b02b4510b647ae3d1770bac0326a805bbefd48056c8c121bdb8",
+    }));
+    assert!(bytes == x"0300000000000000006093e02b6052719f607dacd3a088274f65596bd0d09920b61ab5da61bbdc7f5049334cf11213945d57e5ac7d055d042b7e024aa2b2f08f0a91260805272dc51051c6e47ad4fa403b02b4510b647ae3d1770bac0326a805bbefd48056c8c121bdb8", 0);
+}
+
+#[test]
+fun test_add_member() {
+    use sui::test_utils::destroy;
+
+    let mut ctx = tx_context::dummy();
+    let cap = enclave::new_cap(TEST_COMMITTEE {}, &mut ctx);
+    let enclave = enclave::new_for_testing<TEST_COMMITTEE>(
+        x"03a107bff3ce10be1d70dd18e74bc09967e4d6309ba50d5f1ddc8664125531b8",
+        &mut ctx,
+    );
+    let mut committee = Committee<TEST_COMMITTEE> {
+        id: object::new(&mut ctx),
+        members: vector[],
+        threshold: 1,
+        version: 0,
+    };
+    // Public key of secret key 1, signed by the enclave as in `test_serde_bls_key`.
+    let public_key = *group_ops::bytes(&bls12381::g2_generator());
+    let signature = x"ec8e93566450a0d8f52d0c581c477389d408698b6ed394d572b4113b3377fc974e278bb18e8d002a5a71bbc1717dd0ec3bad940201dfc81f3dcb0ba3371f6d0e";
+    committee.add_member(&cap, &enclave, public_key, &signature);
+    assert!(committee.members == vector[public_key], 0);
+    assert!(committee.version == 1, 1);
+    committee.remove_member(&cap, public_key);
+    assert!(committee.members.is_empty(), 2);
+
+    destroy(committee);
+    destroy(cap);
+    enclave.destroy();
+}
+
+#[test]
+#[expected_failure(abort_code = EInvalidBlsKey)]
+fun test_add_member_unsigned() {
+    use sui::test_utils::destroy;
+
+    let mut ctx = tx_context::dummy();
+    let cap = enclave::new_cap(TEST_COMMITTEE {}, &mut ctx);
+    let enclave = enclave::new_for_testing<TEST_COMMITTEE>(
+        x"03a107bff3ce10be1d70dd18e74bc09967e4d6309ba50d5f1ddc8664125531b8",
+        &mut ctx,
+    );
+    let mut committee = Committee<TEST_COMMITTEE> {
+        id: object::new(&mut ctx),
+        members: vector[],
+        threshold: 1,
+        version: 0,
+    };
+    // The signature is over secret key 1, not 2.
+    let public_key = *group_ops::bytes(
+        &bls12381::g2_mul(&bls12381::scalar_from_u64(2), &bls12381::g2_generator()),
+    );
+    let signature = x"ec8e93566450a0d8f52d0c581c477389d408698b6ed394d572b4113b3377fc974e278bb18e8d002a5a71bbc1717dd0ec3bad940201dfc81f3dcb0ba3371f6d0e";
+    committee.add_member(&cap, &enclave, public_key, &signature);
+
+    destroy(committee);
+    destroy(cap);
+    enclave.destroy();
+}
+
+#[test]
+fun test_committee_signing_payload() {
+    // bytes should be consistent with rust test see `fn test_bls_signing_payload` in `src/nautilus-types/src/lib.rs`.
+    let bytes = committee_signing_payload(
+        x"4c78adac",
+        @0x2,
+        @0x4,
+        0,
+        1744038900000,
+        7u64,
+        option::none(),
+        option::none(),
+    );
+    assert!(
+        bytes == x"044c78adac000000000000000000000000000000000000000000000000000000000000000200000000000000000000000000000000000000000000000000000000000000040020b1d110960100000700000000000000",
+        0,
+    );
+}
+
+#[test]
+fun test_verify_domain_aggregate() {
+    use sui::test_utils::destroy;
+
+    let mut ctx = tx_context::dummy();
+    let committee = Committee<TEST_COMMITTEE> {
+        id: object::new(&mut ctx),
+        members: vector[*group_ops::bytes(&bls12381::g2_generator())],
+        threshold: 1,
+        version: 0,
+    };
+    let message = committee_signing_payload(
+        x"4c78adac",
+        @0x2,
+        committee.id.to_address(),
+        0,
+        1744038900000,
+        7u64,
+        option::none(),
+        option::none(),
+    );
+    // Signature of secret key 1, H(m).
+    let signature = *group_ops::bytes(&bls12381::hash_to_g1(&message));
+    assert!(
+        committee.verify_domain_aggregate(
+            x"4c78adac",
+            @0x2,
+            0,
+            1744038900000,
+            7u64,
+            option::none(),
+            option::none(),
+            &x"01",
+            &signature,
+        ),
+        0,
+    );
+    // Another package, or the bytes without separator.
+    assert!(
+        !committee.verify_domain_aggregate(
+            x"4c78adac",
+            @0x3,
+            0,
+            1744038900000,
+            7u64,
+            option::none(),
+            option::none(),
+            &x"01",
+            &signature,
+        ),
+        1,
+    );
+    assert!(
+        !committee.verify_aggregate(
+            0,
+            1744038900000,
+            7u64,
+            option::none(),
+            option::none(),
+            &x"01",
+            &signature,
+        ),
+        2,
+    );
+
+    destroy(committee);
+}
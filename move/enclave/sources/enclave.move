@@ -7,6 +7,8 @@ module enclave::enclave;
 
 use std::bcs;
 use std::string::String;
+use sui::ecdsa_k1;
+use sui::ecdsa_r1;
 use sui::ed25519;
 use sui::nitro_attestation::NitroAttestationDocument;
 
@@ -16,6 +18,19 @@ const EInvalidPCRs: u64 = 0;
 const EInvalidConfigVersion: u64 = 1;
 const EInvalidCap: u64 = 2;
 const EInvalidOwner: u64 = 3;
+const EInvalidScheme: u64 = 4;
+
+// Signature schemes of the enclave keys, matching `SignatureScheme::flag` in
+// `src/nautilus-types/src/lib.rs`. The ECDSA schemes sign the SHA-256 hash of
+// the payload as r || s, the recoverable one its Keccak-256 hash as r || s || v.
+const ED25519: u8 = 0;
+const SECP256K1: u8 = 1;
+const SECP256R1: u8 = 2;
+const SECP256K1_RECOVERABLE: u8 = 3;
+
+// Hash flags of `sui::ecdsa_k1` and `sui::ecdsa_r1`.
+const KECCAK256: u8 = 0;
+const SHA256: u8 = 1;
 
 // PCR0: Enclave image file
 // PCR1: Enclave Kernel
@@ -40,6 +55,7 @@ public struct EnclaveConfig<phantom T> has key {
 public struct Enclave<phantom T> has key {
     id: UID,
     pk: vector<u8>,
+    scheme: u8, // Signature scheme of pk, see ED25519.
     config_version: u64, // Points to the EnclaveConfig's version.
     owner: address,
 }
@@ -56,6 +72,29 @@ public struct IntentMessage<T: drop> has copy, drop {
     payload: T,
 }
 
+// Client nonce and caller address of a request, signed by the enclave after
+// the intent message. Matches `RequestBinding` in `src/nautilus-types/src/lib.rs`.
+public struct RequestBinding has copy, drop {
+    nonce: Option<vector<u8>>,
+    caller: Option<address>,
+}
+
+// Deployment an enclave signs for, signed before the intent message when a
+// domain is configured. Matches `DomainSeparator` in `src/nautilus-types/src/lib.rs`.
+public struct DomainSeparator has copy, drop {
+    chain_id: vector<u8>,
+    package_id: address,
+    enclave_id: address,
+}
+
+// Signed by the enclave under intent scope 2 for a request it evaluated and
+// found invalid, when configured with `signed_failures`. Matches
+// `FailureAttestation` in `src/nautilus-types/src/lib.rs`.
+public struct FailureAttestation has copy, drop {
+    code: String,
+    request_hash: vector<u8>,
+}
+
 /// Create a new `Cap` using a `witness` T from a module.
 public fun new_cap<T: drop>(_: T, ctx: &mut TxContext): Cap<T> {
     Cap {
@@ -82,16 +121,31 @@ public fun create_enclave_config<T: drop>(
     transfer::share_object(enclave_config);
 }
 
+/// Register an enclave with an Ed25519 key, the default `signature_scheme`.
 public fun register_enclave<T>(
     enclave_config: &EnclaveConfig<T>,
     document: NitroAttestationDocument,
     ctx: &mut TxContext,
+) {
+    register_enclave_with_scheme(enclave_config, document, ED25519, ctx);
+}
+
+/// Register an enclave whose key uses `scheme`, the `key_scheme` bound in the
+/// user_data of the attestation. Aborts if the key does not have the length of
+/// the scheme. The verify functions below check signatures under this scheme.
+public fun register_enclave_with_scheme<T>(
+    enclave_config: &EnclaveConfig<T>,
+    document: NitroAttestationDocument,
+    scheme: u8,
+    ctx: &mut TxContext,
 ) {
     let pk = enclave_config.load_pk(&document);
+    assert!(is_valid_key(scheme, &pk), EInvalidScheme);
 
     let enclave = Enclave<T> {
         id: object::new(ctx),
         pk,
+        scheme,
         config_version: enclave_config.version,
         owner: ctx.sender(),
     };
@@ -108,7 +162,94 @@ public fun verify_signature<T, P: drop>(
 ): bool {
     let intent_message = create_intent_message(intent_scope, timestamp_ms, payload);
     let payload = bcs::to_bytes(&intent_message);
-    return ed25519::ed25519_verify(signature, &enclave.pk, &payload)
+    return enclave.verify_bytes(signature, &payload)
+}
+
+/// Verify a response signed for a request with a client nonce and/or a caller
+/// address. The caller can check the sender and record the nonce to accept
+/// each response only once. With neither, the binding is not signed and this
+/// is the same as `verify_signature`.
+public fun verify_bound_signature<T, P: drop>(
+    enclave: &Enclave<T>,
+    intent_scope: u8,
+    timestamp_ms: u64,
+    payload: P,
+    nonce: Option<vector<u8>>,
+    caller: Option<address>,
+    signature: &vector<u8>,
+): bool {
+    let payload = signing_payload(intent_scope, timestamp_ms, payload, nonce, caller);
+    return enclave.verify_bytes(signature, &payload)
+}
+
+/// Verify a response signed by an enclave configured with a domain, made of
+/// the chain id, the package id of the app and the id of this `Enclave`
+/// object. Pass `option::none()` for the nonce and caller of unbound requests.
+public fun verify_domain_signature<T, P: drop>(
+    enclave: &Enclave<T>,
+    chain_id: vector<u8>,
+    package_id: address,
+    intent_scope: u8,
+    timestamp_ms: u64,
+    payload: P,
+    nonce: Option<vector<u8>>,
+    caller: Option<address>,
+    signature: &vector<u8>,
+): bool {
+    let bytes = domain_signing_payload(
+        chain_id,
+        package_id,
+        enclave.id.to_address(),
+        intent_scope,
+        timestamp_ms,
+        payload,
+        nonce,
+        caller,
+    );
+    return enclave.verify_bytes(signature, &bytes)
+}
+
+/// Bytes signed by an enclave configured with a domain: the domain separator
+/// followed by `signing_payload`.
+public fun domain_signing_payload<P: drop>(
+    chain_id: vector<u8>,
+    package_id: address,
+    enclave_id: address,
+    intent_scope: u8,
+    timestamp_ms: u64,
+    payload: P,
+    nonce: Option<vector<u8>>,
+    caller: Option<address>,
+): vector<u8> {
+    let domain = DomainSeparator { chain_id, package_id, enclave_id };
+    let mut bytes = bcs::to_bytes(&domain);
+    bytes.append(signing_payload(intent_scope, timestamp_ms, payload, nonce, caller));
+    bytes
+}
+
+/// Bytes signed by the enclave for a response without domain: the intent
+/// message, followed by the request binding if a nonce or caller is set. These
+/// are also the leaves of the Merkle tree of a batch, see `enclave::merkle`.
+public fun signing_payload<P: drop>(
+    intent_scope: u8,
+    timestamp_ms: u64,
+    payload: P,
+    nonce: Option<vector<u8>>,
+    caller: Option<address>,
+): vector<u8> {
+    let mut bytes = bcs::to_bytes(&create_intent_message(intent_scope, timestamp_ms, payload));
+    if (nonce.is_some() || caller.is_some()) {
+        bytes.append(bcs::to_bytes(&RequestBinding { nonce, caller }));
+    };
+    bytes
+}
+
+/// Payload of a failure attestation, to verify with intent scope 2 and any of
+/// the verify functions above, e.g. to settle a dispute over a request that
+/// the enclave rejected. `request_hash` is the sha256 hash of the BCS bytes of
+/// the request payload.
+public fun failure_attestation(code: String, request_hash: vector<u8>): FailureAttestation {
+    FailureAttestation { code, request_hash }
 }
 
 public fun update_pcrs<T: drop>(
@@ -144,6 +285,10 @@ public fun pk<T>(enclave: &Enclave<T>): &vector<u8> {
     &enclave.pk
 }
 
+public fun scheme<T>(enclave: &Enclave<T>): u8 {
+    enclave.scheme
+}
+
 public fun destroy_old_enclave<T>(e: Enclave<T>, config: &EnclaveConfig<T>) {
     assert!(e.config_version < config.version, EInvalidConfigVersion);
     let Enclave { id, .. } = e;
@@ -166,6 +311,36 @@ fun load_pk<T>(enclave_config: &EnclaveConfig<T>, document: &NitroAttestationDoc
     (*document.public_key()).destroy_some()
 }
 
+// Ed25519 keys are 32 bytes, ECDSA keys are 33 bytes compressed points.
+fun is_valid_key(scheme: u8, pk: &vector<u8>): bool {
+    if (scheme == ED25519) {
+        pk.length() == 32
+    } else if (scheme == SECP256K1 || scheme == SECP256R1 || scheme == SECP256K1_RECOVERABLE) {
+        pk.length() == 33
+    } else {
+        false
+    }
+}
+
+// Verify a signature of the enclave over `bytes` under the scheme of its key.
+fun verify_bytes<T>(enclave: &Enclave<T>, signature: &vector<u8>, bytes: &vector<u8>): bool {
+    let scheme = enclave.scheme;
+    if (scheme == ED25519) {
+        ed25519::ed25519_verify(signature, &enclave.pk, bytes)
+    } else if (scheme == SECP256K1) {
+        ecdsa_k1::secp256k1_verify(signature, &enclave.pk, bytes, SHA256)
+    } else if (scheme == SECP256R1) {
+        ecdsa_r1::secp256r1_verify(signature, &enclave.pk, bytes, SHA256)
+    } else if (scheme == SECP256K1_RECOVERABLE && signature.length() == 65) {
+        // The recovery id v is only needed to recover the key, e.g. by `ecrecover`.
+        let mut rs = *signature;
+        rs.pop_back();
+        ecdsa_k1::secp256k1_verify(&rs, &enclave.pk, bytes, KECCAK256)
+    } else {
+        false
+    }
+}
+
 fun to_pcrs(document: &NitroAttestationDocument): Pcrs {
     let pcrs = document.pcrs();
     Pcrs(*pcrs[0].value(), *pcrs[1].value(), *pcrs[2].value())
@@ -179,6 +354,26 @@ fun create_intent_message<P: drop>(intent: u8, timestamp_ms: u64, payload: P): I
     }
 }
 
+#[test_only]
+public fun new_for_testing<T>(pk: vector<u8>, ctx: &mut TxContext): Enclave<T> {
+    new_for_testing_with_scheme(pk, ED25519, ctx)
+}
+
+#[test_only]
+public fun new_for_testing_with_scheme<T>(
+    pk: vector<u8>,
+    scheme: u8,
+    ctx: &mut TxContext,
+): Enclave<T> {
+    Enclave {
+        id: object::new(ctx),
+        pk,
+        scheme,
+        config_version: 0,
+        owner: ctx.sender(),
+    }
+}
+
 #[test_only]
 public fun destroy<T>(enclave: Enclave<T>) {
     let Enclave { id, .. } = enclave;
@@ -207,3 +402,155 @@ fun test_serde() {
     let bytes = bcs::to_bytes(&signing_payload);
     assert!(bytes == x"0020b1d110960100000d53616e204672616e636973636f0d00000000000000", 0);
 }
+
+#[test]
+fun test_serde_binding() {
+    // serialization should be consistent with rust test see `fn test_request_binding` in `src/nautilus-types/src/lib.rs`.
+    let signing_payload = create_intent_message(
+        0,
+        1744038900000,
+        SigningPayload {
+            location: b"San Francisco".to_string(),
+            temperature: 13,
+        },
+    );
+    let mut bytes = bcs::to_bytes(&signing_payload);
+    bytes.append(
+        bcs::to_bytes(&RequestBinding {
+            nonce: option::some(x"0102"),
+            caller: option::some(@0x1),
+        }),
+    );
+    assert!(
+        bytes == x"0020b1d110960100000d53616e204672616e636973636f0d0000000000000001020102010000000000000000000000000000000000000000000000000000000000000001",
+        0,
+    );
+}
+
+#[test]
+fun test_serde_domain() {
+    // serialization should be consistent with rust test see `fn test_domain_separator` in `src/nautilus-types/src/lib.rs`.
+    let domain = DomainSeparator {
+        chain_id: x"4c78adac",
+        package_id: @0x2,
+        enclave_id: @0x3,
+    };
+    assert!(
+        bcs::to_bytes(&domain) == x"044c78adac00000000000000000000000000000000000000000000000000000000000000020000000000000000000000000000000000000000000000000000000000000003",
+        0,
+    );
+}
+
+#[test]
+fun test_serde_failure_attestation() {
+    // Generated by `MovePayload::move_test` for `FailureAttestation` in nautilus-types.
+    let mut bytes = vector[2u8];
+    bytes.append(std::bcs::to_bytes(&1744038900000u64));
+    bytes.append(std::bcs::to_bytes(&FailureAttestation {
+        code: b"unprocessable".to_string(),
+        request_hash: x"5c8cdabeffd4c2c113e382834a78a0b31738a2aceac7dea1daa8c64ed26b28c2",
+    }));
+    assert!(bytes == x"0220b1d110960100000d756e70726f6365737361626c65205c8cdabeffd4c2c113e382834a78a0b31738a2aceac7dea1daa8c64ed26b28c2", 0);
+}
+
+#[test]
+fun test_verify_bound_signature_unbound() {
+    // An unbound response only signs the intent message, see `signing_payload`.
+    let mut ctx = tx_context::dummy();
+    let enclave = Enclave<SigningPayload> {
+        id: object::new(&mut ctx),
+        pk: x"03a107bff3ce10be1d70dd18e74bc09967e4d6309ba50d5f1ddc8664125531b8",
+        scheme: ED25519,
+        config_version: 0,
+        owner: @0x0,
+    };
+    let payload = SigningPayload {
+        location: b"San Francisco".to_string(),
+        temperature: 13,
+    };
+    // Signature of the bytes of `test_serde`.
+    let signature = x"be2f4e6345819bd09c1c1db65611a2c4fefdb3613912ced33bee20211b3666d5fdb1081a66ac50bfa8f0f7418c9d6d3fe76a9a0d41d37b1b94d0ecb77f74f109";
+    assert!(enclave.verify_signature(0, 1744038900000, payload, &signature), 0);
+    assert!(
+        enclave.verify_bound_signature(
+            0,
+            1744038900000,
+            payload,
+            option::none(),
+            option::none(),
+            &signature,
+        ),
+        1,
+    );
+    assert!(
+        !enclave.verify_bound_signature(
+            0,
+            1744038900000,
+            payload,
+            option::some(x"0102"),
+            option::none(),
+            &signature,
+        ),
+        2,
+    );
+    enclave.destroy();
+}
+
+#[test]
+fun test_verify_signature_schemes() {
+    // Signatures of the bytes of `test_serde` by the secret key 0x000102..1f,
+    // over their SHA-256 hash, or their Keccak-256 hash for the recoverable scheme.
+    let k1_pk = x"036d6caac248af96f6afa7f904f550253a0f3ef3f5aa2fe6838a95b216691468e2";
+    let k1_signature = x"366a11060f366737298eabd2a7f8e6c7071cd048241c62e4ed0c6695d5bed23a471e7996eb0d42e49166b25808743fb9a8c28fadb4f6e39d8c31282367b17d9f";
+    let r1_pk = x"027a593180860c4037c83c12749845c8ee1424dd297fadcb895e358255d2c7d2b2";
+    let r1_signature = x"0a871625782d012fbfb9fc1ec44aa1ea130a3fd12b67f835281722ff035364ce1b9d220c9fa8bedbc9239324335bfc9e8ef5f7ce92715199e35a519601822f55";
+    let recoverable_signature = x"22b7a34bdf74b99637c0865a8705230bdc72e8599a2fa291c5adea4ece8e709f1728653dfc43a04f325f0a7af2d9cd8cc3db9dc21b5d81064c98f8323e7a2de700";
+
+    assert!(verify_with_scheme(SECP256K1, k1_pk, k1_signature), 0);
+    assert!(verify_with_scheme(SECP256R1, r1_pk, r1_signature), 1);
+    assert!(verify_with_scheme(SECP256K1_RECOVERABLE, k1_pk, recoverable_signature), 2);
+    // The recovery id recovers the key from the Keccak-256 hash, as `ecrecover` does.
+    let bytes = signing_payload(
+        0,
+        1744038900000,
+        SigningPayload {
+            location: b"San Francisco".to_string(),
+            temperature: 13,
+        },
+        option::none(),
+        option::none(),
+    );
+    assert!(ecdsa_k1::secp256k1_ecrecover(&recoverable_signature, &bytes, KECCAK256) == k1_pk, 3);
+
+    // Signatures under another scheme or another hash fail.
+    assert!(!verify_with_scheme(SECP256K1_RECOVERABLE, k1_pk, k1_signature), 4);
+    assert!(!verify_with_scheme(SECP256K1, k1_pk, recoverable_signature), 5);
+    assert!(!verify_with_scheme(SECP256R1, k1_pk, k1_signature), 6);
+    assert!(!verify_with_scheme(ED25519, k1_pk, k1_signature), 7);
+}
+
+#[test]
+fun test_is_valid_key() {
+    let ed25519_pk = x"03a107bff3ce10be1d70dd18e74bc09967e4d6309ba50d5f1ddc8664125531b8";
+    let ecdsa_pk = x"036d6caac248af96f6afa7f904f550253a0f3ef3f5aa2fe6838a95b216691468e2";
+    assert!(is_valid_key(ED25519, &ed25519_pk), 0);
+    assert!(!is_valid_key(ED25519, &ecdsa_pk), 1);
+    assert!(is_valid_key(SECP256K1, &ecdsa_pk), 2);
+    assert!(is_valid_key(SECP256R1, &ecdsa_pk), 3);
+    assert!(is_valid_key(SECP256K1_RECOVERABLE, &ecdsa_pk), 4);
+    assert!(!is_valid_key(SECP256K1, &ed25519_pk), 5);
+    assert!(!is_valid_key(4, &ecdsa_pk), 6);
+}
+
+#[test_only]
+fun verify_with_scheme(scheme: u8, pk: vector<u8>, signature: vector<u8>): bool {
+    let mut ctx = tx_context::dummy();
+    let enclave = new_for_testing_with_scheme<SigningPayload>(pk, scheme, &mut ctx);
+    let payload = SigningPayload {
+        location: b"San Francisco".to_string(),
+        temperature: 13,
+    };
+    let verified = enclave.verify_signature(0, 1744038900000, payload, &signature);
+    enclave.destroy();
+    verified
+}
@@ -0,0 +1,227 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+// Verification of the Merkle proofs returned by the batch endpoints. A batch
+// is signed once over a `BatchRoot`, and each item is proven against the root.
+// Matches `src/nautilus-server/src/merkle.rs`.
+
+module enclave::merkle;
+
+use enclave::enclave::{Self, Enclave};
+use std::hash::sha2_256;
+use sui::hash::blake2b256;
+
+const SHA256: u8 = 0;
+const BLAKE2B256: u8 = 1;
+
+const LEAF_PREFIX: u8 = 0;
+const NODE_PREFIX: u8 = 1;
+
+// Intent scope the enclave signs batch roots under.
+const BATCH_ROOT_INTENT: u8 = 1;
+
+const EInvalidHash: u64 = 0;
+
+// Signed payload of a batch. Matches `BatchRoot` in `src/nautilus-server/src/batch.rs`.
+public struct BatchRoot has copy, drop {
+    root: vector<u8>,
+    count: u64,
+    hash: u8,
+}
+
+/// Verify the signature of a batch root of an enclave without domain, returning
+/// the root to verify items against.
+public fun verify_batch_root<T>(
+    enclave: &Enclave<T>,
+    timestamp_ms: u64,
+    root: vector<u8>,
+    count: u64,
+    hash: u8,
+    signature: &vector<u8>,
+): Option<BatchRoot> {
+    let batch = BatchRoot { root, count, hash };
+    if (enclave.verify_signature(BATCH_ROOT_INTENT, timestamp_ms, batch, signature)) {
+        option::some(batch)
+    } else {
+        option::none()
+    }
+}
+
+/// Verify the signature of a batch root of an enclave configured with a domain,
+/// see `enclave::verify_domain_signature`, returning the root to verify items
+/// against. The leaves of the batch never carry the domain separator.
+public fun verify_domain_batch_root<T>(
+    enclave: &Enclave<T>,
+    chain_id: vector<u8>,
+    package_id: address,
+    timestamp_ms: u64,
+    root: vector<u8>,
+    count: u64,
+    hash: u8,
+    signature: &vector<u8>,
+): Option<BatchRoot> {
+    let batch = BatchRoot { root, count, hash };
+    if (
+        enclave.verify_domain_signature(
+            chain_id,
+            package_id,
+            BATCH_ROOT_INTENT,
+            timestamp_ms,
+            batch,
+            option::none(),
+            option::none(),
+            signature,
+        )
+    ) {
+        option::some(batch)
+    } else {
+        option::none()
+    }
+}
+
+/// Verify that an item, as it would be signed alone by the enclave, is at
+/// `index` of the batch. Pass `option::none()` for the nonce and caller of
+/// unbound requests.
+public fun verify_item<P: drop>(
+    batch: &BatchRoot,
+    intent_scope: u8,
+    timestamp_ms: u64,
+    payload: P,
+    nonce: Option<vector<u8>>,
+    caller: Option<address>,
+    index: u64,
+    siblings: &vector<vector<u8>>,
+): bool {
+    let leaf = enclave::signing_payload(intent_scope, timestamp_ms, payload, nonce, caller);
+    verify_proof(batch.hash, &batch.root, leaf, index, batch.count, siblings)
+}
+
+/// Verify that `leaf` is at `index` of a tree of `count` leaves with `root`.
+/// A node without a sibling is promoted to the next level unchanged.
+public fun verify_proof(
+    hash: u8,
+    root: &vector<u8>,
+    leaf: vector<u8>,
+    index: u64,
+    count: u64,
+    siblings: &vector<vector<u8>>,
+): bool {
+    if (index >= count) return false;
+    let mut node = hash_with_prefix(hash, LEAF_PREFIX, leaf);
+    let mut index = index;
+    let mut width = count;
+    let mut i = 0;
+    while (width > 1) {
+        if (index % 2 == 1 || index + 1 < width) {
+            if (i >= siblings.length()) return false;
+            let mut pair = vector[];
+            if (index % 2 == 1) {
+                pair.append(siblings[i]);
+                pair.append(node);
+            } else {
+                pair.append(node);
+                pair.append(siblings[i]);
+            };
+            node = hash_with_prefix(hash, NODE_PREFIX, pair);
+            i = i + 1;
+        };
+        index = index / 2;
+        width = (width + 1) / 2;
+    };
+    i == siblings.length() && &node == root
+}
+
+public fun root(batch: &BatchRoot): &vector<u8> {
+    &batch.root
+}
+
+public fun count(batch: &BatchRoot): u64 {
+    batch.count
+}
+
+fun hash_with_prefix(hash: u8, prefix: u8, data: vector<u8>): vector<u8> {
+    let mut bytes = vector[prefix];
+    bytes.append(data);
+    if (hash == SHA256) {
+        sha2_256(bytes)
+    } else if (hash == BLAKE2B256) {
+        blake2b256(&bytes)
+    } else {
+        abort EInvalidHash
+    }
+}
+
+#[test]
+fun test_merkle() {
+    // roots should be consistent with rust test see `fn test_merkle_tree` in `src/nautilus-server/src/merkle.rs`.
+    let leaves = vector[b"a", b"b", b"c"];
+    let roots = vector[
+        x"36642e73c2540ab121e3a6bf9545b0a24982cd830eb13d3cd19de3ce6c021ec1",
+        x"17321db51c1ef3ec1f77e271aa300b4e5c6091708bcba37e46025774a26142ee",
+    ];
+    let mut hash = 0;
+    while (hash < 2) {
+        let root = &roots[hash as u64];
+        let a = hash_with_prefix(hash, LEAF_PREFIX, b"a");
+        let b = hash_with_prefix(hash, LEAF_PREFIX, b"b");
+        let c = hash_with_prefix(hash, LEAF_PREFIX, b"c");
+        let mut ab = a;
+        ab.append(b);
+        let ab = hash_with_prefix(hash, NODE_PREFIX, ab);
+
+        assert!(verify_proof(hash, root, leaves[0], 0, 3, &vector[b, c]), 0);
+        assert!(verify_proof(hash, root, leaves[1], 1, 3, &vector[a, c]), 1);
+        // The last leaf is promoted at the first level.
+        assert!(verify_proof(hash, root, leaves[2], 2, 3, &vector[ab]), 2);
+        assert!(!verify_proof(hash, root, leaves[2], 2, 4, &vector[ab]), 3);
+        assert!(!verify_proof(hash, root, b"d", 2, 3, &vector[ab]), 4);
+        assert!(!verify_proof(hash, root, leaves[0], 0, 3, &vector[b]), 5);
+        hash = hash + 1;
+    };
+}
+
+#[test]
+fun test_domain_batch_root() {
+    // bytes should be consistent with rust test see `fn test_batch_root_domain` in `src/nautilus-server/src/batch.rs`.
+    let root = x"abababababababababababababababababababababababababababababababab";
+    let bytes = enclave::domain_signing_payload(
+        x"4c78adac",
+        @0x2,
+        @0x3,
+        BATCH_ROOT_INTENT,
+        1744038900000,
+        BatchRoot { root, count: 3, hash: BLAKE2B256 },
+        option::none(),
+        option::none(),
+    );
+    assert!(
+        bytes == x"044c78adac000000000000000000000000000000000000000000000000000000000000000200000000000000000000000000000000000000000000000000000000000000030120b1d1109601000020abababababababababababababababababababababababababababababababab030000000000000001",
+        0,
+    );
+
+    // A root signed without domain verifies with `verify_batch_root` only.
+    let mut ctx = tx_context::dummy();
+    let enclave = enclave::new_for_testing<BatchRoot>(
+        x"03a107bff3ce10be1d70dd18e74bc09967e4d6309ba50d5f1ddc8664125531b8",
+        &mut ctx,
+    );
+    let signature = x"47da37a3e9614e9fcf6348e22bb4edec5251269f6bc3ab88beba4e6efd92d9a5cbac5c35d7367ae73371c618f0f13b4e9bc13d16a2356a3f27ea937078a7600b";
+    assert!(
+        verify_batch_root(&enclave, 1744038900000, root, 3, BLAKE2B256, &signature).is_some(),
+        1,
+    );
+    assert!(
+        verify_domain_batch_root(
+            &enclave,
+            x"4c78adac",
+            @0x2,
+            1744038900000,
+            root,
+            3,
+            BLAKE2B256,
+            &signature,
+        ).is_none(),
+        2,
+    );
+    enclave.destroy();
+}
@@ -96,4 +96,17 @@ public fun timestamp_ms(nft: &RandomNFT): u64 {
 public fun destroy_for_testing(nft: RandomNFT) {
     let RandomNFT { id, .. } = nft;
     id.delete();
-}
\ No newline at end of file
+}
+
+#[test]
+fun test_serde_random_response() {
+    // Generated by `MovePayload::move_test` for `RandomResponse` in nautilus-types.
+    let mut bytes = vector[0u8];
+    bytes.append(std::bcs::to_bytes(&1744038900000u64));
+    bytes.append(std::bcs::to_bytes(&RandomResponse {
+        random_number: 42u64,
+        min: 1u64,
+        max: 100u64,
+    }));
+    assert!(bytes == x"0020b1d110960100002a0000000000000001000000000000006400000000000000", 0);
+}
@@ -107,4 +107,16 @@ fun test_twitter_flow() {
     enclave.destroy();
     destroy(cap);
     test_scenario::end(scenario);
-}
\ No newline at end of file
+}
+
+#[test]
+fun test_serde_user_data() {
+    // Generated by `MovePayload::move_test` for `UserData` in nautilus-types.
+    let mut bytes = vector[0u8];
+    bytes.append(std::bcs::to_bytes(&1743989326143u64));
+    bytes.append(std::bcs::to_bytes(&UserData {
+        twitter_name: x"6d797374656e696e7465726e",
+        sui_address: x"101ce8865558e08408b83f60ee9e78843d03d547c850cbe12cb599e17833dd3e",
+    }));
+    assert!(bytes == x"003f41dd0d960100000c6d797374656e696e7465726e20101ce8865558e08408b83f60ee9e78843d03d547c850cbe12cb599e17833dd3e", 0);
+}
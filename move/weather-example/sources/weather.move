@@ -126,3 +126,15 @@ fun test_weather_flow() {
     destroy(cap);
     scenario.end();
 }
+
+#[test]
+fun test_serde_weather_response() {
+    // Generated by `MovePayload::move_test` for `WeatherResponse` in nautilus-types.
+    let mut bytes = vector[0u8];
+    bytes.append(std::bcs::to_bytes(&1744038900000u64));
+    bytes.append(std::bcs::to_bytes(&WeatherResponse {
+        location: b"San Francisco".to_string(),
+        temperature: 13u64,
+    }));
+    assert!(bytes == x"0020b1d110960100000d53616e204672616e636973636f0d00000000000000", 0);
+}
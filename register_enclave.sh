@@ -39,9 +39,18 @@ EOF
 )
 
 echo 'converted attestation'
+# Keys of another scheme than ed25519 are registered with the flag of their
+# scheme, e.g. SCHEME_FLAG=3 for secp256k1_recoverable, see enclave.move.
+REGISTER_FUNCTION=register_enclave
+SCHEME_ARG=
+if [ -n "$SCHEME_FLAG" ]; then
+    REGISTER_FUNCTION=register_enclave_with_scheme
+    SCHEME_ARG="${SCHEME_FLAG}u8"
+fi
+
 # Execute sui client command with the converted array and provided arguments
 sui client ptb --assign v "vector$ATTESTATION_ARRAY" \
     --move-call "0x2::nitro_attestation::load_nitro_attestation" v @0x6 \
     --assign result \
-    --move-call "${ENCLAVE_PACKAGE_ID}::enclave::register_enclave<${APP_PACKAGE_ID}::${MODULE_NAME}::${OTW_NAME}>" @${ENCLAVE_CONFIG_OBJECT_ID} result \
+    --move-call "${ENCLAVE_PACKAGE_ID}::enclave::${REGISTER_FUNCTION}<${APP_PACKAGE_ID}::${MODULE_NAME}::${OTW_NAME}>" @${ENCLAVE_CONFIG_OBJECT_ID} result $SCHEME_ARG \
     --gas-budget 100000000
\ No newline at end of file
@@ -0,0 +1,454 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+// Traffic forwarder of the enclave for one allowed endpoint. Listens on the
+// loopback address the endpoint resolves to in /etc/hosts and relays every
+// connection to the vsock-proxy of the endpoint on the parent instance.
+// Started by run.sh, e.g. `/forwarder api.weatherapi.com 127.0.0.64:443 3:8101 &`.
+
+use libc::{c_int, poll, pollfd, setsockopt, shutdown, timeval, AF_VSOCK, POLLIN, SHUT_WR};
+use libc::{SOL_SOCKET, SO_SNDTIMEO};
+use std::env;
+use std::fs::File;
+use std::io::{self, ErrorKind, Read, Write};
+use std::mem::size_of;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::process::exit;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use system::{dmesg, socket_connect};
+
+// Connections relayed at once, further connections are closed right away
+const MAX_CONNECTIONS: usize = 64;
+// Connections without traffic in either direction for this long are closed
+const IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+// Interval of the counters log, skipped when nothing changed
+const STATS_INTERVAL: Duration = Duration::from_secs(60);
+const BUFFER_SIZE: usize = 16 * 1024;
+// Backoff between attempts to listen, doubled after each failure up to the max
+const BIND_RETRY: Duration = Duration::from_secs(1);
+const BIND_RETRY_MAX: Duration = Duration::from_secs(30);
+// Backoff between attempts to accept, doubled after each failure up to the max
+const ACCEPT_RETRY: Duration = Duration::from_millis(100);
+const ACCEPT_RETRY_MAX: Duration = Duration::from_secs(5);
+
+// Counters of the endpoint, logged to the console every STATS_INTERVAL. They
+// are not exported to /metrics of the server, which runs in another process.
+#[derive(Default)]
+struct Counters {
+    active: AtomicUsize,
+    connections: AtomicU64,
+    rejected: AtomicU64,
+    // From the enclave to the endpoint
+    bytes_out: AtomicU64,
+    // From the endpoint to the enclave
+    bytes_in: AtomicU64,
+}
+
+impl Counters {
+    fn snapshot(&self) -> [u64; 4] {
+        [
+            self.connections.load(Ordering::Relaxed),
+            self.rejected.load(Ordering::Relaxed),
+            self.bytes_out.load(Ordering::Relaxed),
+            self.bytes_in.load(Ordering::Relaxed),
+        ]
+    }
+}
+
+// Slot of an active connection, released when the connection ends
+struct Slot(Arc<Counters>);
+
+impl Slot {
+    fn acquire(counters: &Arc<Counters>) -> Option<Slot> {
+        counters
+            .active
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |active| {
+                (active < MAX_CONNECTIONS).then_some(active + 1)
+            })
+            .ok()
+            .map(|_| Slot(counters.clone()))
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug)]
+struct Route {
+    host: String,
+    listen: SocketAddr,
+    cid: u32,
+    port: u32,
+}
+
+// Parse `<host> <listen address> <cid>:<port>`
+fn parse_args(args: &[String]) -> Result<Route, String> {
+    let [host, listen, vsock] = args else {
+        return Err("usage: forwarder <host> <listen address> <cid>:<port>".to_string());
+    };
+    let listen = listen
+        .parse()
+        .map_err(|e| format!("Invalid listen address {}: {}", listen, e))?;
+    let (cid, port) = vsock
+        .split_once(':')
+        .and_then(|(cid, port)| Some((cid.parse().ok()?, port.parse().ok()?)))
+        .ok_or_else(|| format!("Invalid vsock address {}, expected <cid>:<port>", vsock))?;
+    Ok(Route {
+        host: host.clone(),
+        listen,
+        cid,
+        port,
+    })
+}
+
+// Apply a send timeout to a socket, so that a peer not reading does not hold
+// a connection forever
+fn set_send_timeout(fd: RawFd, timeout: Duration) -> io::Result<()> {
+    let tv = timeval {
+        tv_sec: timeout.as_secs() as _,
+        tv_usec: 0,
+    };
+    if unsafe {
+        setsockopt(
+            fd,
+            SOL_SOCKET,
+            SO_SNDTIMEO,
+            &tv as *const timeval as *const _,
+            size_of::<timeval>() as _,
+        )
+    } < 0
+    {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// Relay bytes both ways until both sides are done sending or the connection is
+// idle for `idle_timeout`. A side reaching EOF is shut down for writing on the
+// other side, so that half-closed connections keep working. Uses a single
+// thread per connection.
+fn relay<C, U>(
+    mut client: C,
+    mut upstream: U,
+    counters: &Counters,
+    idle_timeout: Duration,
+) -> io::Result<()>
+where
+    C: Read + Write + AsRawFd,
+    U: Read + Write + AsRawFd,
+{
+    let mut buf = vec![0u8; BUFFER_SIZE];
+    // Whether the client and the upstream may still send data
+    let mut open = [true, true];
+    while open[0] || open[1] {
+        // Sides done sending are left out of the poll with a negative fd
+        let mut fds = [client.as_raw_fd(), upstream.as_raw_fd()].map(|fd| pollfd {
+            fd,
+            events: POLLIN,
+            revents: 0,
+        });
+        for (fd, open) in fds.iter_mut().zip(open) {
+            if !open {
+                fd.fd = -1;
+            }
+        }
+        let ready = unsafe { poll(fds.as_mut_ptr(), 2, idle_timeout.as_millis() as c_int) };
+        if ready < 0 {
+            let e = io::Error::last_os_error();
+            if e.kind() == ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(e);
+        }
+        if ready == 0 {
+            return Err(io::Error::new(ErrorKind::TimedOut, "Idle timeout"));
+        }
+        if fds[0].revents != 0 {
+            open[0] = copy(&mut client, &mut upstream, &mut buf, &counters.bytes_out)?;
+        }
+        if fds[1].revents != 0 {
+            open[1] = copy(&mut upstream, &mut client, &mut buf, &counters.bytes_in)?;
+        }
+    }
+    Ok(())
+}
+
+// Copy one read from `source` to `destination`, returning whether `source` is
+// still open
+fn copy(
+    source: &mut impl Read,
+    destination: &mut (impl Write + AsRawFd),
+    buf: &mut [u8],
+    bytes: &AtomicU64,
+) -> io::Result<bool> {
+    let n = match source.read(buf) {
+        Err(e) if e.kind() == ErrorKind::Interrupted => return Ok(true),
+        result => result?,
+    };
+    if n == 0 {
+        unsafe { shutdown(destination.as_raw_fd(), SHUT_WR) };
+        return Ok(false);
+    }
+    destination.write_all(&buf[..n])?;
+    bytes.fetch_add(n as u64, Ordering::Relaxed);
+    Ok(true)
+}
+
+// Relay one accepted connection to the vsock-proxy of the endpoint
+fn forward(route: &Route, client: TcpStream, counters: &Counters) -> io::Result<()> {
+    let fd =
+        socket_connect(AF_VSOCK, route.port, route.cid).map_err(|e| io::Error::other(e.message))?;
+    let upstream = unsafe { File::from_raw_fd(fd) };
+    client.set_write_timeout(Some(IDLE_TIMEOUT))?;
+    set_send_timeout(upstream.as_raw_fd(), IDLE_TIMEOUT)?;
+    relay(client, upstream, counters, IDLE_TIMEOUT)
+}
+
+fn log_stats(route: &Route, counters: &Counters) {
+    let mut last = [0; 4];
+    loop {
+        thread::sleep(STATS_INTERVAL);
+        let current = counters.snapshot();
+        if current == last {
+            continue;
+        }
+        let [connections, rejected, bytes_out, bytes_in] = current;
+        dmesg(format!(
+            "forwarder {}: {} connections ({} active, {} rejected), {} bytes out, {} bytes in",
+            route.host,
+            connections,
+            counters.active.load(Ordering::Relaxed),
+            rejected,
+            bytes_out,
+            bytes_in
+        ));
+        last = current;
+    }
+}
+
+// Listen on the address of the route, retrying with a backoff until it succeeds.
+// Nothing restarts the forwarder inside the enclave, so it must not exit when
+// the address is not available yet.
+fn bind(route: &Route) -> TcpListener {
+    let mut retry = BIND_RETRY;
+    loop {
+        match TcpListener::bind(route.listen) {
+            Ok(listener) => return listener,
+            Err(e) => {
+                eprintln!(
+                    "forwarder {}: failed to listen on {}: {}, retrying in {}s",
+                    route.host,
+                    route.listen,
+                    e,
+                    retry.as_secs()
+                );
+                thread::sleep(retry);
+                retry = (retry * 2).min(BIND_RETRY_MAX);
+            }
+        }
+    }
+}
+
+// Accept the next client, retrying with a backoff after each failure so that a
+// persistent error, e.g. EMFILE when out of file descriptors, does not spin.
+fn accept_next<T>(route: &Route, mut accept: impl FnMut() -> io::Result<T>) -> T {
+    let mut retry = ACCEPT_RETRY;
+    loop {
+        match accept() {
+            Ok(client) => return client,
+            Err(e) => {
+                eprintln!(
+                    "forwarder {}: failed to accept: {}, retrying in {}ms",
+                    route.host,
+                    e,
+                    retry.as_millis()
+                );
+                thread::sleep(retry);
+                retry = (retry * 2).min(ACCEPT_RETRY_MAX);
+            }
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let route = match parse_args(&args) {
+        Ok(route) => Arc::new(route),
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    };
+    let listener = bind(&route);
+    dmesg(format!(
+        "forwarder {}: {} -> vsock {}:{}",
+        route.host, route.listen, route.cid, route.port
+    ));
+
+    let counters = Arc::new(Counters::default());
+    {
+        let (route, counters) = (route.clone(), counters.clone());
+        thread::spawn(move || log_stats(&route, &counters));
+    }
+
+    loop {
+        let client = accept_next(&route, || listener.accept().map(|(client, _)| client));
+        // Dropping the client closes it when all the slots are taken
+        let Some(slot) = Slot::acquire(&counters) else {
+            counters.rejected.fetch_add(1, Ordering::Relaxed);
+            eprintln!(
+                "forwarder {}: {} connections active, closing new connection",
+                route.host, MAX_CONNECTIONS
+            );
+            continue;
+        };
+        counters.connections.fetch_add(1, Ordering::Relaxed);
+        let route = route.clone();
+        thread::spawn(move || {
+            if let Err(e) = forward(&route, client, &slot.0) {
+                eprintln!("forwarder {}: {}", route.host, e);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Shutdown;
+    use std::os::unix::net::UnixStream;
+    use std::time::Instant;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let route = parse_args(&args(&["api.weatherapi.com", "127.0.0.64:443", "3:8101"])).unwrap();
+        assert_eq!(route.host, "api.weatherapi.com");
+        assert_eq!(route.listen, "127.0.0.64:443".parse().unwrap());
+        assert_eq!((route.cid, route.port), (3, 8101));
+
+        assert!(parse_args(&args(&["api.weatherapi.com", "127.0.0.64:443"]))
+            .unwrap_err()
+            .starts_with("usage:"));
+        assert!(
+            parse_args(&args(&["api.weatherapi.com", "127.0.0.64", "3:8101"]))
+                .unwrap_err()
+                .starts_with("Invalid listen address")
+        );
+        for vsock in ["3", "3:", "x:8101", "3:8101:1"] {
+            assert!(
+                parse_args(&args(&["api.weatherapi.com", "127.0.0.64:443", vsock]))
+                    .unwrap_err()
+                    .starts_with("Invalid vsock address")
+            );
+        }
+    }
+
+    #[test]
+    fn test_relay() {
+        let (mut client, client_end) = UnixStream::pair().unwrap();
+        let (upstream_end, mut upstream) = UnixStream::pair().unwrap();
+        let counters = Arc::new(Counters::default());
+        let relayed = {
+            let counters = counters.clone();
+            thread::spawn(move || {
+                relay(client_end, upstream_end, &counters, Duration::from_secs(5))
+            })
+        };
+
+        let mut buf = [0u8; 8];
+        client.write_all(b"request").unwrap();
+        upstream.read_exact(&mut buf[..7]).unwrap();
+        assert_eq!(&buf[..7], b"request");
+        upstream.write_all(b"response").unwrap();
+        client.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"response");
+
+        // The client is done sending, the response still goes through
+        client.shutdown(Shutdown::Write).unwrap();
+        assert_eq!(upstream.read(&mut buf).unwrap(), 0);
+        upstream.write_all(b"more").unwrap();
+        client.read_exact(&mut buf[..4]).unwrap();
+        assert_eq!(&buf[..4], b"more");
+
+        upstream.shutdown(Shutdown::Write).unwrap();
+        assert_eq!(client.read(&mut buf).unwrap(), 0);
+        relayed.join().unwrap().unwrap();
+        assert_eq!(counters.snapshot(), [0, 0, 7, 12]);
+    }
+
+    #[test]
+    fn test_relay_idle_timeout() {
+        let (_client, client_end) = UnixStream::pair().unwrap();
+        let (upstream_end, _upstream) = UnixStream::pair().unwrap();
+        let err = relay(
+            client_end,
+            upstream_end,
+            &Counters::default(),
+            Duration::from_millis(50),
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn test_connection_limit() {
+        let counters = Arc::new(Counters::default());
+        let mut slots: Vec<Slot> = (0..MAX_CONNECTIONS)
+            .map(|_| Slot::acquire(&counters).unwrap())
+            .collect();
+        assert_eq!(counters.active.load(Ordering::SeqCst), MAX_CONNECTIONS);
+        assert!(Slot::acquire(&counters).is_none());
+
+        // A finished connection frees its slot
+        slots.pop();
+        assert!(Slot::acquire(&counters).is_some());
+        drop(slots);
+        assert_eq!(counters.active.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_bind_retry() {
+        let taken = TcpListener::bind("127.0.0.1:0").unwrap();
+        let route = Route {
+            host: "api.weatherapi.com".to_string(),
+            listen: taken.local_addr().unwrap(),
+            cid: 3,
+            port: 8101,
+        };
+        let bound = thread::spawn(move || bind(&route));
+        // The address is released after the first failed attempt
+        thread::sleep(BIND_RETRY / 2);
+        drop(taken);
+        let listener = bound.join().unwrap();
+        assert!(TcpStream::connect(listener.local_addr().unwrap()).is_ok());
+    }
+
+    #[test]
+    fn test_accept_retry() {
+        let route = parse_args(&args(&["api.weatherapi.com", "127.0.0.64:443", "3:8101"])).unwrap();
+        let mut failures = 0;
+        let start = Instant::now();
+        let client = accept_next(&route, || {
+            if failures < 2 {
+                failures += 1;
+                return Err(io::Error::from_raw_os_error(libc::EMFILE));
+            }
+            Ok("client")
+        });
+        assert_eq!(client, "client");
+        assert_eq!(failures, 2);
+        // Each failure waits before the next attempt, twice as long each time
+        assert!(start.elapsed() >= ACCEPT_RETRY * 3);
+    }
+}
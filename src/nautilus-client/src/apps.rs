@@ -0,0 +1,81 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Typed clients of the example apps.
+
+use crate::{ClientError, EnclaveClient};
+use nautilus_types::apps::random::{RandomRequest, RandomResponse};
+use nautilus_types::apps::twitter::{UserData, UserRequest};
+use nautilus_types::apps::weather::{WeatherRequest, WeatherResponse};
+use nautilus_types::{IntentMessage, ProcessDataRequest, ProcessedDataResponse};
+
+/// Verified response of an app.
+pub type Signed<T> = ProcessedDataResponse<IntentMessage<T>>;
+
+/// Client of the weather-example app.
+#[derive(Debug, Clone)]
+pub struct WeatherClient(pub EnclaveClient);
+
+impl WeatherClient {
+    /// Signed current temperature of `location`.
+    pub async fn weather(&self, location: &str) -> Result<Signed<WeatherResponse>, ClientError> {
+        self.process(&ProcessDataRequest::new(WeatherRequest {
+            location: location.to_string(),
+        }))
+        .await
+    }
+
+    /// Send a request with its nonce, caller or key epoch.
+    pub async fn process(
+        &self,
+        request: &ProcessDataRequest<WeatherRequest>,
+    ) -> Result<Signed<WeatherResponse>, ClientError> {
+        self.0.process_data("/process_data", request).await
+    }
+}
+
+/// Client of the random-example app.
+#[derive(Debug, Clone)]
+pub struct RandomClient(pub EnclaveClient);
+
+impl RandomClient {
+    /// Signed random number in `min..=max`.
+    pub async fn random_number(
+        &self,
+        min: u64,
+        max: u64,
+    ) -> Result<Signed<RandomResponse>, ClientError> {
+        self.process(&ProcessDataRequest::new(RandomRequest { min, max }))
+            .await
+    }
+
+    /// Send a request with its nonce, caller or key epoch.
+    pub async fn process(
+        &self,
+        request: &ProcessDataRequest<RandomRequest>,
+    ) -> Result<Signed<RandomResponse>, ClientError> {
+        self.0.process_data("/process_data", request).await
+    }
+}
+
+/// Client of the twitter-example app.
+#[derive(Debug, Clone)]
+pub struct TwitterClient(pub EnclaveClient);
+
+impl TwitterClient {
+    /// Signed Twitter name and Sui address of the profile or tweet at `user_url`.
+    pub async fn user(&self, user_url: &str) -> Result<Signed<UserData>, ClientError> {
+        self.process(&ProcessDataRequest::new(UserRequest {
+            user_url: user_url.to_string(),
+        }))
+        .await
+    }
+
+    /// Send a request with its nonce, caller or key epoch.
+    pub async fn process(
+        &self,
+        request: &ProcessDataRequest<UserRequest>,
+    ) -> Result<Signed<UserData>, ClientError> {
+        self.0.process_data("/process_data", request).await
+    }
+}
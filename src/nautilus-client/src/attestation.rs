@@ -0,0 +1,131 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Fetch and verify the attestation of an enclave with nautilus-verifier, so
+//! that the attested public key can be pinned before trusting any response.
+
+use crate::{ClientError, EnclaveClient, PinnedKey};
+use nautilus_types::{AttestationUserData, GetAttestationQuery};
+use nautilus_verifier::{check_pcrs, verify_attestation, VerifiedDocument, VerifyError};
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Attestation of an enclave whose document, PCRs and nonce verified.
+#[derive(Debug)]
+pub struct AttestedEnclave {
+    pub document: VerifiedDocument,
+    /// Context of the enclave bound in the user_data of the document.
+    pub user_data: AttestationUserData,
+}
+
+impl AttestedEnclave {
+    /// Attested public key, to pin with `EnclaveClient::with_pinned_key`.
+    pub fn pinned_key(&self) -> PinnedKey {
+        PinnedKey {
+            scheme: self.user_data.key_scheme,
+            public_key: self.document.public_key.clone().expect("checked by attest"),
+        }
+    }
+}
+
+impl EnclaveClient {
+    /// Fetch an attestation for a fresh `nonce` and verify it against the
+    /// DER encoded `root_certificate` of AWS Nitro and the `expected_pcrs`.
+    pub async fn attest(
+        &self,
+        nonce: &[u8],
+        root_certificate: &[u8],
+        expected_pcrs: &BTreeMap<u16, Vec<u8>>,
+    ) -> Result<AttestedEnclave, ClientError> {
+        let document = self
+            .get_attestation(&GetAttestationQuery {
+                nonce: Some(hex::encode(nonce)),
+                ..Default::default()
+            })
+            .await?;
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        check_document(
+            verify_attestation(&document, root_certificate, now_ms)
+                .map_err(ClientError::Attestation)?,
+            nonce,
+            expected_pcrs,
+        )
+    }
+}
+
+/// Check the PCRs, nonce, public key and user_data of a verified document.
+pub fn check_document(
+    document: VerifiedDocument,
+    nonce: &[u8],
+    expected_pcrs: &BTreeMap<u16, Vec<u8>>,
+) -> Result<AttestedEnclave, ClientError> {
+    check_pcrs(&document, expected_pcrs).map_err(ClientError::Attestation)?;
+    let malformed = |msg: &str| ClientError::Attestation(VerifyError::Malformed(msg.to_string()));
+    if document.nonce.as_deref() != Some(nonce) {
+        return Err(malformed("nonce does not match the request"));
+    }
+    if document.public_key.is_none() {
+        return Err(malformed("no public key"));
+    }
+    let user_data = document
+        .user_data
+        .as_deref()
+        .and_then(|data| serde_json::from_slice::<AttestationUserData>(data).ok())
+        .ok_or_else(|| malformed("user_data is not an AttestationUserData"))?;
+    Ok(AttestedEnclave {
+        document,
+        user_data,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use nautilus_types::{BuildInfo, SignatureScheme, ATTESTATION_USER_DATA_VERSION};
+
+    #[test]
+    fn test_check_document() {
+        let user_data = AttestationUserData {
+            version: ATTESTATION_USER_DATA_VERSION,
+            apps: vec!["weather".to_string()],
+            key_scheme: SignatureScheme::Secp256r1,
+            key_epoch: 0,
+            bls_public_key: None,
+            config_hash: "00".repeat(32),
+            build: BuildInfo {
+                package_version: "0.1.0".to_string(),
+                git_revision: None,
+            },
+            client_data: None,
+        };
+        let document = || VerifiedDocument {
+            module_id: "i-0".to_string(),
+            timestamp_ms: 0,
+            pcrs: BTreeMap::from([(0, vec![1; 48])]),
+            public_key: Some(vec![2; 33]),
+            user_data: Some(serde_json::to_vec(&user_data).unwrap()),
+            nonce: Some(vec![3; 16]),
+            certificate: vec![],
+        };
+        let pcrs = BTreeMap::from([(0, vec![1; 48])]);
+
+        let attested = check_document(document(), &[3; 16], &pcrs).unwrap();
+        assert_eq!(attested.user_data, user_data);
+        assert_eq!(
+            attested.pinned_key(),
+            PinnedKey {
+                scheme: SignatureScheme::Secp256r1,
+                public_key: vec![2; 33],
+            }
+        );
+
+        assert!(check_document(document(), &[4; 16], &pcrs).is_err());
+        assert!(check_document(document(), &[3; 16], &BTreeMap::from([(0, vec![0; 48])])).is_err());
+        let mut no_user_data = document();
+        no_user_data.user_data = Some(b"{}".to_vec());
+        assert!(check_document(no_user_data, &[3; 16], &pcrs).is_err());
+    }
+}
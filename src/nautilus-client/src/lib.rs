@@ -0,0 +1,317 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Async client of a Nautilus server: it calls the endpoints of an enclave and
+//! verifies the signature, binding and freshness of every response before
+//! returning it, with the request and response types shared with the server
+//! through nautilus-types.
+
+use nautilus_types::{
+    DomainSeparator, ErrorResponse, FailureAttestation, GetAttestationQuery,
+    GetAttestationResponse, IntentMessage, IntentScope, ProcessDataRequest, ProcessedDataResponse,
+    SignatureScheme,
+};
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+use std::time::Duration;
+
+pub mod apps;
+#[cfg(feature = "attestation")]
+pub mod attestation;
+pub mod verify;
+
+pub use nautilus_types;
+pub use verify::{PinnedKey, Verifier};
+
+/// Default timeout of the requests to the enclave.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Client of one enclave, every response is checked with its `Verifier`.
+#[derive(Debug, Clone)]
+pub struct EnclaveClient {
+    http: Client,
+    base_url: String,
+    verifier: Verifier,
+}
+
+impl EnclaveClient {
+    /// Client of the enclave at `base_url`, e.g. "http://<PUBLIC_IP>:3000",
+    /// verifying ed25519 signatures from any key.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            http: Client::builder()
+                .timeout(DEFAULT_TIMEOUT)
+                .build()
+                .expect("default client should build"),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            verifier: Verifier::default(),
+        }
+    }
+
+    /// Use `http` to send the requests, e.g. with custom timeouts or TLS.
+    pub fn with_http_client(mut self, http: Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Signature scheme of the enclave keys, see `signature_scheme` in
+    /// `server_config.yaml`.
+    pub fn with_scheme(mut self, scheme: SignatureScheme) -> Self {
+        self.verifier.scheme = scheme;
+        self
+    }
+
+    /// Only accept responses signed by `key`.
+    pub fn with_pinned_key(mut self, key: PinnedKey) -> Self {
+        self.verifier.pinned_key = Some(key);
+        self
+    }
+
+    /// Domain separator of the deployment, if the enclave is configured with one.
+    pub fn with_domain(mut self, domain: DomainSeparator) -> Self {
+        self.verifier.domain = Some(domain);
+        self
+    }
+
+    /// Reject responses whose timestamp is older than `max_age`.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.verifier.max_age = Some(max_age);
+        self
+    }
+
+    pub fn verifier(&self) -> &Verifier {
+        &self.verifier
+    }
+
+    /// POST a process data request to `path`, e.g. "/process_data", and
+    /// verify the signed response. If the enclave returns a signed failure
+    /// attestation, it is verified against the request before the error is
+    /// returned.
+    pub async fn process_data<Req, Resp>(
+        &self,
+        path: &str,
+        request: &ProcessDataRequest<Req>,
+    ) -> Result<ProcessedDataResponse<IntentMessage<Resp>>, ClientError>
+    where
+        Req: Serialize,
+        Resp: Serialize + DeserializeOwned,
+    {
+        let binding = request
+            .binding()
+            .map_err(|e| ClientError::InvalidRequest(e.to_string()))?;
+        let response = self.http.post(self.url(path)).json(request).send().await?;
+        let response = json(response).await;
+        if let Err(ClientError::Enclave {
+            attestation: Some(attestation),
+            ..
+        }) = &response
+        {
+            self.verifier
+                .verify_failure(attestation, &request.payload, binding.as_ref())?;
+        }
+        let response: ProcessedDataResponse<IntentMessage<Resp>> = response?;
+        self.verifier
+            .verify(&response, IntentScope::ProcessData, binding.as_ref())?;
+        Ok(response)
+    }
+
+    /// Fetch a raw attestation document of the enclave, see
+    /// `attestation::attest` to verify it.
+    pub async fn get_attestation(
+        &self,
+        query: &GetAttestationQuery,
+    ) -> Result<Vec<u8>, ClientError> {
+        let response = self
+            .http
+            .get(self.url("/get_attestation"))
+            .query(query)
+            .send()
+            .await?;
+        let response: GetAttestationResponse = json(response).await?;
+        hex::decode(&response.attestation).map_err(|_| {
+            ClientError::InvalidResponse("attestation must be hex encoded".to_string())
+        })
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url, path.trim_start_matches('/'))
+    }
+}
+
+/// Decode a successful JSON response, or the error returned by the enclave.
+async fn json<T: DeserializeOwned>(response: reqwest::Response) -> Result<T, ClientError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response.json().await?);
+    }
+    let body = response.text().await?;
+    let (message, code, attestation) = match serde_json::from_str::<ErrorResponse>(&body) {
+        Ok(error) => (error.error, error.code, error.attestation.map(Box::new)),
+        Err(_) => (body, None, None),
+    };
+    Err(ClientError::Enclave {
+        status: status.as_u16(),
+        code,
+        message,
+        attestation,
+    })
+}
+
+/// Client errors enum.
+#[derive(Debug)]
+pub enum ClientError {
+    /// The request could not be encoded, e.g. a nonce that is not hex.
+    InvalidRequest(String),
+    /// The request failed before the enclave answered, or its body could not
+    /// be decoded.
+    Http(reqwest::Error),
+    /// The enclave returned an error, with its stable `code` if any, and its
+    /// signed failure attestation if the enclave signs failures.
+    Enclave {
+        status: u16,
+        code: Option<String>,
+        message: String,
+        attestation: Option<Box<ProcessedDataResponse<IntentMessage<FailureAttestation>>>>,
+    },
+    /// The response is malformed or does not match the request.
+    InvalidResponse(String),
+    /// The signature does not verify against the public key of the response.
+    InvalidSignature(String),
+    /// The response is signed by another key than the pinned key.
+    UnexpectedKey(String),
+    /// The timestamp of the response is outside of the accepted window.
+    Stale { timestamp_ms: u64, now_ms: u64 },
+    /// The attestation document does not verify.
+    #[cfg(feature = "attestation")]
+    Attestation(nautilus_verifier::VerifyError),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::InvalidRequest(e) => write!(f, "Invalid request: {}", e),
+            ClientError::Http(e) => write!(f, "Request failed: {}", e),
+            ClientError::Enclave {
+                status,
+                code,
+                message,
+                ..
+            } => write!(
+                f,
+                "Enclave returned {} ({}): {}",
+                status,
+                code.as_deref().unwrap_or("unknown"),
+                message
+            ),
+            ClientError::InvalidResponse(e) => write!(f, "Invalid response: {}", e),
+            ClientError::InvalidSignature(e) => write!(f, "Invalid signature: {}", e),
+            ClientError::UnexpectedKey(key) => {
+                write!(f, "Response signed by unexpected public key {}", key)
+            }
+            ClientError::Stale {
+                timestamp_ms,
+                now_ms,
+            } => write!(
+                f,
+                "Response timestamp {} is outside of the accepted window at {}",
+                timestamp_ms, now_ms
+            ),
+            #[cfg(feature = "attestation")]
+            ClientError::Attestation(e) => write!(f, "Invalid attestation: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(e: reqwest::Error) -> Self {
+        ClientError::Http(e)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::apps::RandomClient;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serve one HTTP response with `status` and `body`, returning the base URL.
+    async fn serve_once(status: &'static str, body: String) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0; 4096];
+            let _ = socket.read(&mut buf).await.unwrap();
+            let response = format!(
+                "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_process_data() {
+        use ed25519_dalek::Signer;
+        use nautilus_types::apps::random::RandomResponse;
+        use nautilus_types::signing_payload;
+
+        let key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let response = IntentMessage::new(
+            RandomResponse {
+                random_number: 4,
+                min: 1,
+                max: 6,
+            },
+            1744038900000,
+            IntentScope::ProcessData,
+        );
+        let signature = key.sign(&signing_payload(None, &response, None));
+        let body = serde_json::to_string(&ProcessedDataResponse {
+            response,
+            signature: hex::encode(signature.to_bytes()),
+            key_epoch: 0,
+            public_key: hex::encode(key.verifying_key().to_bytes()),
+            nonce: None,
+            caller: None,
+            bls: None,
+        })
+        .unwrap();
+
+        let client = RandomClient(EnclaveClient::new(serve_once("200 OK", body.clone()).await));
+        let signed = client.random_number(1, 6).await.unwrap();
+        assert_eq!(signed.response.data.random_number, 4);
+
+        // The same response is rejected once another key is pinned.
+        let client = RandomClient(
+            EnclaveClient::new(serve_once("200 OK", body).await).with_pinned_key(PinnedKey {
+                scheme: SignatureScheme::Ed25519,
+                public_key: vec![0; 32],
+            }),
+        );
+        assert!(matches!(
+            client.random_number(1, 6).await,
+            Err(ClientError::UnexpectedKey(_))
+        ));
+
+        let error = r#"{"error":"min must be less than max","code":"bad_request"}"#;
+        let client = RandomClient(EnclaveClient::new(
+            serve_once("400 Bad Request", error.to_string()).await,
+        ));
+        match client.random_number(6, 1).await {
+            Err(ClientError::Enclave { status, code, .. }) => {
+                assert_eq!(status, 400);
+                assert_eq!(code.as_deref(), Some("bad_request"));
+            }
+            _ => panic!("expected an enclave error"),
+        }
+    }
+}
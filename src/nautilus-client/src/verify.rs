@@ -0,0 +1,396 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Verification of signed responses, rebuilding the bytes the enclave signs
+//! with `nautilus_types::signing_payload`.
+
+use crate::ClientError;
+use nautilus_types::{
+    request_hash, signing_payload, DomainSeparator, FailureAttestation, IntentMessage, IntentScope,
+    ProcessedDataResponse, RequestBinding, SignatureScheme,
+};
+use serde::Serialize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Tolerated drift of the enclave clock ahead of the local clock.
+pub const MAX_CLOCK_SKEW: Duration = Duration::from_secs(30);
+
+/// Public key the responses must be signed with, e.g. the key registered
+/// onchain or the key of a verified attestation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinnedKey {
+    pub scheme: SignatureScheme,
+    pub public_key: Vec<u8>,
+}
+
+/// Checks applied to every response: the signature under the scheme of the
+/// enclave keys, and optionally the signing key, the domain and the age.
+#[derive(Debug, Clone, Default)]
+pub struct Verifier {
+    /// Signature scheme of the enclave keys, ignored if a key is pinned.
+    pub scheme: SignatureScheme,
+    /// Only accept responses signed by this key.
+    pub pinned_key: Option<PinnedKey>,
+    /// Domain separator the enclave signs with, if a domain is configured.
+    pub domain: Option<DomainSeparator>,
+    /// Reject responses whose timestamp is older than this.
+    pub max_age: Option<Duration>,
+}
+
+impl Verifier {
+    /// Verify a response to a request with `binding` under `intent`.
+    pub fn verify<T: Serialize>(
+        &self,
+        response: &ProcessedDataResponse<IntentMessage<T>>,
+        intent: IntentScope,
+        binding: Option<&RequestBinding>,
+    ) -> Result<(), ClientError> {
+        if response.response.intent != intent {
+            return Err(ClientError::InvalidResponse(format!(
+                "expected intent scope {:?}, got {:?}",
+                intent, response.response.intent
+            )));
+        }
+        let public_key = decode("public_key", &response.public_key)?;
+        let scheme = match &self.pinned_key {
+            Some(pinned) if pinned.public_key != public_key => {
+                return Err(ClientError::UnexpectedKey(response.public_key.clone()));
+            }
+            Some(pinned) => pinned.scheme,
+            None => self.scheme,
+        };
+        if response_binding(response)?.as_ref() != binding {
+            return Err(ClientError::InvalidResponse(
+                "nonce or caller of the response does not match the request".to_string(),
+            ));
+        }
+
+        let message = signing_payload(self.domain.as_ref(), &response.response, binding);
+        let signature = decode("signature", &response.signature)?;
+        verify_signature(scheme, &public_key, &message, &signature)?;
+
+        if let Some(max_age) = self.max_age {
+            check_freshness(response.response.timestamp_ms, max_age, SystemTime::now())?;
+        }
+        Ok(())
+    }
+
+    /// Verify a failure attestation returned for a request with `payload` and
+    /// `binding`, which proves that the enclave evaluated it and found it invalid.
+    pub fn verify_failure<T: Serialize>(
+        &self,
+        attestation: &ProcessedDataResponse<IntentMessage<FailureAttestation>>,
+        payload: &T,
+        binding: Option<&RequestBinding>,
+    ) -> Result<(), ClientError> {
+        self.verify(attestation, IntentScope::Failure, binding)?;
+        if attestation.response.data.request_hash != request_hash(payload) {
+            return Err(ClientError::InvalidResponse(
+                "request hash of the failure attestation does not match the request".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Verify a signature of the enclave over `message`, the ECDSA schemes hash
+/// it with SHA-256 and sign it as 64 bytes r || s, except the recoverable
+/// scheme which hashes it with Keccak-256 and appends the recovery id.
+pub fn verify_signature(
+    scheme: SignatureScheme,
+    public_key: &[u8],
+    message: &[u8],
+    signature: &[u8],
+) -> Result<(), ClientError> {
+    let invalid = |e: &dyn std::fmt::Display| ClientError::InvalidSignature(e.to_string());
+    match scheme {
+        SignatureScheme::Ed25519 => {
+            use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+            let public_key: &[u8; 32] = public_key
+                .try_into()
+                .map_err(|_| invalid(&"ed25519 public keys are 32 bytes"))?;
+            let key = VerifyingKey::from_bytes(public_key).map_err(|e| invalid(&e))?;
+            let signature = Signature::from_slice(signature).map_err(|e| invalid(&e))?;
+            key.verify(message, &signature).map_err(|e| invalid(&e))
+        }
+        SignatureScheme::Secp256k1 => {
+            use k256::ecdsa::signature::Verifier;
+            use k256::ecdsa::{Signature, VerifyingKey};
+            let key = VerifyingKey::from_sec1_bytes(public_key).map_err(|e| invalid(&e))?;
+            let signature = Signature::from_slice(signature).map_err(|e| invalid(&e))?;
+            key.verify(message, &signature).map_err(|e| invalid(&e))
+        }
+        SignatureScheme::Secp256r1 => {
+            use p256::ecdsa::signature::Verifier;
+            use p256::ecdsa::{Signature, VerifyingKey};
+            let key = VerifyingKey::from_sec1_bytes(public_key).map_err(|e| invalid(&e))?;
+            let signature = Signature::from_slice(signature).map_err(|e| invalid(&e))?;
+            key.verify(message, &signature).map_err(|e| invalid(&e))
+        }
+        SignatureScheme::Secp256k1Recoverable => {
+            use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+            use sha3::{Digest, Keccak256};
+            let (signature, v) = match signature {
+                [rs @ .., v] if rs.len() == 64 => (rs, *v),
+                _ => return Err(invalid(&"recoverable signatures are 65 bytes")),
+            };
+            let signature = Signature::from_slice(signature).map_err(|e| invalid(&e))?;
+            let v = RecoveryId::from_byte(v).ok_or_else(|| invalid(&"invalid recovery id"))?;
+            let recovered =
+                VerifyingKey::recover_from_prehash(&Keccak256::digest(message), &signature, v)
+                    .map_err(|e| invalid(&e))?;
+            if recovered.to_sec1_bytes().as_ref() != public_key {
+                return Err(invalid(&"signature recovers another public key"));
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Reject a timestamp older than `max_age` or ahead of `now` by more than
+/// `MAX_CLOCK_SKEW`.
+pub fn check_freshness(
+    timestamp_ms: u64,
+    max_age: Duration,
+    now: SystemTime,
+) -> Result<(), ClientError> {
+    let now_ms = now
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default();
+    let too_old = now_ms.saturating_sub(timestamp_ms) > max_age.as_millis() as u64;
+    let too_new = timestamp_ms.saturating_sub(now_ms) > MAX_CLOCK_SKEW.as_millis() as u64;
+    if too_old || too_new {
+        return Err(ClientError::Stale {
+            timestamp_ms,
+            now_ms,
+        });
+    }
+    Ok(())
+}
+
+/// Binding signed with a response, decoded from its nonce and caller.
+fn response_binding<T>(
+    response: &ProcessedDataResponse<T>,
+) -> Result<Option<RequestBinding>, ClientError> {
+    let nonce = response
+        .nonce
+        .as_deref()
+        .map(|nonce| decode("nonce", nonce))
+        .transpose()?;
+    let caller = response
+        .caller
+        .as_deref()
+        .map(|caller| {
+            nautilus_types::decode_address(caller)
+                .ok_or_else(|| ClientError::InvalidResponse(format!("invalid caller {}", caller)))
+        })
+        .transpose()?;
+    Ok((nonce.is_some() || caller.is_some()).then_some(RequestBinding { nonce, caller }))
+}
+
+fn decode(name: &str, value: &str) -> Result<Vec<u8>, ClientError> {
+    hex::decode(value)
+        .map_err(|_| ClientError::InvalidResponse(format!("{} must be hex encoded", name)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use nautilus_types::ProcessDataRequest;
+
+    fn signed(
+        scheme: SignatureScheme,
+        binding: Option<&RequestBinding>,
+        timestamp_ms: u64,
+    ) -> ProcessedDataResponse<IntentMessage<u64>> {
+        let response = IntentMessage::new(7u64, timestamp_ms, IntentScope::ProcessData);
+        let message = signing_payload(None, &response, binding);
+        let (public_key, signature) = match scheme {
+            SignatureScheme::Ed25519 => {
+                use ed25519_dalek::Signer;
+                let key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+                (
+                    key.verifying_key().to_bytes().to_vec(),
+                    key.sign(&message).to_bytes().to_vec(),
+                )
+            }
+            SignatureScheme::Secp256k1 => {
+                use k256::ecdsa::signature::Signer;
+                let key = k256::ecdsa::SigningKey::from_slice(&[7; 32]).unwrap();
+                let signature: k256::ecdsa::Signature = key.sign(&message);
+                (
+                    key.verifying_key().to_sec1_bytes().to_vec(),
+                    signature.to_bytes().to_vec(),
+                )
+            }
+            SignatureScheme::Secp256r1 => {
+                use p256::ecdsa::signature::Signer;
+                let key = p256::ecdsa::SigningKey::from_slice(&[7; 32]).unwrap();
+                let signature: p256::ecdsa::Signature = key.sign(&message);
+                (
+                    key.verifying_key().to_sec1_bytes().to_vec(),
+                    signature.to_bytes().to_vec(),
+                )
+            }
+            SignatureScheme::Secp256k1Recoverable => {
+                use sha3::{Digest, Keccak256};
+                let key = k256::ecdsa::SigningKey::from_slice(&[7; 32]).unwrap();
+                let (signature, v) = key
+                    .sign_prehash_recoverable(&Keccak256::digest(&message))
+                    .unwrap();
+                let mut signature = signature.to_bytes().to_vec();
+                signature.push(v.to_byte());
+                (key.verifying_key().to_sec1_bytes().to_vec(), signature)
+            }
+        };
+        ProcessedDataResponse {
+            response,
+            signature: hex::encode(signature),
+            key_epoch: 0,
+            public_key: hex::encode(public_key),
+            nonce: binding.and_then(|b| b.nonce.as_ref()).map(hex::encode),
+            caller: binding
+                .and_then(|b| b.caller)
+                .map(|caller| format!("0x{}", hex::encode(caller))),
+            bls: None,
+        }
+    }
+
+    #[test]
+    fn test_verify() {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        for scheme in [
+            SignatureScheme::Ed25519,
+            SignatureScheme::Secp256k1,
+            SignatureScheme::Secp256r1,
+            SignatureScheme::Secp256k1Recoverable,
+        ] {
+            let verifier = Verifier {
+                scheme,
+                ..Default::default()
+            };
+            let response = signed(scheme, None, now);
+            verifier
+                .verify(&response, IntentScope::ProcessData, None)
+                .unwrap();
+            assert!(verifier
+                .verify(&response, IntentScope::BatchRoot, None)
+                .is_err());
+
+            // A tampered response or the wrong scheme fails.
+            let mut tampered = signed(scheme, None, now);
+            tampered.response.data = 8;
+            assert!(verifier
+                .verify(&tampered, IntentScope::ProcessData, None)
+                .is_err());
+            let other = Verifier {
+                scheme: match scheme {
+                    SignatureScheme::Secp256k1 => SignatureScheme::Secp256r1,
+                    _ => SignatureScheme::Secp256k1,
+                },
+                ..Default::default()
+            };
+            assert!(other
+                .verify(&response, IntentScope::ProcessData, None)
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn test_verify_binding_and_pinning() {
+        let request = ProcessDataRequest {
+            nonce: Some("0102".to_string()),
+            caller: Some(format!("0x{}", "11".repeat(32))),
+            ..ProcessDataRequest::new(())
+        };
+        let binding = request.binding().unwrap();
+        let response = signed(SignatureScheme::Ed25519, binding.as_ref(), 1744038900000);
+
+        let verifier = Verifier::default();
+        verifier
+            .verify(&response, IntentScope::ProcessData, binding.as_ref())
+            .unwrap();
+        // The response must carry the binding of the request.
+        assert!(verifier
+            .verify(&response, IntentScope::ProcessData, None)
+            .is_err());
+
+        let pinned = |public_key: Vec<u8>| Verifier {
+            pinned_key: Some(PinnedKey {
+                scheme: SignatureScheme::Ed25519,
+                public_key,
+            }),
+            ..Default::default()
+        };
+        pinned(hex::decode(&response.public_key).unwrap())
+            .verify(&response, IntentScope::ProcessData, binding.as_ref())
+            .unwrap();
+        assert!(matches!(
+            pinned(vec![0; 32]).verify(&response, IntentScope::ProcessData, binding.as_ref()),
+            Err(ClientError::UnexpectedKey(_))
+        ));
+
+        // The response is older than the max age.
+        let fresh = Verifier {
+            max_age: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        assert!(matches!(
+            fresh.verify(&response, IntentScope::ProcessData, binding.as_ref()),
+            Err(ClientError::Stale { .. })
+        ));
+    }
+
+    #[test]
+    fn test_verify_failure() {
+        use ed25519_dalek::Signer;
+
+        let payload = "San Francisco".to_string();
+        let response = IntentMessage::new(
+            FailureAttestation {
+                code: "unprocessable".to_string(),
+                request_hash: request_hash(&payload),
+            },
+            1744038900000,
+            IntentScope::Failure,
+        );
+        let key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let signature = key.sign(&signing_payload(None, &response, None));
+        let attestation = ProcessedDataResponse {
+            response,
+            signature: hex::encode(signature.to_bytes()),
+            key_epoch: 0,
+            public_key: hex::encode(key.verifying_key().to_bytes()),
+            nonce: None,
+            caller: None,
+            bls: None,
+        };
+
+        let verifier = Verifier::default();
+        verifier
+            .verify_failure(&attestation, &payload, None)
+            .unwrap();
+        // The attestation is only valid for the request it was signed for.
+        assert!(verifier
+            .verify_failure(&attestation, &"London".to_string(), None)
+            .is_err());
+        assert!(verifier
+            .verify(&attestation, IntentScope::ProcessData, None)
+            .is_err());
+    }
+
+    #[test]
+    fn test_check_freshness() {
+        let now = UNIX_EPOCH + Duration::from_millis(1_000_000);
+        let max_age = Duration::from_secs(60);
+        assert!(check_freshness(1_000_000, max_age, now).is_ok());
+        assert!(check_freshness(940_000, max_age, now).is_ok());
+        assert!(check_freshness(939_999, max_age, now).is_err());
+        assert!(check_freshness(1_030_000, max_age, now).is_ok());
+        assert!(check_freshness(1_030_001, max_age, now).is_err());
+    }
+}
@@ -0,0 +1,377 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! `#[derive(MovePayload)]` for the payload types an enclave signs as the data
+//! of an `IntentMessage<T>`. It registers the type with its intent scope and
+//! its Move struct definition, see `nautilus_types::MovePayload`:
+//!
+//! ```ignore
+//! #[derive(Serialize, MovePayload)]
+//! #[move_payload(intent = ProcessData, source = "../../move/weather-example/sources/weather.move")]
+//! pub struct WeatherResponse {
+//!     pub location: String,
+//!     pub temperature: u64,
+//! }
+//! ```
+//!
+//! With `source`, the struct of the same name (or `name = "..."`) is read from
+//! the Move file, relative to the crate root, and the build fails if its fields
+//! differ in name, order or type from the Rust fields.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use std::path::PathBuf;
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, Data, DeriveInput, Error, Fields, GenericArgument, Ident, LitStr,
+    PathArguments, Type,
+};
+
+#[proc_macro_derive(MovePayload, attributes(move_payload))]
+pub fn derive_move_payload(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// Arguments of `#[move_payload(...)]`.
+struct Args {
+    intent: Ident,
+    name: Option<String>,
+    source: Option<LitStr>,
+}
+
+fn parse_args(input: &DeriveInput) -> syn::Result<Args> {
+    let mut intent = None;
+    let mut name = None;
+    let mut source = None;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|a| a.path().is_ident("move_payload"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("intent") {
+                intent = Some(meta.value()?.parse::<Ident>()?);
+            } else if meta.path.is_ident("name") {
+                name = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("source") {
+                source = Some(meta.value()?.parse::<LitStr>()?);
+            } else {
+                return Err(meta.error("expected `intent`, `name` or `source`"));
+            }
+            Ok(())
+        })?;
+    }
+    let intent = intent.ok_or_else(|| {
+        Error::new(
+            Span::call_site(),
+            "missing #[move_payload(intent = <IntentScope variant>)]",
+        )
+    })?;
+    Ok(Args {
+        intent,
+        name,
+        source,
+    })
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let args = parse_args(&input)?;
+    if !input.generics.params.is_empty() {
+        return Err(Error::new(
+            input.generics.span(),
+            "MovePayload does not support generic types",
+        ));
+    }
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new(
+                    input.ident.span(),
+                    "MovePayload requires named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new(
+                input.ident.span(),
+                "MovePayload can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut move_fields = Vec::new();
+    for field in fields {
+        let name = field.ident.as_ref().expect("named field").to_string();
+        move_fields.push((name, move_type(&field.ty)?, field.ty.span()));
+    }
+    let move_name = args.name.unwrap_or_else(|| input.ident.to_string());
+
+    // Track the Move source so that the crate is rebuilt when it changes.
+    let source = match &args.source {
+        Some(source) => {
+            let path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default())
+                .join(source.value());
+            let content = std::fs::read_to_string(&path).map_err(|e| {
+                Error::new(
+                    source.span(),
+                    format!("cannot read {}: {}", path.display(), e),
+                )
+            })?;
+            check_source(&content, &move_name, &move_fields, source)?;
+            let path = path.to_string_lossy().into_owned();
+            quote! { const _: &[u8] = include_bytes!(#path); }
+        }
+        None => quote! {},
+    };
+
+    let ident = &input.ident;
+    let intent = &args.intent;
+    let names = move_fields.iter().map(|(name, _, _)| name);
+    let types = move_fields.iter().map(|(_, ty, _)| ty);
+    let members = fields.iter().map(|field| &field.ident);
+    Ok(quote! {
+        #source
+
+        impl ::nautilus_types::MovePayload for #ident {
+            const INTENT: ::nautilus_types::IntentScope = ::nautilus_types::IntentScope::#intent;
+            const MOVE_NAME: &'static str = #move_name;
+            const MOVE_FIELDS: &'static [(&'static str, &'static str)] = &[#((#names, #types)),*];
+
+            fn move_field_values(&self) -> ::std::vec::Vec<::std::string::String> {
+                ::std::vec![#(::nautilus_types::MoveValue::move_value(&self.#members)),*]
+            }
+        }
+
+        impl ::nautilus_types::MoveValue for #ident {
+            fn move_value(&self) -> ::std::string::String {
+                let fields: ::std::vec::Vec<_> = <Self as ::nautilus_types::MovePayload>::MOVE_FIELDS
+                    .iter()
+                    .zip(<Self as ::nautilus_types::MovePayload>::move_field_values(self))
+                    .map(|((name, _), value)| ::std::format!("{}: {}", name, value))
+                    .collect();
+                ::std::format!("{} {{ {} }}", #move_name, fields.join(", "))
+            }
+        }
+    })
+}
+
+/// Move type of a Rust field type, with the same BCS encoding.
+fn move_type(ty: &Type) -> syn::Result<String> {
+    let unsupported = || {
+        Error::new(
+            ty.span(),
+            "unsupported type, expected an integer, bool, String, Vec<T>, Option<T>, [u8; 32] or a MovePayload struct",
+        )
+    };
+    match ty {
+        Type::Array(array) => {
+            let is_u8 = matches!(&*array.elem, Type::Path(p) if p.path.is_ident("u8"));
+            let is_32 = matches!(&array.len, syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Int(n), .. }) if n.base10_digits() == "32");
+            if is_u8 && is_32 {
+                Ok("address".to_string())
+            } else {
+                Err(unsupported())
+            }
+        }
+        Type::Path(path) if path.qself.is_none() => {
+            let segment = path.path.segments.last().ok_or_else(unsupported)?;
+            let name = segment.ident.to_string();
+            let inner = || match &segment.arguments {
+                PathArguments::AngleBracketed(args) if args.args.len() == 1 => {
+                    match args.args.first() {
+                        Some(GenericArgument::Type(inner)) => move_type(inner),
+                        _ => Err(unsupported()),
+                    }
+                }
+                _ => Err(unsupported()),
+            };
+            match name.as_str() {
+                "u8" | "u16" | "u32" | "u64" | "u128" | "bool" => {
+                    if segment.arguments.is_empty() {
+                        Ok(name)
+                    } else {
+                        Err(unsupported())
+                    }
+                }
+                "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "usize" | "f32" | "f64"
+                | "char" => Err(unsupported()),
+                "String" => Ok("String".to_string()),
+                "Vec" => Ok(format!("vector<{}>", inner()?)),
+                "Option" => Ok(format!("Option<{}>", inner()?)),
+                _ if segment.arguments.is_empty() => Ok(name),
+                _ => Err(unsupported()),
+            }
+        }
+        _ => Err(unsupported()),
+    }
+}
+
+/// Compare the fields of the struct `name` in the Move source to the Rust fields.
+fn check_source(
+    source: &str,
+    name: &str,
+    fields: &[(String, String, Span)],
+    path: &LitStr,
+) -> syn::Result<()> {
+    let move_fields = parse_move_struct(source, name).ok_or_else(|| {
+        Error::new(
+            path.span(),
+            format!(
+                "struct {} with named fields not found in the Move source",
+                name
+            ),
+        )
+    })?;
+    for (i, (field, ty, span)) in fields.iter().enumerate() {
+        match move_fields.get(i) {
+            Some((move_field, move_ty)) if move_field == field && *move_ty == normalize(ty) => {}
+            Some((move_field, move_ty)) => {
+                return Err(Error::new(
+                    *span,
+                    format!(
+                        "field {} of {} is `{}: {}` in Rust but `{}: {}` in Move",
+                        i, name, field, ty, move_field, move_ty
+                    ),
+                ))
+            }
+            None => {
+                return Err(Error::new(
+                    *span,
+                    format!("field `{}` of {} is missing in Move", field, name),
+                ))
+            }
+        }
+    }
+    if let Some((move_field, _)) = move_fields.get(fields.len()) {
+        return Err(Error::new(
+            path.span(),
+            format!("field `{}` of {} is missing in Rust", move_field, name),
+        ));
+    }
+    Ok(())
+}
+
+/// Fields of `struct <name> ... { field: type, ... }` in a Move source, with
+/// normalized types.
+fn parse_move_struct(source: &str, name: &str) -> Option<Vec<(String, String)>> {
+    let source: String = source
+        .lines()
+        .map(|line| line.split("//").next().unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut rest = source.as_str();
+    let body = loop {
+        let start = rest.find("struct ")? + "struct ".len();
+        rest = &rest[start..];
+        let ident_len = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        if &rest[..ident_len] == name {
+            let header_end = rest.find(['{', '(', ';'])?;
+            if !rest[header_end..].starts_with('{') {
+                return None;
+            }
+            let body = &rest[header_end + 1..];
+            break &body[..body.find('}')?];
+        }
+    };
+
+    let mut fields = Vec::new();
+    let mut depth = 0;
+    let mut current = String::new();
+    for c in body.chars().chain(std::iter::once(',')) {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            _ => {}
+        }
+        if c == ',' && depth == 0 {
+            if !current.trim().is_empty() {
+                let (field, ty) = current.split_once(':')?;
+                fields.push((field.trim().to_string(), normalize(ty)));
+            }
+            current.clear();
+        } else {
+            current.push(c);
+        }
+    }
+    Some(fields)
+}
+
+/// Move type without whitespace or module paths, e.g. `std::string::String`
+/// becomes `String`.
+fn normalize(ty: &str) -> String {
+    let ty: String = ty.chars().filter(|c| !c.is_whitespace()).collect();
+    let mut normalized = String::new();
+    let mut segment = String::new();
+    let mut chars = ty.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == ':' && chars.peek() == Some(&':') {
+            chars.next();
+            segment.clear();
+        } else if c.is_alphanumeric() || c == '_' {
+            segment.push(c);
+        } else {
+            normalized.push_str(&segment);
+            segment.clear();
+            normalized.push(c);
+        }
+    }
+    normalized.push_str(&segment);
+    normalized
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_move_struct() {
+        let source = r#"
+module app::weather;
+
+/// Should match the inner struct T used for IntentMessage<T> in Rust.
+public struct WeatherNFT has key, store {
+    id: UID,
+    location: String,
+}
+
+public struct WeatherResponse has copy, drop {
+    location: std::string::String, // the city
+    temperature: u64,
+    readings: vector<Option<u64>>,
+}
+
+public struct Positional(u64) has drop;
+"#;
+        assert_eq!(
+            parse_move_struct(source, "WeatherResponse").unwrap(),
+            vec![
+                ("location".to_string(), "String".to_string()),
+                ("temperature".to_string(), "u64".to_string()),
+                ("readings".to_string(), "vector<Option<u64>>".to_string()),
+            ]
+        );
+        assert_eq!(parse_move_struct(source, "Weather"), None);
+        assert_eq!(parse_move_struct(source, "Positional"), None);
+    }
+
+    #[test]
+    fn test_move_type() {
+        let ty = |s: &str| move_type(&syn::parse_str::<Type>(s).unwrap());
+        assert_eq!(ty("u64").unwrap(), "u64");
+        assert_eq!(ty("std::string::String").unwrap(), "String");
+        assert_eq!(ty("Vec<u8>").unwrap(), "vector<u8>");
+        assert_eq!(ty("Option<Vec<Inner>>").unwrap(), "Option<vector<Inner>>");
+        assert_eq!(ty("[u8; 32]").unwrap(), "address");
+        assert!(ty("[u8; 20]").is_err());
+        assert!(ty("i64").is_err());
+        assert!(ty("(u8, u8)").is_err());
+        assert!(ty("HashMap<u8, u8>").is_err());
+    }
+}
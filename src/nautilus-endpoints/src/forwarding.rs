@@ -0,0 +1,202 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Traffic forwarding of the allowed endpoints, since the enclave has no
+//! network. In the enclave, `/etc/hosts` resolves each host to its own
+//! loopback address, where a traffic forwarder (`src/forwarder`) relays port
+//! 443 to a vsock port of the parent instance. There, a vsock-proxy relays that port to the host.
+//! The `nautilus-endpoints` binary renders both sides from the same
+//! `ForwardingPlan`, so that the enclave and the parent never drift apart.
+
+use crate::{EndpointsConfig, InvalidEndpoints};
+use std::net::Ipv4Addr;
+
+/// Loopback address of the first host, the following hosts get the next ones.
+const FIRST_LOOPBACK: u8 = 64;
+/// Vsock port of the first host on the parent, the following hosts get the next ones.
+const FIRST_VSOCK_PORT: u32 = 8101;
+/// CID of the parent instance as seen from the enclave.
+const PARENT_CID: u32 = 3;
+/// Port the hosts are called on, the egress client only calls https.
+const HTTPS_PORT: u16 = 443;
+/// Allowlist of the vsock-proxy on the parent instance.
+const VSOCK_PROXY_CONFIG: &str = "/etc/nitro_enclaves/vsock-proxy.yaml";
+
+/// Names of the blocks of `run.sh` that are generated, between the lines
+/// `# BEGIN nautilus-endpoints <name>` and `# END nautilus-endpoints <name>`.
+const HOSTS_BLOCK: &str = "hosts";
+const FORWARDERS_BLOCK: &str = "forwarders";
+
+/// Forwarding of one host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Forward {
+    pub host: String,
+    /// Address the host resolves to in the enclave.
+    pub loopback: Ipv4Addr,
+    /// Port of the vsock-proxy of the host on the parent instance.
+    pub vsock_port: u32,
+}
+
+/// Loopback addresses and vsock ports assigned to the allowed endpoints.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ForwardingPlan {
+    pub forwards: Vec<Forward>,
+}
+
+impl ForwardingPlan {
+    /// Assign addresses and ports to the hosts in sorted order, so that the
+    /// plan only depends on the set of hosts and not on their order.
+    pub fn new(config: &EndpointsConfig) -> Result<Self, InvalidEndpoints> {
+        let mut hosts: Vec<_> = config.endpoints.iter().map(|e| e.host.clone()).collect();
+        hosts.sort();
+        hosts.dedup();
+        let max = usize::from(u8::MAX - FIRST_LOOPBACK);
+        if hosts.len() > max {
+            return Err(InvalidEndpoints(format!(
+                "At most {} endpoints can be forwarded, got {}",
+                max,
+                hosts.len()
+            )));
+        }
+        let forwards = hosts
+            .into_iter()
+            .zip(0u8..)
+            .map(|(host, i)| Forward {
+                host,
+                loopback: Ipv4Addr::new(127, 0, 0, FIRST_LOOPBACK + i),
+                vsock_port: FIRST_VSOCK_PORT + u32::from(i),
+            })
+            .collect();
+        Ok(Self { forwards })
+    }
+
+    /// Lines of `run.sh` adding the hosts to `/etc/hosts` in the enclave.
+    pub fn hosts(&self) -> String {
+        self.lines(|f| format!("echo \"{}   {}\" >> /etc/hosts", f.loopback, f.host))
+    }
+
+    /// Lines of `run.sh` starting a traffic forwarder for each host.
+    pub fn forwarders(&self) -> String {
+        self.lines(|f| {
+            format!(
+                "/forwarder {} {}:{} {}:{} &",
+                f.host, f.loopback, HTTPS_PORT, PARENT_CID, f.vsock_port
+            )
+        })
+    }
+
+    /// Entries of the vsock-proxy allowlist of the parent instance.
+    pub fn vsock_proxy_allowlist(&self) -> String {
+        self.lines(|f| format!("- {{address: {}, port: {}}}", f.host, HTTPS_PORT))
+    }
+
+    /// Commands of the parent instance adding the hosts to the vsock-proxy
+    /// allowlist and starting a vsock-proxy for each host.
+    pub fn vsock_proxy(&self) -> String {
+        let allow = self.lines(|f| {
+            format!(
+                "echo \"- {{address: {}, port: {}}}\" | sudo tee -a {}",
+                f.host, HTTPS_PORT, VSOCK_PROXY_CONFIG
+            )
+        });
+        let start = self.lines(|f| {
+            format!(
+                "vsock-proxy {} {} {} --config {} &",
+                f.vsock_port, f.host, HTTPS_PORT, VSOCK_PROXY_CONFIG
+            )
+        });
+        allow + &start
+    }
+
+    /// `run_sh` with its generated blocks replaced by the ones of this plan.
+    pub fn render_run_sh(&self, run_sh: &str) -> Result<String, InvalidEndpoints> {
+        let run_sh = replace_block(run_sh, HOSTS_BLOCK, &self.hosts())?;
+        replace_block(&run_sh, FORWARDERS_BLOCK, &self.forwarders())
+    }
+
+    fn lines(&self, line: impl Fn(&Forward) -> String) -> String {
+        self.forwards.iter().map(|f| line(f) + "\n").collect()
+    }
+}
+
+/// Replace the lines between the markers of block `name` with `content`.
+fn replace_block(text: &str, name: &str, content: &str) -> Result<String, InvalidEndpoints> {
+    let begin = format!("# BEGIN nautilus-endpoints {}\n", name);
+    let end = format!("# END nautilus-endpoints {}\n", name);
+    let start = text
+        .find(&begin)
+        .map(|i| i + begin.len())
+        .ok_or_else(|| InvalidEndpoints(format!("Missing line {}", begin.trim())))?;
+    let stop = text[start..]
+        .find(&end)
+        .map(|i| start + i)
+        .ok_or_else(|| InvalidEndpoints(format!("Missing line {}", end.trim())))?;
+    Ok(format!("{}{}{}", &text[..start], content, &text[stop..]))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn plan_of(yaml: &str) -> ForwardingPlan {
+        ForwardingPlan::new(&EndpointsConfig::from_yaml(yaml).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_forwarding_plan() {
+        let plan = plan_of(
+            "endpoints:\n  - host: kms.us-east-1.amazonaws.com\n    probe:\n      path: /ping\n  - api.weatherapi.com\n",
+        );
+        assert_eq!(
+            plan.forwards[0],
+            Forward {
+                host: "api.weatherapi.com".to_string(),
+                loopback: Ipv4Addr::new(127, 0, 0, 64),
+                vsock_port: 8101,
+            }
+        );
+        assert_eq!(
+            plan.hosts(),
+            "echo \"127.0.0.64   api.weatherapi.com\" >> /etc/hosts\n\
+             echo \"127.0.0.65   kms.us-east-1.amazonaws.com\" >> /etc/hosts\n"
+        );
+        assert_eq!(
+            plan.forwarders(),
+            "/forwarder api.weatherapi.com 127.0.0.64:443 3:8101 &\n\
+             /forwarder kms.us-east-1.amazonaws.com 127.0.0.65:443 3:8102 &\n"
+        );
+        assert_eq!(
+            plan.vsock_proxy_allowlist(),
+            "- {address: api.weatherapi.com, port: 443}\n\
+             - {address: kms.us-east-1.amazonaws.com, port: 443}\n"
+        );
+        assert!(plan.vsock_proxy().ends_with(
+            "vsock-proxy 8102 kms.us-east-1.amazonaws.com 443 --config /etc/nitro_enclaves/vsock-proxy.yaml &\n"
+        ));
+
+        // The order of the endpoints does not change the plan.
+        assert_eq!(
+            plan.forwards,
+            plan_of("endpoints:\n  - api.weatherapi.com\n  - kms.us-east-1.amazonaws.com\n")
+                .forwards
+        );
+    }
+
+    #[test]
+    fn test_render_run_sh() {
+        let run_sh = include_str!("../../nautilus-server/run.sh");
+        let plan = plan_of("endpoints:\n  - api.weatherapi.com\n");
+        let rendered = plan.render_run_sh(run_sh).unwrap();
+        assert!(rendered.contains(
+            "# BEGIN nautilus-endpoints hosts\necho \"127.0.0.64   api.weatherapi.com\" >> /etc/hosts\n# END"
+        ));
+        assert!(rendered.contains("/forwarder api.weatherapi.com 127.0.0.64:443 3:8101 &\n"));
+        // Rendering is idempotent, and an empty plan restores the committed run.sh.
+        assert_eq!(plan.render_run_sh(&rendered).unwrap(), rendered);
+        assert_eq!(
+            ForwardingPlan::default().render_run_sh(&rendered).unwrap(),
+            run_sh
+        );
+        assert!(plan.render_run_sh("#!/bin/sh\n").is_err());
+    }
+}
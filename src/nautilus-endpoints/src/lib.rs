@@ -0,0 +1,256 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Allowed endpoints of the Nautilus apps and the traffic forwarding generated
+//! from them, see `forwarding`. Kept apart from the server so that
+//! `configure_enclave.sh` can render `run.sh` without building the server.
+
+pub mod forwarding;
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// An `allowed_endpoints.yaml` that cannot be parsed, or endpoints that
+/// cannot be forwarded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidEndpoints(pub String);
+
+impl fmt::Display for InvalidEndpoints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidEndpoints {}
+
+/// Upper bound for the timeout of a probe, health checks must stay fast.
+const MAX_PROBE_TIMEOUT_MS: u64 = 30_000;
+
+/// Endpoints an app is allowed to call, see the `allowed_endpoints.yaml` of
+/// each app. Each entry is either a bare host, probed with the default probe
+/// of the host, see `ProbeConfig::for_host`, or a host with its probe:
+///
+/// ```yaml
+/// endpoints:
+///   - api.weatherapi.com
+///   - host: kms.us-east-1.amazonaws.com
+///     probe:
+///       path: /ping
+///       expected_body: healthy
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EndpointsConfig {
+    #[serde(default)]
+    pub endpoints: Vec<Endpoint>,
+}
+
+/// An allowed endpoint and how the health check probes it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "EndpointEntry")]
+pub struct Endpoint {
+    /// Host name, without scheme or path, e.g. "api.weatherapi.com".
+    pub host: String,
+    pub probe: ProbeConfig,
+}
+
+/// Entry of `endpoints`, a bare host or a host with its probe.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum EndpointEntry {
+    Host(String),
+    Detailed(DetailedEndpoint),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DetailedEndpoint {
+    host: String,
+    #[serde(default)]
+    probe: Option<ProbeConfig>,
+}
+
+impl From<EndpointEntry> for Endpoint {
+    fn from(entry: EndpointEntry) -> Self {
+        let (host, probe) = match entry {
+            EndpointEntry::Host(host) => (host, None),
+            EndpointEntry::Detailed(DetailedEndpoint { host, probe }) => (host, probe),
+        };
+        let probe = probe.unwrap_or_else(|| ProbeConfig::for_host(&host));
+        Endpoint { host, probe }
+    }
+}
+
+/// Probe of an endpoint, an HTTPS GET request on its host.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProbeConfig {
+    /// Path requested, "/" by default.
+    #[serde(default = "default_path")]
+    pub path: String,
+    /// Expected HTTP status, any 2xx status if unset.
+    #[serde(default)]
+    pub expected_status: Option<u16>,
+    /// Text the body must contain, ignoring case, if set.
+    #[serde(default)]
+    pub expected_body: Option<String>,
+    /// Timeout of the probe in milliseconds.
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_path() -> String {
+    "/".to_string()
+}
+
+fn default_timeout_ms() -> u64 {
+    5_000
+}
+
+impl Default for ProbeConfig {
+    fn default() -> Self {
+        Self {
+            path: default_path(),
+            expected_status: None,
+            expected_body: None,
+            timeout_ms: default_timeout_ms(),
+        }
+    }
+}
+
+impl ProbeConfig {
+    /// Probe of a host listed without one. AWS endpoints answer "healthy" on
+    /// /ping, other hosts are expected to answer / with a 2xx status.
+    pub fn for_host(host: &str) -> Self {
+        if host.ends_with(".amazonaws.com") {
+            Self {
+                path: "/ping".to_string(),
+                expected_body: Some("healthy".to_string()),
+                ..Self::default()
+            }
+        } else {
+            Self::default()
+        }
+    }
+}
+
+impl EndpointsConfig {
+    /// Parse and validate the `allowed_endpoints.yaml` of an app.
+    pub fn from_yaml(yaml: &str) -> Result<Self, InvalidEndpoints> {
+        let config: EndpointsConfig = serde_yaml::from_str(yaml)
+            .map_err(|e| InvalidEndpoints(format!("Failed to parse allowed endpoints: {}", e)))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Add the endpoints of `other` that are not listed yet. A host listed in
+    /// both must have the same probe in each.
+    pub fn merge(&mut self, other: EndpointsConfig) -> Result<(), InvalidEndpoints> {
+        for endpoint in other.endpoints {
+            match self.endpoints.iter().find(|e| e.host == endpoint.host) {
+                Some(existing) if *existing != endpoint => {
+                    return Err(InvalidEndpoints(format!(
+                        "Endpoint {} is listed with different probes",
+                        endpoint.host
+                    )))
+                }
+                Some(_) => {}
+                None => self.endpoints.push(endpoint),
+            }
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), InvalidEndpoints> {
+        let invalid = |msg: String| Err(InvalidEndpoints(msg));
+        for Endpoint { host, probe } in &self.endpoints {
+            let valid_host = !host.is_empty()
+                && host
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-');
+            if !valid_host {
+                return invalid(format!(
+                    "Invalid endpoint {}, expected a host name e.g. api.example.com",
+                    host
+                ));
+            }
+            if !probe.path.starts_with('/') {
+                return invalid(format!(
+                    "Probe path of {} must start with /, got {}",
+                    host, probe.path
+                ));
+            }
+            if probe.timeout_ms == 0 || probe.timeout_ms > MAX_PROBE_TIMEOUT_MS {
+                return invalid(format!(
+                    "Probe timeout_ms of {} must be in 1..={}, got {}",
+                    host, MAX_PROBE_TIMEOUT_MS, probe.timeout_ms
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_endpoints_config() {
+        let config = EndpointsConfig::from_yaml(
+            "endpoints:\n  - api.weatherapi.com\n  - host: kms.us-east-1.amazonaws.com\n    probe:\n      path: /ping\n      expected_body: healthy\n      timeout_ms: 1000\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.endpoints[0],
+            Endpoint {
+                host: "api.weatherapi.com".to_string(),
+                probe: ProbeConfig::default(),
+            }
+        );
+        let probe = &config.endpoints[1].probe;
+        assert_eq!(probe.path, "/ping");
+        assert_eq!(probe.expected_body.as_deref(), Some("healthy"));
+        assert_eq!(probe.expected_status, None);
+        assert_eq!(probe.timeout_ms, 1000);
+
+        // AWS endpoints listed without probe keep their /ping probe.
+        let config = EndpointsConfig::from_yaml(
+            "endpoints:\n  - kms.us-east-1.amazonaws.com\n  - host: secretsmanager.us-east-1.amazonaws.com\n",
+        )
+        .unwrap();
+        for endpoint in &config.endpoints {
+            assert_eq!(endpoint.probe.path, "/ping");
+            assert_eq!(endpoint.probe.expected_body.as_deref(), Some("healthy"));
+        }
+
+        assert!(EndpointsConfig::from_yaml("endpoints: []")
+            .unwrap()
+            .endpoints
+            .is_empty());
+        let invalid = |yaml: &str| EndpointsConfig::from_yaml(yaml).is_err();
+        assert!(invalid("endpoints:\n  - https://api.weatherapi.com"));
+        assert!(invalid(
+            "endpoints:\n  - host: a.com\n    probe:\n      path: ping"
+        ));
+        assert!(invalid(
+            "endpoints:\n  - host: a.com\n    probe:\n      timeout_ms: 0"
+        ));
+        assert!(invalid(
+            "endpoints:\n  - host: a.com\n    probe:\n      unknown: 1"
+        ));
+        assert!(invalid("allowed_endpoints: []"));
+    }
+
+    #[test]
+    fn test_baked_endpoints() {
+        for yaml in [
+            include_str!("../../nautilus-server/src/apps/weather-example/allowed_endpoints.yaml"),
+            include_str!("../../nautilus-server/src/apps/twitter-example/allowed_endpoints.yaml"),
+            include_str!("../../nautilus-server/src/apps/seal-example/allowed_endpoints.yaml"),
+            include_str!("../../nautilus-server/src/apps/random-example/allowed_endpoints.yaml"),
+        ] {
+            EndpointsConfig::from_yaml(yaml).unwrap();
+        }
+    }
+}
@@ -0,0 +1,78 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Generator of the traffic forwarding of the allowed endpoints, inside the
+//! enclave in `run.sh` and on the parent instance for vsock-proxy, see
+//! `forwarding.rs`. The endpoints of several apps are merged. Used by
+//! `configure_enclave.sh`, e.g.
+//! `cargo run -- run-sh ../nautilus-server/run.sh ../nautilus-server/src/apps/weather-example/allowed_endpoints.yaml`.
+//!
+//! Commands:
+//! - `hosts <yaml>...`: print the `/etc/hosts` lines of `run.sh`.
+//! - `forwarders <yaml>...`: print the traffic forwarder lines of `run.sh`.
+//! - `vsock-proxy-allowlist <yaml>...`: print the entries of the vsock-proxy allowlist.
+//! - `vsock-proxy <yaml>...`: print the parent commands allowing and starting the vsock-proxies.
+//! - `run-sh [--check] <run.sh> <yaml>...`: rewrite the generated blocks of
+//!   `run.sh`, or with `--check` fail if they are not up to date.
+
+use anyhow::{bail, Context, Result};
+use nautilus_endpoints::forwarding::ForwardingPlan;
+use nautilus_endpoints::EndpointsConfig;
+
+const USAGE: &str = "usage: nautilus-endpoints <hosts|forwarders|vsock-proxy-allowlist|vsock-proxy> <allowed_endpoints.yaml>...\n       nautilus-endpoints run-sh [--check] <run.sh> <allowed_endpoints.yaml>...";
+
+fn main() -> Result<()> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() {
+        bail!(USAGE);
+    }
+    let command = args.remove(0);
+    let check = if command == "run-sh" && args.first().map(String::as_str) == Some("--check") {
+        args.remove(0);
+        true
+    } else {
+        false
+    };
+    let run_sh = if command == "run-sh" {
+        if args.is_empty() {
+            bail!(USAGE);
+        }
+        Some(args.remove(0))
+    } else {
+        None
+    };
+
+    let mut config = EndpointsConfig::default();
+    for path in &args {
+        let yaml =
+            std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?;
+        config.merge(EndpointsConfig::from_yaml(&yaml).with_context(|| path.clone())?)?;
+    }
+    let plan = ForwardingPlan::new(&config)?;
+
+    match (command.as_str(), run_sh) {
+        ("hosts", _) => print!("{}", plan.hosts()),
+        ("forwarders", _) => print!("{}", plan.forwarders()),
+        ("vsock-proxy-allowlist", _) => print!("{}", plan.vsock_proxy_allowlist()),
+        ("vsock-proxy", _) => print!("{}", plan.vsock_proxy()),
+        ("run-sh", Some(path)) => {
+            let current = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path))?;
+            let rendered = plan.render_run_sh(&current)?;
+            if check {
+                if rendered != current {
+                    bail!(
+                        "{} is not up to date with the allowed endpoints, run nautilus-endpoints run-sh",
+                        path
+                    );
+                }
+            } else if rendered != current {
+                std::fs::write(&path, rendered)
+                    .with_context(|| format!("Failed to write {}", path))?;
+                eprintln!("updated {}", path);
+            }
+        }
+        _ => bail!(USAGE),
+    }
+    Ok(())
+}
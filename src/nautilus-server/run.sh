@@ -3,16 +3,16 @@
 # SPDX-License-Identifier: Apache-2.0
 
 # - Setup script for nautilus-server that acts as an init script
-# - Sets up Python and library paths
+# - Sets up library paths
 # - Configures loopback network and /etc/hosts
 # - Waits for secrets.json to be passed from the parent instance. 
 # - Forwards VSOCK port 3000 to localhost:3000
+# - Forwards VSOCK port 3001 to localhost:3001 for the host-only server
 # - Optionally pulls secrets and sets in environmen variables.
 # - Launches nautilus-server
 
 set -e # Exit immediately if a command exits with a non-zero status
 echo "run.sh script is running"
-export PYTHONPATH=/lib/python3.11:/usr/local/lib/python3.11/lib-dynload:/usr/local/lib/python3.11/site-packages:/lib
 export LD_LIBRARY_PATH=/lib:$LD_LIBRARY_PATH
 
 echo "Script completed."
@@ -23,17 +23,10 @@ busybox ip link set dev lo up
 # Add a hosts record, pointing target site calls to local loopback
 echo "127.0.0.1   localhost" > /etc/hosts
 
-
-
-
-
-
-
-
-
-
-
-# == ATTENTION: code should be generated here that parses allowed_endpoints.yaml and populate domains here ===
+# One loopback address per allowed endpoint, generated from allowed_endpoints.yaml
+# by `nautilus-endpoints run-sh`, see `src/nautilus-endpoints`. Do not edit by hand.
+# BEGIN nautilus-endpoints hosts
+# END nautilus-endpoints hosts
 
 cat /etc/hosts
 
@@ -45,22 +38,22 @@ JSON_RESPONSE=$(socat - VSOCK-LISTEN:7777,reuseaddr)
 echo "$JSON_RESPONSE" | jq -r 'to_entries[] | "\(.key)=\(.value)"' > /tmp/kvpairs ; while IFS="=" read -r key value; do export "$key"="$value"; done < /tmp/kvpairs ; rm -f /tmp/kvpairs
 
 # Run traffic forwarder in background and start the server
-# Forwards traffic from 127.0.0.x -> Port 443 at CID 3 Listening on port 800x
+# Forwards traffic from 127.0.0.x -> Port 443 at CID 3 Listening on port 81xx
 # There is a vsock-proxy that listens for this and forwards to the respective domains
+# The forwarder logs its connection and byte counters per domain every minute to the
+# console, they are not exported to /metrics
 
-# == ATTENTION: code should be generated here that added all hosts to forward traffic ===
-# Traffic-forwarder-block
-
-
-
-
-
-
-
-
-
+# One traffic forwarder per allowed endpoint, generated with the hosts above.
+# BEGIN nautilus-endpoints forwarders
+# END nautilus-endpoints forwarders
 
 # Listens on Local VSOCK Port 3000 and forwards to localhost 3000
 socat VSOCK-LISTEN:3000,reuseaddr,fork TCP:localhost:3000 &
 
-/nautilus-server
+# Listens on Local VSOCK Port 3001 and forwards to localhost 3001, the host-only
+# server for metrics and bootstrap routes. Only exposed on the host's localhost.
+socat VSOCK-LISTEN:3001,reuseaddr,fork TCP:localhost:3001 &
+
+# Replace the shell so SIGTERM reaches the server, which drains in-flight
+# requests before exiting.
+exec /nautilus-server
@@ -0,0 +1,307 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Aggregation of the BLS12-381 signatures of enclave replicas, so that a
+//! consumer checks k-of-n agreement with a single signature verification. Each
+//! replica runs with `bls_signing` and signs the same bytes, see `BlsSignature`.
+//! The aggregate signature is checked against the committee with
+//! `verify_aggregate` in `move/enclave/sources/bls_committee.move`.
+
+use crate::common::ProcessedDataResponse;
+use crate::EnclaveError;
+use fastcrypto::bls12381::min_sig::{
+    BLS12381AggregateSignature, BLS12381PublicKey, BLS12381Signature,
+};
+use fastcrypto::encoding::{Encoding, Hex};
+use fastcrypto::traits::{AggregateAuthenticator, ToFromBytes, VerifyingKey};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tracing::warn;
+
+/// Maximum number of replicas in a committee.
+pub const MAX_COMMITTEE_SIZE: usize = 256;
+
+/// BLS12-381 public keys of the replicas, in the order of the signer bitmap,
+/// and the number of them that must sign the same message.
+#[derive(Debug, Clone)]
+pub struct Committee {
+    members: Vec<BLS12381PublicKey>,
+    threshold: usize,
+}
+
+/// Aggregate signature of the replicas that signed the same message.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AggregatedResponse<T> {
+    /// Response of one of the signers, every signer signed `message` for it.
+    pub response: T,
+    /// Hex encoded signed bytes.
+    pub message: String,
+    /// Hex encoded aggregate signature of 48 bytes.
+    pub signature: String,
+    /// Hex encoded signer bitmap, bit `i % 8` of byte `i / 8` is set if
+    /// member `i` of the committee signed.
+    pub signers: String,
+    /// Number of signers.
+    pub signer_count: usize,
+}
+
+impl Committee {
+    pub fn new(members: Vec<BLS12381PublicKey>, threshold: usize) -> Result<Self, EnclaveError> {
+        if members.is_empty() || members.len() > MAX_COMMITTEE_SIZE {
+            return Err(EnclaveError::BadRequest(format!(
+                "A committee must have 1 to {} members, got {}",
+                MAX_COMMITTEE_SIZE,
+                members.len()
+            )));
+        }
+        if threshold == 0 || threshold > members.len() {
+            return Err(EnclaveError::BadRequest(format!(
+                "Threshold must be 1 to {}, got {}",
+                members.len(),
+                threshold
+            )));
+        }
+        for (i, member) in members.iter().enumerate() {
+            if members[..i].contains(member) {
+                return Err(EnclaveError::BadRequest(format!(
+                    "Committee member {} is a duplicate",
+                    i
+                )));
+            }
+        }
+        Ok(Self { members, threshold })
+    }
+
+    /// Committee of hex encoded public keys.
+    pub fn from_hex(members: &[String], threshold: usize) -> Result<Self, EnclaveError> {
+        let members = members
+            .iter()
+            .map(|pk| {
+                Hex::decode(pk)
+                    .ok()
+                    .and_then(|bytes| BLS12381PublicKey::from_bytes(&bytes).ok())
+                    .ok_or_else(|| {
+                        EnclaveError::BadRequest(format!("Invalid BLS12-381 public key {}", pk))
+                    })
+            })
+            .collect::<Result<_, _>>()?;
+        Self::new(members, threshold)
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// Aggregate the signatures of the members over the message signed by the
+    /// most members. Responses without a valid signature of a member are
+    /// ignored, and fewer than `threshold` agreeing members is an error.
+    pub fn aggregate<T>(
+        &self,
+        responses: Vec<ProcessedDataResponse<T>>,
+    ) -> Result<AggregatedResponse<T>, EnclaveError> {
+        // Signers by signed message, the first response is kept for each message.
+        let mut groups: BTreeMap<Vec<u8>, (T, BTreeMap<usize, BLS12381Signature>)> =
+            BTreeMap::new();
+        for response in responses {
+            let Some(bls) = &response.bls else {
+                warn!("ignoring response without BLS signature");
+                continue;
+            };
+            let Some((index, message, signature)) =
+                self.verify(&bls.public_key, &bls.message, &bls.signature)
+            else {
+                warn!(
+                    "ignoring response with an invalid signature or an unknown public key {}",
+                    bls.public_key
+                );
+                continue;
+            };
+            groups
+                .entry(message)
+                .or_insert_with(|| (response.response, BTreeMap::new()))
+                .1
+                .insert(index, signature);
+        }
+
+        let (message, (response, signatures)) = groups
+            .into_iter()
+            .max_by_key(|(_, (_, signatures))| signatures.len())
+            .ok_or_else(|| {
+                EnclaveError::UpstreamError("No replica returned a valid signature".to_string())
+            })?;
+        if signatures.len() < self.threshold {
+            return Err(EnclaveError::UpstreamError(format!(
+                "Only {} of {} replicas agree, {} required",
+                signatures.len(),
+                self.members.len(),
+                self.threshold
+            )));
+        }
+
+        let signature = BLS12381AggregateSignature::aggregate(signatures.values())
+            .map_err(|e| EnclaveError::InternalError(format!("Failed to aggregate: {}", e)))?;
+        Ok(AggregatedResponse {
+            response,
+            message: Hex::encode(message),
+            signature: Hex::encode(signature.as_bytes()),
+            signers: Hex::encode(signer_bitmap(signatures.keys().copied(), self.len())),
+            signer_count: signatures.len(),
+        })
+    }
+
+    /// Index of the member and the decoded message and signature, if the
+    /// public key is a member's and the signature is valid.
+    fn verify(
+        &self,
+        public_key: &str,
+        message: &str,
+        signature: &str,
+    ) -> Option<(usize, Vec<u8>, BLS12381Signature)> {
+        let public_key = BLS12381PublicKey::from_bytes(&Hex::decode(public_key).ok()?).ok()?;
+        let index = self.members.iter().position(|pk| *pk == public_key)?;
+        let message = Hex::decode(message).ok()?;
+        let signature = BLS12381Signature::from_bytes(&Hex::decode(signature).ok()?).ok()?;
+        public_key.verify(&message, &signature).ok()?;
+        Some((index, message, signature))
+    }
+}
+
+/// Bitmap of `len` bits with the bits of `indices` set, bit `i % 8` of byte `i / 8`.
+pub fn signer_bitmap(indices: impl IntoIterator<Item = usize>, len: usize) -> Vec<u8> {
+    let mut bitmap = vec![0u8; len.div_ceil(8)];
+    for i in indices {
+        bitmap[i / 8] |= 1 << (i % 8);
+    }
+    bitmap
+}
+
+/// Send the same request to every replica concurrently and return the
+/// responses of the replicas that succeeded.
+pub async fn collect(
+    client: &Client,
+    replicas: &[String],
+    path: &str,
+    body: &serde_json::Value,
+) -> Vec<ProcessedDataResponse<serde_json::Value>> {
+    let requests = replicas.iter().map(|replica| async move {
+        let url = format!("{}{}", replica.trim_end_matches('/'), path);
+        let response = client
+            .post(&url)
+            .json(body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        match response {
+            Ok(response) => response
+                .json::<ProcessedDataResponse<serde_json::Value>>()
+                .await
+                .inspect_err(|e| warn!("invalid response from {}: {}", url, e))
+                .ok(),
+            Err(e) => {
+                warn!("request to {} failed: {}", url, e);
+                None
+            }
+        }
+    });
+    futures::future::join_all(requests)
+        .await
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::{to_signed_response, IntentMessage, IntentScope};
+    use crate::keys::{test_keys, KeyManager, SignatureScheme};
+    use std::time::Duration;
+
+    #[test]
+    fn test_aggregate() {
+        let replicas: Vec<KeyManager> = (0..4)
+            .map(|_| test_keys(SignatureScheme::Ed25519, Duration::ZERO, true))
+            .collect();
+        let members: Vec<String> = replicas
+            .iter()
+            .map(|keys| keys.current().bls_public_key_hex().unwrap())
+            .collect();
+        let committee = Committee::from_hex(&members, 2).unwrap();
+        let sign = |replica: usize, value: u64| {
+            to_signed_response(
+                &replicas[replica].current(),
+                value,
+                1744038900000,
+                IntentScope::ProcessData,
+                None,
+            )
+        };
+
+        // Replicas 0, 2 and 3 agree, replica 1 signed another value.
+        let aggregated = committee
+            .aggregate(vec![
+                sign(0, 7),
+                sign(1, 8),
+                sign(2, 7),
+                sign(3, 7),
+                sign(3, 7),
+            ])
+            .unwrap();
+        assert_eq!(aggregated.response.data, 7);
+        assert_eq!(aggregated.signer_count, 3);
+        assert_eq!(aggregated.signers, "0d");
+
+        let message = Hex::decode(&aggregated.message).unwrap();
+        assert_eq!(
+            message,
+            bcs::to_bytes(&IntentMessage::new(
+                7u64,
+                1744038900000,
+                IntentScope::ProcessData
+            ))
+            .unwrap()
+        );
+        let signature =
+            BLS12381AggregateSignature::from_bytes(&Hex::decode(&aggregated.signature).unwrap())
+                .unwrap();
+        let signers: Vec<BLS12381PublicKey> = [0, 2, 3]
+            .iter()
+            .map(|&i| committee.members[i].clone())
+            .collect();
+        signature.verify(&signers, &message).unwrap();
+
+        // Below the threshold, or signed by keys outside of the committee.
+        let committee = Committee::from_hex(&members, 3).unwrap();
+        assert!(committee.aggregate(vec![sign(0, 7), sign(1, 7)]).is_err());
+        let outsider = test_keys(SignatureScheme::Ed25519, Duration::ZERO, true);
+        let outsider = to_signed_response(
+            &outsider.current(),
+            7u64,
+            1744038900000,
+            IntentScope::ProcessData,
+            None,
+        );
+        assert!(committee
+            .aggregate(vec![sign(0, 7), sign(1, 7), outsider])
+            .is_err());
+
+        assert!(Committee::from_hex(&members, 5).is_err());
+        assert!(Committee::from_hex(&[members[0].clone(), members[0].clone()], 1).is_err());
+    }
+
+    #[test]
+    fn test_signer_bitmap() {
+        assert_eq!(signer_bitmap([0, 2, 3], 4), vec![0x0d]);
+        assert_eq!(signer_bitmap([1, 8], 9), vec![0x02, 0x01]);
+        assert_eq!(signer_bitmap([], 8), vec![0x00]);
+    }
+}
@@ -1,41 +1,93 @@
 // Copyright (c), Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::batch::{process_batch as sign_batch, ProcessBatchRequest, ProcessedBatchResponse};
 use crate::common::IntentMessage;
 use crate::common::{to_signed_response, IntentScope, ProcessDataRequest, ProcessedDataResponse};
+use crate::endpoints::EndpointsConfig;
+use crate::registry::{AppContext, EnclaveApp};
 use crate::AppState;
 use crate::EnclaveError;
 use axum::extract::State;
-use axum::Json;
-use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use axum::routing::post;
+use axum::{Json, Router};
 use rand::Rng;
+use std::sync::Arc;
+
+/// Inner types of IntentMessage<T> and ProcessDataRequest<T>, shared with clients.
+pub use nautilus_types::apps::random::{RandomRequest, RandomResponse};
+
+/// Allowed endpoints of the app, baked into the enclave image.
+const ALLOWED_ENDPOINTS: &str = include_str!("allowed_endpoints.yaml");
 
-/// Inner type T for IntentMessage<T>
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct RandomResponse {
-    pub random_number: u64,
-    pub min: u64,
-    pub max: u64,
+/// Random number app, signs a number drawn uniformly from a requested range.
+#[derive(Default)]
+pub struct RandomApp;
+
+impl RandomApp {
+    pub fn new() -> Self {
+        Self
+    }
 }
 
-/// Inner type T for ProcessDataRequest<T>
-#[derive(Debug, Serialize, Deserialize)]
-pub struct RandomRequest {
-    pub min: u64,
-    pub max: u64,
+impl EnclaveApp for RandomApp {
+    fn name(&self) -> &'static str {
+        "random"
+    }
+
+    fn intent_scopes(&self) -> &'static [IntentScope] {
+        &[IntentScope::ProcessData, IntentScope::BatchRoot]
+    }
+
+    fn routes(self: Arc<Self>, state: Arc<AppState>) -> Router {
+        Router::new()
+            .route("/process_data", post(process_data))
+            .route("/process_batch", post(process_batch))
+            .with_state(AppContext::new(state, self))
+    }
+
+    fn allowed_endpoints(&self) -> Result<EndpointsConfig, EnclaveError> {
+        Ok(EndpointsConfig::from_yaml(ALLOWED_ENDPOINTS)?)
+    }
 }
 
 pub async fn process_data(
-    State(state): State<Arc<AppState>>,
+    State(ctx): State<AppContext<RandomApp>>,
     Json(request): Json<ProcessDataRequest<RandomRequest>>,
 ) -> Result<Json<ProcessedDataResponse<IntentMessage<RandomResponse>>>, EnclaveError> {
-    let min = request.payload.min;
-    let max = request.payload.max;
+    let binding = request.binding()?;
+    let (response, current_timestamp) = random_number(request.payload)?;
+    let key = ctx.enclave.signing_key(request.key_epoch)?;
+    Ok(Json(to_signed_response(
+        &key,
+        response,
+        current_timestamp,
+        IntentScope::ProcessData,
+        binding,
+    )))
+}
+
+/// Draw a random number for every request of the batch, signed with a single
+/// signature over the Merkle root of the results.
+pub async fn process_batch(
+    State(ctx): State<AppContext<RandomApp>>,
+    Json(request): Json<ProcessBatchRequest<RandomRequest>>,
+) -> Result<Json<ProcessedBatchResponse<IntentMessage<RandomResponse>>>, EnclaveError> {
+    let response = sign_batch(&ctx.enclave, request, IntentScope::ProcessData, |payload| {
+        std::future::ready(random_number(payload))
+    })
+    .await?;
+    Ok(Json(response))
+}
+
+/// Draw a number in the requested range, returned with the current timestamp.
+fn random_number(request: RandomRequest) -> Result<(RandomResponse, u64), EnclaveError> {
+    let min = request.min;
+    let max = request.max;
 
     // Validate input
     if min >= max {
-        return Err(EnclaveError::GenericError(
+        return Err(EnclaveError::BadRequest(
             "min must be less than max".to_string(),
         ));
     }
@@ -46,47 +98,44 @@ pub async fn process_data(
 
     let current_timestamp = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
-        .map_err(|e| EnclaveError::GenericError(format!("Failed to get current timestamp: {}", e)))?
+        .map_err(|e| {
+            EnclaveError::InternalError(format!("Failed to get current timestamp: {}", e))
+        })?
         .as_millis() as u64;
 
-    Ok(Json(to_signed_response(
-        &state.eph_kp,
+    Ok((
         RandomResponse {
             random_number,
             min,
             max,
         },
         current_timestamp,
-        IntentScope::ProcessData,
-    )))
+    ))
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::common::IntentMessage;
+    use crate::test_state;
     use axum::{extract::State, Json};
-    use fastcrypto::{ed25519::Ed25519KeyPair, traits::KeyPair};
 
     #[tokio::test]
     async fn test_process_data() {
-        let state = Arc::new(AppState {
-            eph_kp: Ed25519KeyPair::generate(&mut rand::thread_rng()),
-            api_key: String::new(),
-        });
-        
+        let ctx = AppContext::new(Arc::new(test_state()), Arc::new(RandomApp::new()));
+
         let signed_random_response = process_data(
-            State(state),
+            State(ctx),
             Json(ProcessDataRequest {
-                payload: RandomRequest {
-                    min: 1,
-                    max: 100,
-                },
+                payload: RandomRequest { min: 1, max: 100 },
+                key_epoch: None,
+                nonce: None,
+                caller: None,
             }),
         )
         .await
         .unwrap();
-        
+
         let random_num = signed_random_response.response.data.random_number;
         assert!(random_num >= 1 && random_num <= 100);
     }
@@ -102,9 +151,9 @@ mod test {
         let timestamp = 1744038900000;
         let intent_msg = IntentMessage::new(payload, timestamp, IntentScope::ProcessData);
         let signing_payload = bcs::to_bytes(&intent_msg).expect("should not fail");
-        
+
         // This will vary based on the exact structure, but ensures serialization works
         println!("Serialized: {}", Hex::encode(&signing_payload));
         assert!(!signing_payload.is_empty());
     }
-}
\ No newline at end of file
+}
@@ -21,7 +21,15 @@ use sui_sdk_types::{
 use tokio::sync::RwLock;
 
 use super::types::*;
-use crate::{AppState, EnclaveError};
+use super::SealApp;
+use crate::keys::SigningKey;
+use crate::metrics::BOOTSTRAP_STATE;
+use crate::registry::AppContext;
+use crate::EnclaveError;
+
+/// Values of the bootstrap state gauge for the Seal app.
+const BOOTSTRAP_INIT_DONE: i64 = 1;
+const BOOTSTRAP_COMPLETE: i64 = 2;
 
 lazy_static::lazy_static! {
     /// Configuration for Seal key servers, containing package
@@ -50,20 +58,18 @@ lazy_static::lazy_static! {
 /// and the desired ptb for seal_approve. This is the first step for
 /// the bootstrap phase.
 pub async fn init_parameter_load(
-    State(state): State<Arc<AppState>>,
+    State(ctx): State<AppContext<SealApp>>,
     Json(request): Json<InitParameterLoadRequest>,
 ) -> Result<Json<InitParameterLoadResponse>, EnclaveError> {
     if SEAL_API_KEY.read().await.is_some() {
-        return Err(EnclaveError::GenericError(
-            "API key already set".to_string(),
-        ));
+        return Err(EnclaveError::BadRequest("API key already set".to_string()));
     }
     // Generate the session and create certificate.
     let session = Ed25519KeyPair::generate(&mut thread_rng());
     let session_vk = session.public();
     let creation_time = SystemTime::now()
         .duration_since(UNIX_EPOCH)
-        .map_err(|e| EnclaveError::GenericError(format!("Time error: {}", e)))?
+        .map_err(|e| EnclaveError::InternalError(format!("Time error: {}", e)))?
         .as_millis() as u64;
     let ttl_min = 10;
     let message = signed_message(
@@ -73,9 +79,17 @@ pub async fn init_parameter_load(
         ttl_min,
     );
 
-    // Convert fastcrypto keypair to sui-crypto for signing.
+    // Convert fastcrypto keypair to sui-crypto for signing, Seal certificates
+    // are signed by an Ed25519 Sui address.
     let sui_private_key = {
-        let priv_key_bytes = state.eph_kp.as_ref();
+        let key = ctx.enclave.keys.current();
+        let SigningKey::Ed25519(kp) = &key.kp else {
+            return Err(EnclaveError::InternalError(format!(
+                "Seal requires the ed25519 signature scheme, got {}",
+                key.kp.scheme()
+            )));
+        };
+        let priv_key_bytes = kp.as_ref();
         let key_bytes: [u8; 32] = priv_key_bytes
             .try_into()
             .expect("Invalid private key length");
@@ -88,7 +102,7 @@ pub async fn init_parameter_load(
         sui_private_key
             .sign_personal_message(&PersonalMessage(message.as_bytes().into()))
             .map_err(|e| {
-                EnclaveError::GenericError(format!("Failed to sign personal message: {}", e))
+                EnclaveError::InternalError(format!("Failed to sign personal message: {}", e))
             })?
     };
 
@@ -110,7 +124,7 @@ pub async fn init_parameter_load(
         request.ids,
     )
     .await
-    .map_err(|e| EnclaveError::GenericError(format!("Failed to create PTB: {}", e)))?;
+    .map_err(|e| EnclaveError::BadRequest(format!("Failed to create PTB: {}", e)))?;
 
     // Load the encryption public key and verification key.
     let (_enc_secret, enc_key, enc_verification_key) = &*ENCRYPTION_KEYS;
@@ -126,6 +140,9 @@ pub async fn init_parameter_load(
         certificate,
     };
 
+    BOOTSTRAP_STATE
+        .with_label_values(&["seal"])
+        .set(BOOTSTRAP_INIT_DONE);
     Ok(Json(InitParameterLoadResponse {
         encoded_request: Hex::encode(bcs::to_bytes(&request).expect("should not fail")),
     }))
@@ -138,13 +155,11 @@ pub async fn init_parameter_load(
 /// Remove dummy secrets for your app. This is done after the Seal responses are fetched
 /// and to complete the bootstrap phase.
 pub async fn complete_parameter_load(
-    State(_state): State<Arc<AppState>>,
+    State(_ctx): State<AppContext<SealApp>>,
     Json(request): Json<CompleteParameterLoadRequest>,
 ) -> Result<Json<CompleteParameterLoadResponse>, EnclaveError> {
     if SEAL_API_KEY.read().await.is_some() {
-        return Err(EnclaveError::GenericError(
-            "API key already set".to_string(),
-        ));
+        return Err(EnclaveError::BadRequest("API key already set".to_string()));
     }
 
     // Load the encryption secret key and try decrypting all encrypted objects.
@@ -155,17 +170,20 @@ pub async fn complete_parameter_load(
         &request.encrypted_objects,
         &SEAL_CONFIG.server_pk_map,
     )
-    .map_err(|e| EnclaveError::GenericError(format!("Failed to decrypt objects: {}", e)))?;
+    .map_err(|e| EnclaveError::BadRequest(format!("Failed to decrypt objects: {}", e)))?;
 
     // The first secret is the weather API key, store it.
     if let Some(api_key_bytes) = decrypted_results.first() {
         let api_key_str = String::from_utf8(api_key_bytes.clone())
-            .map_err(|e| EnclaveError::GenericError(format!("Invalid UTF-8 in secret: {}", e)))?;
+            .map_err(|e| EnclaveError::Unprocessable(format!("Invalid UTF-8 in secret: {}", e)))?;
 
         let mut api_key_guard = (*SEAL_API_KEY).write().await;
         *api_key_guard = Some(api_key_str.clone());
+        BOOTSTRAP_STATE
+            .with_label_values(&["seal"])
+            .set(BOOTSTRAP_COMPLETE);
     } else {
-        return Err(EnclaveError::GenericError(
+        return Err(EnclaveError::BadRequest(
             "No secrets were decrypted".to_string(),
         ));
     }
@@ -7,17 +7,23 @@ pub mod types;
 pub use endpoints::{complete_parameter_load, init_parameter_load};
 pub use types::*;
 
-use crate::app::endpoints::SEAL_API_KEY;
 use crate::common::IntentMessage;
 use crate::common::{to_signed_response, IntentScope, ProcessDataRequest, ProcessedDataResponse};
+use crate::egress::EgressClient;
+use crate::endpoints::EndpointsConfig;
+use crate::failure::{attest_failure, ProcessDataError};
+use crate::metrics::BOOTSTRAP_STATE;
+use crate::readiness::ReadinessCheck;
+use crate::registry::{AppContext, EnclaveApp};
 use crate::AppState;
 use crate::EnclaveError;
 use axum::extract::State;
-use axum::Json;
+use axum::routing::post;
+use axum::{Json, Router};
+use endpoints::SEAL_API_KEY;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::sync::Arc;
-use tracing::info;
 /// Inner type T for IntentMessage<T>
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct WeatherResponse {
@@ -31,27 +37,106 @@ pub struct WeatherRequest {
     pub location: String,
 }
 
+/// Allowed endpoints of the app, baked into the enclave image.
+const ALLOWED_ENDPOINTS: &str = include_str!("allowed_endpoints.yaml");
+
+/// Seal app, serves weather data with an API key that is loaded through the
+/// two phase Seal bootstrap on the host-only listener.
+#[derive(Default)]
+pub struct SealApp;
+
+impl SealApp {
+    pub fn new() -> Self {
+        BOOTSTRAP_STATE.with_label_values(&["seal"]).set(0);
+        Self
+    }
+}
+
+impl EnclaveApp for SealApp {
+    fn name(&self) -> &'static str {
+        "seal"
+    }
+
+    fn intent_scopes(&self) -> &'static [IntentScope] {
+        &[IntentScope::ProcessData, IntentScope::Failure]
+    }
+
+    fn routes(self: Arc<Self>, state: Arc<AppState>) -> Router {
+        Router::new()
+            .route("/process_data", post(process_data))
+            .with_state(AppContext::new(state, self))
+    }
+
+    fn host_routes(self: Arc<Self>, state: Arc<AppState>) -> Option<Router> {
+        Some(
+            Router::new()
+                .route("/seal/init_parameter_load", post(init_parameter_load))
+                .route(
+                    "/seal/complete_parameter_load",
+                    post(complete_parameter_load),
+                )
+                .with_state(AppContext::new(state, self)),
+        )
+    }
+
+    fn allowed_endpoints(&self) -> Result<EndpointsConfig, EnclaveError> {
+        Ok(EndpointsConfig::from_yaml(ALLOWED_ENDPOINTS)?)
+    }
+
+    fn readiness(&self) -> Vec<ReadinessCheck> {
+        // A write lock is only held while the key is set, so it is not loaded yet.
+        let loaded = match SEAL_API_KEY.try_read() {
+            Ok(api_key) if api_key.is_some() => Ok(()),
+            _ => Err("Seal secrets are not loaded, call /complete_parameter_load".to_string()),
+        };
+        vec![ReadinessCheck::new("secrets_loaded", loaded)]
+    }
+}
+
 pub async fn process_data(
-    State(state): State<Arc<AppState>>,
+    State(ctx): State<AppContext<SealApp>>,
     Json(request): Json<ProcessDataRequest<WeatherRequest>>,
-) -> Result<Json<ProcessedDataResponse<IntentMessage<WeatherResponse>>>, EnclaveError> {
+) -> Result<Json<ProcessedDataResponse<IntentMessage<WeatherResponse>>>, ProcessDataError> {
+    let binding = request.binding()?;
     // API key loaded from what was set during bootstrap.
     let api_key_guard = SEAL_API_KEY.read().await;
     let api_key = api_key_guard.as_ref().ok_or_else(|| {
-        EnclaveError::GenericError(
+        EnclaveError::NotReady(
             "API key not initialized. Please complete parameter load first.".to_string(),
         )
     })?;
 
+    let (response, last_updated_timestamp_ms) =
+        current_weather(&ctx.enclave.egress, api_key, &request.payload.location)
+            .await
+            .map_err(|e| attest_failure(&ctx.enclave, &request, e))?;
+    let key = ctx.enclave.signing_key(request.key_epoch)?;
+    Ok(Json(to_signed_response(
+        &key,
+        response,
+        last_updated_timestamp_ms,
+        IntentScope::ProcessData,
+        binding,
+    )))
+}
+
+/// Current weather of a location, returned with the time it was last updated.
+async fn current_weather(
+    egress: &EgressClient,
+    api_key: &str,
+    location: &str,
+) -> Result<(WeatherResponse, u64), EnclaveError> {
     let url = format!(
         "https://api.weatherapi.com/v1/current.json?key={}&q={}",
-        api_key, request.payload.location
+        api_key, location
     );
-    let response = reqwest::get(url.clone()).await.map_err(|e| {
-        EnclaveError::GenericError(format!("Failed to get weather response: {}", e))
-    })?;
+    let response = egress
+        .get(&url)?
+        .send()
+        .await
+        .map_err(|e| EnclaveError::upstream("Failed to get weather response", e))?;
     let json = response.json::<Value>().await.map_err(|e| {
-        EnclaveError::GenericError(format!("Failed to parse weather response: {}", e))
+        EnclaveError::UpstreamError(format!("Failed to parse weather response: {}", e))
     })?;
     let location = json["location"]["name"].as_str().unwrap_or("Unknown");
     let temperature = json["current"]["temp_c"].as_f64().unwrap_or(0.0) as u64;
@@ -59,75 +144,25 @@ pub async fn process_data(
     let last_updated_timestamp_ms = last_updated_epoch * 1000_u64;
     let current_timestamp = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
-        .map_err(|e| EnclaveError::GenericError(format!("Failed to get current timestamp: {}", e)))?
+        .map_err(|e| {
+            EnclaveError::InternalError(format!("Failed to get current timestamp: {}", e))
+        })?
         .as_millis() as u64;
 
     // 1 hour in milliseconds = 60 * 60 * 1000 = 3_600_000
     if last_updated_timestamp_ms + 3_600_000 < current_timestamp {
-        return Err(EnclaveError::GenericError(
+        return Err(EnclaveError::Unprocessable(
             "Weather API timestamp is too old".to_string(),
         ));
     }
 
-    Ok(Json(to_signed_response(
-        &state.eph_kp,
+    Ok((
         WeatherResponse {
             location: location.to_string(),
             temperature,
         },
         last_updated_timestamp_ms,
-        IntentScope::ProcessData,
-    )))
-}
-
-/// Host-only init functionality
-use axum::{
-    routing::{get, post},
-    Router,
-};
-use tokio::net::TcpListener;
-
-/// Response for the ping endpoint
-#[derive(Debug, Serialize, Deserialize)]
-pub struct PingResponse {
-    pub message: String,
-}
-
-/// Simple ping handler for host-only access
-pub async fn ping() -> Json<PingResponse> {
-    info!("Host init ping received");
-    Json(PingResponse {
-        message: "pong".to_string(),
-    })
-}
-
-/// Spawn a separate server on localhost:3001 for host-only bootstrap access.
-pub async fn spawn_host_init_server(state: Arc<AppState>) -> Result<(), EnclaveError> {
-    let host_app = Router::new()
-        .route("/ping", get(ping))
-        .route("/seal/init_parameter_load", post(init_parameter_load))
-        .route(
-            "/seal/complete_parameter_load",
-            post(complete_parameter_load),
-        )
-        .with_state(state);
-
-    let host_listener = TcpListener::bind("0.0.0.0:3001").await.map_err(|e| {
-        EnclaveError::GenericError(format!("Failed to bind host init server: {}", e))
-    })?;
-
-    info!(
-        "Host-only init server listening on {}",
-        host_listener.local_addr().unwrap()
-    );
-
-    tokio::spawn(async move {
-        axum::serve(host_listener, host_app.into_make_service())
-            .await
-            .expect("Host init server failed");
-    });
-
-    Ok(())
+    ))
 }
 
 #[cfg(test)]
@@ -3,70 +3,109 @@
 
 use crate::common::IntentMessage;
 use crate::common::{to_signed_response, IntentScope, ProcessDataRequest, ProcessedDataResponse};
+use crate::egress::EgressClient;
+use crate::endpoints::EndpointsConfig;
+use crate::failure::{attest_failure, ProcessDataError};
+use crate::registry::{AppContext, EnclaveApp};
 use crate::AppState;
 use crate::EnclaveError;
 use axum::extract::State;
-use axum::Json;
+use axum::routing::post;
+use axum::{Json, Router};
 use fastcrypto::encoding::{Encoding, Hex};
-use regex::Regex;
-use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-use tracing::info;
 /// ====
 /// Core Nautilus server logic, replace it with your own
 /// relavant structs and process_data endpoint.
 /// ====
-/// Inner type for IntentMessage<T>
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct UserData {
-    pub twitter_name: Vec<u8>,
-    pub sui_address: Vec<u8>,
+/// Inner types of IntentMessage<T> and ProcessDataRequest<T>, shared with clients.
+pub use nautilus_types::apps::twitter::{UserData, UserRequest};
+use regex::Regex;
+use std::sync::Arc;
+use tracing::info;
+
+/// Allowed endpoints of the app, baked into the enclave image.
+const ALLOWED_ENDPOINTS: &str = include_str!("allowed_endpoints.yaml");
+
+/// Twitter app, signs the binding between a Twitter handle and a Sui address.
+pub struct TwitterApp {
+    /// Bearer token when querying api.twitter.com
+    pub api_key: String,
 }
 
-/// Inner type for ProcessDataRequest<T>
-#[derive(Debug, Serialize, Deserialize)]
-pub struct UserRequest {
-    pub user_url: String,
+impl TwitterApp {
+    pub fn from_env() -> Result<Self, EnclaveError> {
+        let api_key = std::env::var("TWITTER_API_KEY")
+            .map_err(|_| EnclaveError::InternalError("TWITTER_API_KEY must be set".to_string()))?;
+        Ok(Self { api_key })
+    }
+}
+
+impl EnclaveApp for TwitterApp {
+    fn name(&self) -> &'static str {
+        "twitter"
+    }
+
+    fn intent_scopes(&self) -> &'static [IntentScope] {
+        &[IntentScope::ProcessData, IntentScope::Failure]
+    }
+
+    fn routes(self: Arc<Self>, state: Arc<AppState>) -> Router {
+        Router::new()
+            .route("/process_data", post(process_data))
+            .with_state(AppContext::new(state, self))
+    }
+
+    fn allowed_endpoints(&self) -> Result<EndpointsConfig, EnclaveError> {
+        Ok(EndpointsConfig::from_yaml(ALLOWED_ENDPOINTS)?)
+    }
 }
 
 pub async fn process_data(
-    State(state): State<Arc<AppState>>,
+    State(ctx): State<AppContext<TwitterApp>>,
     Json(request): Json<ProcessDataRequest<UserRequest>>,
-) -> Result<Json<ProcessedDataResponse<IntentMessage<UserData>>>, EnclaveError> {
+) -> Result<Json<ProcessedDataResponse<IntentMessage<UserData>>>, ProcessDataError> {
+    let binding = request.binding()?;
     let user_url = request.payload.user_url.clone();
     info!("Processing data for user URL: {}", user_url);
 
     let current_timestamp = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
-        .map_err(|e| EnclaveError::GenericError(format!("Failed to get current timestamp: {}", e)))?
+        .map_err(|e| {
+            EnclaveError::InternalError(format!("Failed to get current timestamp: {}", e))
+        })?
         .as_millis() as u64;
     // Fetch tweet content
-    let (twitter_name, sui_address) = fetch_tweet_content(&state.api_key, &user_url).await?;
+    let (twitter_name, sui_address) =
+        fetch_tweet_content(&ctx.enclave.egress, &ctx.app.api_key, &user_url)
+            .await
+            .map_err(|e| attest_failure(&ctx.enclave, &request, e))?;
+    let key = ctx.enclave.signing_key(request.key_epoch)?;
     Ok(Json(to_signed_response(
-        &state.eph_kp,
+        &key,
         UserData {
             twitter_name: twitter_name.as_bytes().to_vec(),
             sui_address: sui_address.clone(),
         },
         current_timestamp,
         IntentScope::ProcessData,
+        binding,
     )))
 }
 
 async fn fetch_tweet_content(
+    egress: &EgressClient,
     api_key: &str,
     user_url: &str,
 ) -> Result<(String, Vec<u8>), EnclaveError> {
-    let client = reqwest::Client::new();
     if user_url.contains("/status/") {
         // Extract tweet ID from URL using regex
         let re = Regex::new(r"x\.com/\w+/status/(\d+)")
-            .map_err(|_| EnclaveError::GenericError("Invalid tweet URL".to_string()))?;
+            .map_err(|_| EnclaveError::BadRequest("Invalid tweet URL".to_string()))?;
         let tweet_id = re
             .captures(user_url)
             .and_then(|cap| cap.get(1))
             .map(|m| m.as_str())
-            .ok_or_else(|| EnclaveError::GenericError("Invalid tweet URL".to_string()))?;
+            .ok_or_else(|| EnclaveError::BadRequest("Invalid tweet URL".to_string()))?;
 
         // Construct the Twitter API URL
         let url = format!(
@@ -75,45 +114,43 @@ async fn fetch_tweet_content(
         );
 
         // Make the request to Twitter API
-        let response = client
-            .get(&url)
+        let response = egress
+            .get(&url)?
             .header("Authorization", format!("Bearer {}", api_key))
             .send()
             .await
-            .map_err(|_| {
-                EnclaveError::GenericError("Failed to send request to Twitter API".to_string())
-            })?
+            .map_err(|e| EnclaveError::upstream("Failed to send request to Twitter API", e))?
             .json::<serde_json::Value>()
             .await
             .map_err(|_| {
-                EnclaveError::GenericError("Failed to parse response from Twitter API".to_string())
+                EnclaveError::UpstreamError("Failed to parse response from Twitter API".to_string())
             })?;
 
         // Extract tweet text and author username
         let tweet_text = response["data"]["text"].as_str().ok_or_else(|| {
-            EnclaveError::GenericError(format!("Failed to extract tweet text {}", response))
+            EnclaveError::UpstreamError(format!("Failed to extract tweet text {}", response))
         })?;
 
         let twitter_name = response["includes"]["users"]
             .as_array()
             .and_then(|users| users.first())
             .and_then(|user| user["username"].as_str())
-            .ok_or_else(|| EnclaveError::GenericError("Failed to extract username".to_string()))?;
+            .ok_or_else(|| EnclaveError::UpstreamError("Failed to extract username".to_string()))?;
 
         // Find the position of "#SUI" and extract address before it
         let sui_tag_pos = tweet_text
             .find("#SUI")
-            .ok_or_else(|| EnclaveError::GenericError("No #SUI tag found in tweet".to_string()))?;
+            .ok_or_else(|| EnclaveError::Unprocessable("No #SUI tag found in tweet".to_string()))?;
 
         let text_before_tag = &tweet_text[..sui_tag_pos];
         let sui_address_re = Regex::new(r"0x[0-9a-fA-F]{64}")
-            .map_err(|_| EnclaveError::GenericError("Invalid Sui address regex".to_string()))?;
+            .map_err(|_| EnclaveError::InternalError("Invalid Sui address regex".to_string()))?;
 
         let sui_address = sui_address_re
             .find(text_before_tag)
             .map(|m| m.as_str())
             .ok_or_else(|| {
-                EnclaveError::GenericError(
+                EnclaveError::Unprocessable(
                     "No valid Sui address found before #SUI in profile description".to_string(),
                 )
             })?;
@@ -121,17 +158,17 @@ async fn fetch_tweet_content(
         Ok((
             twitter_name.to_string(),
             Hex::decode(sui_address)
-                .map_err(|_| EnclaveError::GenericError("Invalid Sui address".to_string()))?,
+                .map_err(|_| EnclaveError::Unprocessable("Invalid Sui address".to_string()))?,
         ))
     } else {
         // Handle profile URL
         let re = Regex::new(r"x\.com/(\w+)(?:/)?$")
-            .map_err(|_| EnclaveError::GenericError("Invalid profile URL".to_string()))?;
+            .map_err(|_| EnclaveError::BadRequest("Invalid profile URL".to_string()))?;
         let username = re
             .captures(user_url)
             .and_then(|cap| cap.get(1))
             .map(|m| m.as_str())
-            .ok_or_else(|| EnclaveError::GenericError("Invalid profile URL".to_string()))?;
+            .ok_or_else(|| EnclaveError::BadRequest("Invalid profile URL".to_string()))?;
 
         // Fetch user profile
         let url = format!(
@@ -139,38 +176,36 @@ async fn fetch_tweet_content(
             username
         );
 
-        let response = client
-            .get(&url)
+        let response = egress
+            .get(&url)?
             .header("Authorization", format!("Bearer {}", api_key))
             .send()
             .await
-            .map_err(|_| {
-                EnclaveError::GenericError("Failed to send request to Twitter API".to_string())
-            })?
+            .map_err(|e| EnclaveError::upstream("Failed to send request to Twitter API", e))?
             .json::<serde_json::Value>()
             .await
             .map_err(|_| {
-                EnclaveError::GenericError("Failed to parse response from Twitter API".to_string())
+                EnclaveError::UpstreamError("Failed to parse response from Twitter API".to_string())
             })?;
 
         // Extract user description
         let description = response["data"]["description"].as_str().ok_or_else(|| {
-            EnclaveError::GenericError("Failed to extract user description".to_string())
+            EnclaveError::UpstreamError("Failed to extract user description".to_string())
         })?;
 
         let sui_tag_pos = description.find("#SUI").ok_or_else(|| {
-            EnclaveError::GenericError("No #SUI tag found in profile description".to_string())
+            EnclaveError::Unprocessable("No #SUI tag found in profile description".to_string())
         })?;
 
         let text_before_tag = &description[..sui_tag_pos];
         let sui_address_re = Regex::new(r"0x[0-9a-fA-F]{64}")
-            .map_err(|_| EnclaveError::GenericError("Invalid Sui address regex".to_string()))?;
+            .map_err(|_| EnclaveError::InternalError("Invalid Sui address regex".to_string()))?;
 
         let sui_address = sui_address_re
             .find(text_before_tag)
             .map(|m| m.as_str())
             .ok_or_else(|| {
-                EnclaveError::GenericError(
+                EnclaveError::Unprocessable(
                     "No valid Sui address found before #SUI in profile description".to_string(),
                 )
             })?;
@@ -178,7 +213,7 @@ async fn fetch_tweet_content(
         Ok((
             username.to_string(),
             Hex::decode(&sui_address[2..])
-                .map_err(|_| EnclaveError::GenericError("Invalid Sui address".to_string()))?,
+                .map_err(|_| EnclaveError::Unprocessable("Invalid Sui address".to_string()))?,
         ))
     }
 }
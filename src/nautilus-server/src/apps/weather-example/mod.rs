@@ -1,45 +1,126 @@
 // Copyright (c), Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::batch::{process_batch as sign_batch, ProcessBatchRequest, ProcessedBatchResponse};
 use crate::common::IntentMessage;
 use crate::common::{to_signed_response, IntentScope, ProcessDataRequest, ProcessedDataResponse};
+use crate::egress::EgressClient;
+use crate::endpoints::EndpointsConfig;
+use crate::failure::{attest_failure, ProcessDataError};
+use crate::registry::{AppContext, EnclaveApp};
 use crate::AppState;
 use crate::EnclaveError;
 use axum::extract::State;
-use axum::Json;
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use std::sync::Arc;
+use axum::routing::post;
+use axum::{Json, Router};
 /// ====
 /// Core Nautilus server logic, replace it with your own
 /// relavant structs and process_data endpoint.
 /// ====
-/// Inner type T for IntentMessage<T>
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct WeatherResponse {
-    pub location: String,
-    pub temperature: u64,
+/// Inner types of IntentMessage<T> and ProcessDataRequest<T>, shared with clients.
+pub use nautilus_types::apps::weather::{WeatherRequest, WeatherResponse};
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Allowed endpoints of the app, baked into the enclave image.
+const ALLOWED_ENDPOINTS: &str = include_str!("allowed_endpoints.yaml");
+
+/// Weather app, signs the current temperature of a location.
+pub struct WeatherApp {
+    /// API key when querying api.weatherapi.com
+    pub api_key: String,
 }
 
-/// Inner type T for ProcessDataRequest<T>
-#[derive(Debug, Serialize, Deserialize)]
-pub struct WeatherRequest {
-    pub location: String,
+impl WeatherApp {
+    pub fn from_env() -> Result<Self, EnclaveError> {
+        // This WEATHER_API_KEY value can be stored with secret-manager. To do that, follow the prompt `sh configure_enclave.sh`
+        // Answer `y` to `Do you want to use a secret?` and finish. Otherwise, use a hardcoded value instead.
+        // Each app reads its own variable, so that enabling several apps never hands a key to another upstream.
+        let api_key = std::env::var("WEATHER_API_KEY")
+            .map_err(|_| EnclaveError::InternalError("WEATHER_API_KEY must be set".to_string()))?;
+        Ok(Self { api_key })
+    }
+}
+
+impl EnclaveApp for WeatherApp {
+    fn name(&self) -> &'static str {
+        "weather"
+    }
+
+    fn intent_scopes(&self) -> &'static [IntentScope] {
+        &[
+            IntentScope::ProcessData,
+            IntentScope::BatchRoot,
+            IntentScope::Failure,
+        ]
+    }
+
+    fn routes(self: Arc<Self>, state: Arc<AppState>) -> Router {
+        Router::new()
+            .route("/process_data", post(process_data))
+            .route("/process_batch", post(process_batch))
+            .with_state(AppContext::new(state, self))
+    }
+
+    fn allowed_endpoints(&self) -> Result<EndpointsConfig, EnclaveError> {
+        Ok(EndpointsConfig::from_yaml(ALLOWED_ENDPOINTS)?)
+    }
 }
 
 pub async fn process_data(
-    State(state): State<Arc<AppState>>,
+    State(ctx): State<AppContext<WeatherApp>>,
     Json(request): Json<ProcessDataRequest<WeatherRequest>>,
-) -> Result<Json<ProcessedDataResponse<IntentMessage<WeatherResponse>>>, EnclaveError> {
+) -> Result<Json<ProcessedDataResponse<IntentMessage<WeatherResponse>>>, ProcessDataError> {
+    let binding = request.binding()?;
+    let (response, last_updated_timestamp_ms) =
+        current_weather(&ctx.enclave.egress, &ctx.app, &request.payload.location)
+            .await
+            .map_err(|e| attest_failure(&ctx.enclave, &request, e))?;
+    let key = ctx.enclave.signing_key(request.key_epoch)?;
+    Ok(Json(to_signed_response(
+        &key,
+        response,
+        last_updated_timestamp_ms,
+        IntentScope::ProcessData,
+        binding,
+    )))
+}
+
+/// Fetch the weather of every location of the batch concurrently, signed with
+/// a single signature over the Merkle root of the results.
+pub async fn process_batch(
+    State(ctx): State<AppContext<WeatherApp>>,
+    Json(request): Json<ProcessBatchRequest<WeatherRequest>>,
+) -> Result<Json<ProcessedBatchResponse<IntentMessage<WeatherResponse>>>, EnclaveError> {
+    let app = &ctx.app;
+    let egress = &ctx.enclave.egress;
+    let response = sign_batch(
+        &ctx.enclave,
+        request,
+        IntentScope::ProcessData,
+        |payload| async move { current_weather(egress, app, &payload.location).await },
+    )
+    .await?;
+    Ok(Json(response))
+}
+
+/// Current weather of a location, returned with the time it was last updated.
+async fn current_weather(
+    egress: &EgressClient,
+    app: &WeatherApp,
+    location: &str,
+) -> Result<(WeatherResponse, u64), EnclaveError> {
     let url = format!(
         "https://api.weatherapi.com/v1/current.json?key={}&q={}",
-        state.api_key, request.payload.location
+        app.api_key, location
     );
-    let response = reqwest::get(url.clone()).await.map_err(|e| {
-        EnclaveError::GenericError(format!("Failed to get weather response: {}", e))
-    })?;
+    let response = egress
+        .get(&url)?
+        .send()
+        .await
+        .map_err(|e| EnclaveError::upstream("Failed to get weather response", e))?;
     let json = response.json::<Value>().await.map_err(|e| {
-        EnclaveError::GenericError(format!("Failed to parse weather response: {}", e))
+        EnclaveError::UpstreamError(format!("Failed to parse weather response: {}", e))
     })?;
     let location = json["location"]["name"].as_str().unwrap_or("Unknown");
     let temperature = json["current"]["temp_c"].as_f64().unwrap_or(0.0) as u64;
@@ -47,46 +128,59 @@ pub async fn process_data(
     let last_updated_timestamp_ms = last_updated_epoch * 1000_u64;
     let current_timestamp = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
-        .map_err(|e| EnclaveError::GenericError(format!("Failed to get current timestamp: {}", e)))?
+        .map_err(|e| {
+            EnclaveError::InternalError(format!("Failed to get current timestamp: {}", e))
+        })?
         .as_millis() as u64;
 
     // 1 hour in milliseconds = 60 * 60 * 1000 = 3_600_000
     if last_updated_timestamp_ms + 3_600_000 < current_timestamp {
-        return Err(EnclaveError::GenericError(
+        return Err(EnclaveError::Unprocessable(
             "Weather API timestamp is too old".to_string(),
         ));
     }
 
-    Ok(Json(to_signed_response(
-        &state.eph_kp,
+    Ok((
         WeatherResponse {
             location: location.to_string(),
             temperature,
         },
         last_updated_timestamp_ms,
-        IntentScope::ProcessData,
-    )))
+    ))
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::common::IntentMessage;
+    use crate::egress::EgressClient;
+    use crate::test_state;
     use axum::{extract::State, Json};
-    use fastcrypto::{ed25519::Ed25519KeyPair, traits::KeyPair};
 
     #[tokio::test]
     async fn test_process_data() {
-        let state = Arc::new(AppState {
-            eph_kp: Ed25519KeyPair::generate(&mut rand::thread_rng()),
-            api_key: "045a27812dbe456392913223221306".to_string(),
-        });
+        let ctx = AppContext::new(
+            Arc::new(AppState {
+                egress: EgressClient::new(
+                    &EndpointsConfig::from_yaml(ALLOWED_ENDPOINTS).unwrap(),
+                    &Default::default(),
+                )
+                .unwrap(),
+                ..test_state()
+            }),
+            Arc::new(WeatherApp {
+                api_key: "045a27812dbe456392913223221306".to_string(),
+            }),
+        );
         let signed_weather_response = process_data(
-            State(state),
+            State(ctx),
             Json(ProcessDataRequest {
                 payload: WeatherRequest {
                     location: "San Francisco".to_string(),
                 },
+                key_epoch: None,
+                nonce: None,
+                caller: None,
             }),
         )
         .await
@@ -0,0 +1,355 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::common::{
+    signing_payload, to_signed_response, IntentMessage, IntentScope, ProcessDataRequest,
+    ProcessedDataResponse,
+};
+use crate::merkle::{MerkleHash, MerkleTree};
+use crate::AppState;
+use crate::EnclaveError;
+use fastcrypto::encoding::{Encoding, Hex};
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::Debug;
+use std::future::Future;
+
+/// Maximum number of requests in a batch.
+pub const MAX_BATCH_SIZE: usize = 256;
+
+/// Batch of process data requests, signed with a single signature over the
+/// Merkle root of the results.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProcessBatchRequest<T> {
+    /// Requests of the batch, their `key_epoch` is ignored in favor of the
+    /// epoch of the batch.
+    pub requests: Vec<ProcessDataRequest<T>>,
+    /// Hash function of the Merkle tree, defaults to sha256.
+    #[serde(default)]
+    pub hash: MerkleHash,
+    /// Epoch of the key to sign the root with, defaults to the current key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_epoch: Option<u64>,
+}
+
+/// Signed payload of a batch, the Merkle root over the signing payloads of
+/// the successful items. Matches `BatchRoot` in `move/enclave/sources/merkle.move`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchRoot {
+    /// Merkle root, hex encoded in JSON.
+    #[serde(with = "hex_bytes")]
+    pub root: Vec<u8>,
+    /// Number of leaves of the tree.
+    pub count: u64,
+    /// Hash function of the tree, see `MerkleHash::id`.
+    pub hash: u8,
+}
+
+/// Inclusion proof of an item in the batch root.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MerkleProof {
+    /// Index of the leaf in the tree.
+    pub index: u64,
+    /// Hex encoded siblings from the leaf up to the root.
+    pub siblings: Vec<String>,
+}
+
+/// Result of a request of the batch, either a response with its proof or an error.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchItem<T> {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<T>,
+    /// Hex encoded client nonce bound in the leaf, if requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    /// Caller address bound in the leaf, if requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub caller: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proof: Option<MerkleProof>,
+    /// Error of a failed request, which is left out of the tree.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Signed batch root and the results of the requests, in request order.
+#[derive(Serialize, Deserialize)]
+pub struct ProcessedBatchResponse<T> {
+    pub root: ProcessedDataResponse<IntentMessage<BatchRoot>>,
+    pub items: Vec<BatchItem<T>>,
+}
+
+/// Process the requests of the batch with `process`, at most
+/// `batch.concurrency` of the server config at once, which returns the
+/// response data and its timestamp, then sign the Merkle root of
+/// the successful results under `IntentScope::BatchRoot`. Each leaf is the
+/// payload `to_signed_response` would sign for the item alone, without domain.
+pub async fn process_batch<T, U, F, Fut>(
+    state: &AppState,
+    request: ProcessBatchRequest<T>,
+    intent: IntentScope,
+    process: F,
+) -> Result<ProcessedBatchResponse<IntentMessage<U>>, EnclaveError>
+where
+    U: Serialize + Debug,
+    F: Fn(T) -> Fut,
+    Fut: Future<Output = Result<(U, u64), EnclaveError>>,
+{
+    if request.requests.is_empty() || request.requests.len() > MAX_BATCH_SIZE {
+        return Err(EnclaveError::BadRequest(format!(
+            "A batch must have 1 to {} requests, got {}",
+            MAX_BATCH_SIZE,
+            request.requests.len()
+        )));
+    }
+    let key = state.signing_key(request.key_epoch)?;
+
+    let process = &process;
+    let mut results: Vec<_> = stream::iter(request.requests.into_iter().enumerate().map(
+        |(i, item)| async move {
+            let result = async move {
+                let binding = item.binding()?;
+                let (data, timestamp_ms) = process(item.payload).await?;
+                Ok::<_, EnclaveError>((IntentMessage::new(data, timestamp_ms, intent), binding))
+            };
+            (i, result.await)
+        },
+    ))
+    .buffer_unordered(state.config.batch.concurrency)
+    .collect()
+    .await;
+    // Back to request order, the leaves follow the order of the requests.
+    results.sort_by_key(|(i, _)| *i);
+    let results: Vec<_> = results.into_iter().map(|(_, result)| result).collect();
+
+    let leaves: Vec<Vec<u8>> = results
+        .iter()
+        .filter_map(|result| result.as_ref().ok())
+        .map(|(intent_msg, binding)| signing_payload(None, intent_msg, binding.as_ref()))
+        .collect();
+    if leaves.is_empty() {
+        let error = results.into_iter().find_map(Result::err);
+        return Err(error.expect("batch is not empty"));
+    }
+    let tree = MerkleTree::new(request.hash, &leaves);
+
+    let mut index = 0;
+    let items = results
+        .into_iter()
+        .map(|result| match result {
+            Ok((intent_msg, binding)) => {
+                let proof = MerkleProof {
+                    index: index as u64,
+                    siblings: tree.proof(index).iter().map(Hex::encode).collect(),
+                };
+                index += 1;
+                BatchItem {
+                    response: Some(intent_msg),
+                    nonce: binding
+                        .as_ref()
+                        .and_then(|b| b.nonce.as_ref())
+                        .map(Hex::encode),
+                    caller: binding
+                        .as_ref()
+                        .and_then(|b| b.caller)
+                        .map(|caller| format!("0x{}", Hex::encode(caller))),
+                    proof: Some(proof),
+                    error: None,
+                }
+            }
+            Err(e) => BatchItem {
+                response: None,
+                nonce: None,
+                caller: None,
+                proof: None,
+                error: Some(e.to_string()),
+            },
+        })
+        .collect();
+
+    let timestamp_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| {
+            EnclaveError::InternalError(format!("Failed to get current timestamp: {}", e))
+        })?
+        .as_millis() as u64;
+    let root = BatchRoot {
+        root: tree.root().to_vec(),
+        count: tree.len() as u64,
+        hash: request.hash.id(),
+    };
+    Ok(ProcessedBatchResponse {
+        root: to_signed_response(&key, root, timestamp_ms, IntentScope::BatchRoot, None),
+        items,
+    })
+}
+
+/// Bytes as hex in human readable formats such as JSON, and as bytes in BCS.
+mod hex_bytes {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            Hex::encode(bytes).serialize(serializer)
+        } else {
+            bytes.serialize(serializer)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        if deserializer.is_human_readable() {
+            let hex = String::deserialize(deserializer)?;
+            Hex::decode(&hex).map_err(serde::de::Error::custom)
+        } else {
+            Vec::deserialize(deserializer)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::ServerConfig;
+    use crate::merkle::verify_proof;
+    use crate::test_state;
+    use fastcrypto::ed25519::{Ed25519PublicKey, Ed25519Signature};
+    use fastcrypto::traits::{ToFromBytes, VerifyingKey};
+
+    #[tokio::test]
+    async fn test_process_batch() {
+        let state = test_state();
+        let request = |payload: u64, nonce: Option<&str>| ProcessDataRequest {
+            payload,
+            key_epoch: None,
+            nonce: nonce.map(str::to_string),
+            caller: None,
+        };
+        let batch = ProcessBatchRequest {
+            requests: vec![request(1, None), request(0, None), request(3, Some("0102"))],
+            hash: MerkleHash::Blake2b256,
+            key_epoch: None,
+        };
+        let response = process_batch(&state, batch, IntentScope::ProcessData, |n| async move {
+            if n == 0 {
+                return Err(EnclaveError::BadRequest("zero".to_string()));
+            }
+            Ok((n * 2, 1744038900000))
+        })
+        .await
+        .unwrap();
+
+        let root = &response.root.response.data;
+        assert_eq!(root.count, 2);
+        assert_eq!(root.hash, MerkleHash::Blake2b256.id());
+        let pk =
+            Ed25519PublicKey::from_bytes(&Hex::decode(&response.root.public_key).unwrap()).unwrap();
+        let signature =
+            Ed25519Signature::from_bytes(&Hex::decode(&response.root.signature).unwrap()).unwrap();
+        pk.verify(&bcs::to_bytes(&response.root.response).unwrap(), &signature)
+            .unwrap();
+
+        assert!(response.items[1].error.is_some());
+        let root: [u8; 32] = root.root.clone().try_into().unwrap();
+        for item in [&response.items[0], &response.items[2]] {
+            let proof = item.proof.as_ref().unwrap();
+            let binding = ProcessDataRequest {
+                payload: (),
+                key_epoch: None,
+                nonce: item.nonce.clone(),
+                caller: None,
+            }
+            .binding()
+            .unwrap();
+            let leaf = signing_payload(None, item.response.as_ref().unwrap(), binding.as_ref());
+            let siblings: Vec<[u8; 32]> = proof
+                .siblings
+                .iter()
+                .map(|s| Hex::decode(s).unwrap().try_into().unwrap())
+                .collect();
+            assert!(verify_proof(
+                MerkleHash::Blake2b256,
+                &root,
+                &leaf,
+                proof.index,
+                2,
+                &siblings
+            ));
+        }
+        assert_eq!(response.items[2].nonce.as_deref(), Some("0102"));
+    }
+
+    #[tokio::test]
+    async fn test_batch_concurrency() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::time::Duration;
+
+        let mut config = ServerConfig::load().unwrap();
+        config.batch.concurrency = 3;
+        let state = AppState {
+            config,
+            ..test_state()
+        };
+        let batch = ProcessBatchRequest {
+            requests: (0..20u64)
+                .map(|payload| ProcessDataRequest {
+                    payload,
+                    key_epoch: None,
+                    nonce: None,
+                    caller: None,
+                })
+                .collect(),
+            hash: MerkleHash::Sha256,
+            key_epoch: None,
+        };
+        let (active, max_active) = (AtomicUsize::new(0), AtomicUsize::new(0));
+        let response = process_batch(&state, batch, IntentScope::ProcessData, |n| {
+            let (active, max_active) = (&active, &max_active);
+            async move {
+                let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                max_active.fetch_max(now, Ordering::SeqCst);
+                // Later requests finish first, the items keep the request order.
+                tokio::time::sleep(Duration::from_millis(20 - n)).await;
+                active.fetch_sub(1, Ordering::SeqCst);
+                Ok((n, 1744038900000))
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(max_active.load(Ordering::SeqCst), 3);
+        let data: Vec<u64> = response
+            .items
+            .iter()
+            .map(|item| item.response.as_ref().unwrap().data)
+            .collect();
+        assert_eq!(data, (0..20).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_batch_root_domain() {
+        // Should be consistent with `test_domain_batch_root` in `move/enclave/sources/merkle.move`.
+        let mut package_id = [0; 32];
+        package_id[31] = 2;
+        let mut enclave_id = [0; 32];
+        enclave_id[31] = 3;
+        let domain = nautilus_types::DomainSeparator {
+            chain_id: vec![0x4c, 0x78, 0xad, 0xac],
+            package_id,
+            enclave_id,
+        };
+        let batch = BatchRoot {
+            root: vec![0xab; 32],
+            count: 3,
+            hash: MerkleHash::Blake2b256.id(),
+        };
+        let intent_msg = IntentMessage::new(batch, 1744038900000, IntentScope::BatchRoot);
+        assert_eq!(
+            Hex::encode(signing_payload(Some(&domain), &intent_msg, None)),
+            format!(
+                "044c78adac{}02{}030120b1d1109601000020{}030000000000000001",
+                "00".repeat(31),
+                "00".repeat(31),
+                "ab".repeat(32)
+            )
+        );
+    }
+}
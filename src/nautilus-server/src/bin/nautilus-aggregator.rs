@@ -0,0 +1,113 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Aggregator of enclave replicas running with `bls_signing`, outside of the
+//! enclaves. Every `POST` is forwarded to all replicas, and the response is the
+//! aggregate BLS12-381 signature of the replicas that signed the same message,
+//! see `aggregator.rs`. Run with the path of its configuration, e.g.
+//! `cargo run --bin nautilus-aggregator -- aggregator.yaml`.
+
+use anyhow::{Context, Result};
+use axum::extract::{Path, State};
+use axum::routing::post;
+use axum::{Json, Router};
+use nautilus_server::aggregator::{collect, AggregatedResponse, Committee};
+use nautilus_server::logging::init_tracing;
+use nautilus_server::EnclaveError;
+use reqwest::Client;
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::info;
+
+/// Configuration of the aggregator, see `aggregator.yaml`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AggregatorConfig {
+    listen_addr: SocketAddr,
+    /// Number of replicas that must sign the same message.
+    threshold: usize,
+    /// Timeout of the requests to the replicas.
+    #[serde(default = "default_timeout_secs")]
+    timeout_secs: u64,
+    /// Replicas in the order of the signer bitmap.
+    replicas: Vec<Replica>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Replica {
+    url: String,
+    /// Hex encoded BLS12-381 public key, from the attestation of the replica.
+    bls_public_key: String,
+}
+
+fn default_timeout_secs() -> u64 {
+    10
+}
+
+struct Aggregator {
+    client: Client,
+    committee: Committee,
+    urls: Vec<String>,
+}
+
+async fn aggregate(
+    State(aggregator): State<Arc<Aggregator>>,
+    Path(path): Path<String>,
+    Json(body): Json<serde_json::Value>,
+) -> Result<Json<AggregatedResponse<serde_json::Value>>, EnclaveError> {
+    let responses = collect(
+        &aggregator.client,
+        &aggregator.urls,
+        &format!("/{}", path),
+        &body,
+    )
+    .await;
+    Ok(Json(aggregator.committee.aggregate(responses)?))
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    init_tracing();
+
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "aggregator.yaml".to_string());
+    let config: AggregatorConfig = serde_yaml::from_str(
+        &std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path))?,
+    )
+    .with_context(|| format!("Failed to parse {}", path))?;
+
+    let keys: Vec<String> = config
+        .replicas
+        .iter()
+        .map(|replica| replica.bls_public_key.clone())
+        .collect();
+    let committee = Committee::from_hex(&keys, config.threshold)?;
+    let aggregator = Arc::new(Aggregator {
+        client: Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()?,
+        committee,
+        urls: config
+            .replicas
+            .into_iter()
+            .map(|replica| replica.url)
+            .collect(),
+    });
+
+    let app = Router::new()
+        .route("/*path", post(aggregate))
+        .with_state(aggregator.clone());
+    let listener = tokio::net::TcpListener::bind(config.listen_addr).await?;
+    info!(
+        "aggregating {} of {} replicas on {}",
+        aggregator.committee.threshold(),
+        aggregator.committee.len(),
+        listener.local_addr()?
+    );
+    axum::serve(listener, app.into_make_service()).await?;
+    Ok(())
+}
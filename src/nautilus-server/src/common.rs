@@ -1,73 +1,45 @@
 // Copyright (c), Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::domain::DomainConfig;
+use crate::endpoints::EndpointStatus;
+use crate::keys::{EpochKey, KeyStatus, SignatureScheme};
+use crate::metrics;
+use crate::nsm::AttestationRequest;
+use crate::supervisor::TaskStatus;
 use crate::AppState;
 use crate::EnclaveError;
-use axum::{extract::State, Json};
-use fastcrypto::traits::Signer;
-use fastcrypto::{encoding::Encoding, traits::ToFromBytes};
-use fastcrypto::{encoding::Hex, traits::KeyPair as FcKeyPair};
-use nsm_api::api::{Request as NsmRequest, Response as NsmResponse};
-use nsm_api::driver;
-use reqwest::Client;
+use axum::extract::{Query, State};
+use axum::Json;
+use fastcrypto::encoding::{Encoding, Hex};
+use fastcrypto::traits::{KeyPair, Signer, ToFromBytes};
 use serde::{Deserialize, Serialize};
-use serde_bytes::ByteBuf;
-use serde_repr::Deserialize_repr;
-use serde_repr::Serialize_repr;
-use std::collections::HashMap;
-use std::fmt::Debug;
+use std::collections::BTreeMap;
 use std::sync::Arc;
-use std::time::Duration;
 use tracing::info;
 
-use fastcrypto::ed25519::Ed25519KeyPair;
 /// ==== COMMON TYPES ====
-/// Intent message wrapper struct containing the intent scope and timestamp.
-/// This standardizes the serialized payload for signing.
-#[derive(Debug, Serialize, Deserialize)]
-pub struct IntentMessage<T: Serialize> {
-    pub intent: IntentScope,
-    pub timestamp_ms: u64,
-    pub data: T,
-}
-
-/// Intent scope enum. Add new scope here if needed, each corresponds to a
-/// scope for signing. Replace in with your own intent per message type being signed by the enclave.
-#[derive(Serialize_repr, Deserialize_repr, Debug)]
-#[repr(u8)]
-pub enum IntentScope {
-    ProcessData = 0,
-}
-
-impl<T: Serialize + Debug> IntentMessage<T> {
-    pub fn new(data: T, timestamp_ms: u64, intent: IntentScope) -> Self {
-        Self {
-            data,
-            timestamp_ms,
-            intent,
-        }
-    }
-}
-
-/// Wrapper struct containing the response (the intent message) and signature.
-#[derive(Serialize, Deserialize)]
-pub struct ProcessedDataResponse<T> {
-    pub response: T,
-    pub signature: String,
-}
-
-/// Wrapper struct containing the request payload.
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ProcessDataRequest<T> {
-    pub payload: T,
-}
+/// Request, response and signing payload types shared with clients, see the
+/// nautilus-types crate.
+pub use nautilus_types::{
+    bls_signing_payload, decode_address, signing_payload, AttestationUserData, BlsSignature,
+    BuildInfo, GetAttestationQuery, GetAttestationResponse, IntentMessage, IntentScope,
+    ProcessDataRequest, ProcessedDataResponse, RequestBinding, ATTESTATION_USER_DATA_VERSION,
+    MAX_CLIENT_NONCE_LEN,
+};
 
-/// Sign the bcs bytes of the the payload with keypair.
+/// Sign the bcs bytes of the the payload with the key. If the key is bound to
+/// a domain, the bcs bytes of the domain separator are prepended to the signing
+/// payload. If the request is bound, the bcs bytes of the binding are appended.
+/// The BLS key signs with the committee separator instead of the domain
+/// separator, and does not sign if the key is bound to a domain but not to a
+/// committee.
 pub fn to_signed_response<T: Serialize + Clone>(
-    kp: &Ed25519KeyPair,
+    key: &EpochKey,
     payload: T,
     timestamp_ms: u64,
     intent: IntentScope,
+    binding: Option<RequestBinding>,
 ) -> ProcessedDataResponse<IntentMessage<T>> {
     let intent_msg = IntentMessage {
         intent,
@@ -75,147 +47,337 @@ pub fn to_signed_response<T: Serialize + Clone>(
         data: payload.clone(),
     };
 
-    let signing_payload = bcs::to_bytes(&intent_msg).expect("should not fail");
-    let sig = kp.sign(&signing_payload);
+    let signing_payload = signing_payload(key.domain.get(), &intent_msg, binding.as_ref());
+    let sig = key.kp.sign(&signing_payload);
+    let bls = key.bls.as_ref().and_then(|kp| {
+        let message = match key.domain.get() {
+            Some(_) => {
+                bls_signing_payload(Some(key.committee.get()?), &intent_msg, binding.as_ref())
+            }
+            None => signing_payload.clone(),
+        };
+        Some(BlsSignature {
+            public_key: Hex::encode(kp.public().as_bytes()),
+            signature: Hex::encode(kp.sign(&message).as_ref()),
+            message: Hex::encode(message),
+        })
+    });
+    metrics::observe_signature(&intent_msg.intent);
     ProcessedDataResponse {
         response: intent_msg,
         signature: Hex::encode(sig),
+        key_epoch: key.epoch,
+        public_key: key.public_key_hex(),
+        nonce: binding
+            .as_ref()
+            .and_then(|b| b.nonce.as_ref())
+            .map(Hex::encode),
+        caller: binding
+            .as_ref()
+            .and_then(|b| b.caller)
+            .map(|caller| format!("0x{}", Hex::encode(caller))),
+        bls,
     }
 }
 
 /// ==== HEALTHCHECK, GET ATTESTASTION ENDPOINT IMPL ====
-/// Response for get attestation.
-#[derive(Debug, Serialize, Deserialize)]
-pub struct GetAttestationResponse {
-    /// Attestation document serialized in Hex.
-    pub attestation: String,
+/// Maximum length in bytes of the user_data of an attestation, set by the NSM.
+pub const MAX_USER_DATA_LEN: usize = 512;
+/// Maximum length in bytes of the nonce of an attestation, set by the NSM.
+pub const MAX_NONCE_LEN: usize = 512;
+/// Maximum length in bytes of the public key of an attestation, set by the NSM.
+pub const MAX_PUBLIC_KEY_LEN: usize = 1024;
+
+/// Build information of the running server binary.
+pub fn build_info() -> BuildInfo {
+    BuildInfo {
+        package_version: env!("CARGO_PKG_VERSION").to_string(),
+        git_revision: option_env!("GIT_REVISION").map(str::to_string),
+    }
 }
 
-/// Endpoint that returns an attestation committed
-/// to the enclave's public key.
+/// Endpoint that returns an attestation committed to the enclave's public
+/// key, with an optional verifier nonce and a structured user_data, e.g.
+/// `/get_attestation?nonce=<hex>&user_data=<hex>`.
 pub async fn get_attestation(
     State(state): State<Arc<AppState>>,
+    Query(query): Query<GetAttestationQuery>,
 ) -> Result<Json<GetAttestationResponse>, EnclaveError> {
     info!("get attestation called");
 
-    let pk = state.eph_kp.public();
-    let fd = driver::nsm_init();
+    // Pin the epoch so that the key marked as attested is the attested key,
+    // even if the key rotates meanwhile.
+    let key = state.keys.key(query.epoch)?;
+    let query = GetAttestationQuery {
+        epoch: Some(key.epoch),
+        ..query
+    };
+    let request = attestation_request(&state, &query)?;
 
-    // Send attestation request to NSM driver with public key set.
-    let request = NsmRequest::Attestation {
-        user_data: None,
-        nonce: None,
-        public_key: Some(ByteBuf::from(pk.as_bytes().to_vec())),
+    metrics::NSM_ATTESTATIONS.inc();
+    let document = state.nsm.attestation(request).inspect_err(|_| {
+        metrics::NSM_ATTESTATION_FAILURES.inc();
+    })?;
+    key.mark_attested();
+    Ok(Json(GetAttestationResponse {
+        attestation: Hex::encode(document),
+    }))
+}
+
+/// Build the NSM attestation request for the enclave public key, the
+/// verifier nonce and the user_data, enforcing the NSM length limits.
+pub fn attestation_request(
+    state: &AppState,
+    query: &GetAttestationQuery,
+) -> Result<AttestationRequest, EnclaveError> {
+    let decode = |name: &str, value: &str| {
+        Hex::decode(value)
+            .map_err(|_| EnclaveError::BadRequest(format!("{} must be hex encoded", name)))
     };
 
-    let response = driver::nsm_process_request(fd, request);
-    match response {
-        NsmResponse::Attestation { document } => {
-            driver::nsm_exit(fd);
-            Ok(Json(GetAttestationResponse {
-                attestation: Hex::encode(document),
-            }))
-        }
-        _ => {
-            driver::nsm_exit(fd);
-            Err(EnclaveError::GenericError(
-                "unexpected response".to_string(),
-            ))
+    let nonce = match &query.nonce {
+        Some(nonce) => {
+            let nonce = decode("nonce", nonce)?;
+            if nonce.is_empty() || nonce.len() > MAX_NONCE_LEN {
+                return Err(EnclaveError::BadRequest(format!(
+                    "nonce must be 1 to {} bytes, got {}",
+                    MAX_NONCE_LEN,
+                    nonce.len()
+                )));
+            }
+            Some(nonce)
         }
+        None => None,
+    };
+
+    // Round trip the client data so that it is bound in canonical lowercase hex.
+    let client_data = match &query.user_data {
+        Some(data) => Some(Hex::encode(decode("user_data", data)?)),
+        None => None,
+    };
+    let key = state.keys.key(query.epoch)?;
+    let user_data = serde_json::to_vec(&AttestationUserData {
+        version: ATTESTATION_USER_DATA_VERSION,
+        apps: state.apps.iter().map(|app| app.to_string()).collect(),
+        key_scheme: key.kp.scheme(),
+        key_epoch: key.epoch,
+        bls_public_key: key.bls_public_key_hex(),
+        config_hash: Hex::encode(state.config.hash),
+        build: build_info(),
+        client_data,
+    })
+    .map_err(|e| EnclaveError::InternalError(format!("Failed to encode user_data: {}", e)))?;
+    if user_data.len() > MAX_USER_DATA_LEN {
+        return Err(EnclaveError::BadRequest(format!(
+            "user_data exceeds {} bytes once encoded, got {}",
+            MAX_USER_DATA_LEN,
+            user_data.len()
+        )));
     }
+
+    let public_key = key.kp.public_key_bytes();
+    if public_key.len() > MAX_PUBLIC_KEY_LEN {
+        return Err(EnclaveError::InternalError(format!(
+            "public key exceeds {} bytes",
+            MAX_PUBLIC_KEY_LEN
+        )));
+    }
+
+    Ok(AttestationRequest {
+        user_data: Some(user_data),
+        nonce,
+        public_key: Some(public_key),
+    })
 }
 
 /// Health check response.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct HealthCheckResponse {
-    /// Hex encoded public key booted on enclave.
+    /// Hex encoded current public key of the enclave.
     pub pk: String,
-    /// Status of endpoint connectivity checks
-    pub endpoints_status: HashMap<String, bool>,
+    /// Epoch of the current public key.
+    pub key_epoch: u64,
+    /// Signature scheme of the keys.
+    pub key_scheme: SignatureScheme,
+    /// Chain and package the keys sign for, if a domain is configured. The
+    /// `Enclave` object id bound to each key is reported in `keys`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain: Option<DomainConfig>,
+    /// Keys that can sign, the current key and the previous one during its
+    /// overlap window after a rotation.
+    pub keys: Vec<KeyStatus>,
+    /// Hex encoded sha256 hash of the server configuration.
+    pub config_hash: String,
+    /// Hex encoded digest of the runtime configuration measured into PCR16.
+    pub config_measurement: String,
+    /// Hex encoded entries of the runtime configuration, by name, from which a
+    /// verifier recomputes `config_measurement`.
+    pub config_entries: BTreeMap<String, String>,
+    /// Status of the probes of the allowed endpoints, by host.
+    pub endpoints_status: BTreeMap<String, EndpointStatus>,
+    /// Status of supervised background tasks, e.g. the host-only server.
+    pub tasks: BTreeMap<String, TaskStatus>,
 }
 
-/// Endpoint that health checks the enclave connectivity to all
-/// domains and returns the enclave's public key.
+/// Endpoint that probes the allowed endpoints of all apps concurrently and
+/// returns the enclave's public key.
 pub async fn health_check(
     State(state): State<Arc<AppState>>,
 ) -> Result<Json<HealthCheckResponse>, EnclaveError> {
-    let pk = state.eph_kp.public();
-
-    // Create HTTP client with timeout
-    let client = Client::builder()
-        .timeout(Duration::from_secs(5))
-        .build()
-        .map_err(|e| EnclaveError::GenericError(format!("Failed to create HTTP client: {}", e)))?;
-
-    // Load allowed endpoints from YAML file
-    let endpoints_status = match std::fs::read_to_string("allowed_endpoints.yaml") {
-        Ok(yaml_content) => {
-            match serde_yaml::from_str::<serde_yaml::Value>(&yaml_content) {
-                Ok(yaml_value) => {
-                    let mut status_map = HashMap::new();
-
-                    if let Some(endpoints) =
-                        yaml_value.get("endpoints").and_then(|e| e.as_sequence())
-                    {
-                        for endpoint in endpoints {
-                            if let Some(endpoint_str) = endpoint.as_str() {
-                                // Check connectivity to each endpoint
-                                let url = if endpoint_str.contains(".amazonaws.com") {
-                                    format!("https://{}/ping", endpoint_str)
-                                } else {
-                                    format!("https://{}", endpoint_str)
-                                };
-
-                                let is_reachable = match client.get(&url).send().await {
-                                    Ok(response) => {
-                                        if endpoint_str.contains(".amazonaws.com") {
-                                            // For AWS endpoints, check if response body contains "healthy"
-                                            match response.text().await {
-                                                Ok(body) => body.to_lowercase().contains("healthy"),
-                                                Err(e) => {
-                                                    info!(
-                                                        "Failed to read response body from {}: {}",
-                                                        endpoint_str, e
-                                                    );
-                                                    false
-                                                }
-                                            }
-                                        } else {
-                                            // For non-AWS endpoints, check for 200 status
-                                            response.status().is_success()
-                                        }
-                                    }
-                                    Err(e) => {
-                                        info!("Failed to connect to {}: {}", endpoint_str, e);
-                                        false
-                                    }
-                                };
-
-                                status_map.insert(endpoint_str.to_string(), is_reachable);
-                                info!(
-                                    "Checked endpoint {}: reachable = {}",
-                                    endpoint_str, is_reachable
-                                );
-                            }
-                        }
-                    }
-
-                    status_map
-                }
-                Err(e) => {
-                    info!("Failed to parse YAML: {}", e);
-                    HashMap::new()
-                }
-            }
-        }
-        Err(e) => {
-            info!("Failed to read allowed_endpoints.yaml: {}", e);
-            HashMap::new()
-        }
-    };
+    let key = state.keys.current();
+
+    let endpoints_status = state.endpoints.probe_all().await;
 
     Ok(Json(HealthCheckResponse {
-        pk: Hex::encode(pk.as_bytes()),
+        pk: key.public_key_hex(),
+        key_epoch: key.epoch,
+        key_scheme: state.keys.scheme(),
+        domain: state.config.domain.clone(),
+        keys: state.keys.statuses(),
+        config_hash: Hex::encode(state.config.hash),
+        config_measurement: Hex::encode(state.config_measurement.digest),
+        config_entries: state.config_measurement.hex_entries(),
         endpoints_status,
+        tasks: state.supervisor.statuses(),
     }))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_state;
+
+    #[test]
+    fn test_attestation_request() {
+        let state = test_state();
+        let query = GetAttestationQuery {
+            nonce: Some("0a0b".to_string()),
+            user_data: Some("C0FFEE".to_string()),
+            epoch: None,
+        };
+        let AttestationRequest {
+            user_data,
+            nonce,
+            public_key,
+        } = attestation_request(&state, &query).unwrap();
+        assert_eq!(nonce.unwrap(), vec![0x0a, 0x0b]);
+        assert_eq!(
+            public_key.unwrap(),
+            state.keys.current().kp.public_key_bytes()
+        );
+        let user_data: AttestationUserData = serde_json::from_slice(&user_data.unwrap()).unwrap();
+        assert_eq!(user_data.apps, vec!["test".to_string()]);
+        assert_eq!(user_data.key_scheme, SignatureScheme::Ed25519);
+        assert_eq!(user_data.key_epoch, 0);
+        assert_eq!(user_data.config_hash, Hex::encode(state.config.hash));
+        assert_eq!(user_data.client_data.as_deref(), Some("c0ffee"));
+    }
+
+    #[test]
+    fn test_attestation_request_limits() {
+        let state = test_state();
+        let request = |nonce: Option<String>, user_data: Option<String>| {
+            attestation_request(
+                &state,
+                &GetAttestationQuery {
+                    nonce,
+                    user_data,
+                    epoch: None,
+                },
+            )
+        };
+        assert!(request(None, None).is_ok());
+        assert!(request(Some("zz".to_string()), None).is_err());
+        assert!(request(Some(String::new()), None).is_err());
+        assert!(request(Some("00".repeat(MAX_NONCE_LEN)), None).is_ok());
+        assert!(request(Some("00".repeat(MAX_NONCE_LEN + 1)), None).is_err());
+        assert!(request(None, Some("00".repeat(MAX_USER_DATA_LEN))).is_err());
+        let unknown_epoch = GetAttestationQuery {
+            nonce: None,
+            user_data: None,
+            epoch: Some(1),
+        };
+        assert!(attestation_request(&state, &unknown_epoch).is_err());
+    }
+
+    #[test]
+    fn test_request_binding() {
+        let request = |nonce: Option<&str>, caller: Option<&str>| ProcessDataRequest {
+            payload: (),
+            key_epoch: None,
+            nonce: nonce.map(str::to_string),
+            caller: caller.map(str::to_string),
+        };
+        assert_eq!(request(None, None).binding().unwrap(), None);
+        assert!(request(Some("zz"), None).binding().is_err());
+        assert!(request(Some(""), None).binding().is_err());
+        assert!(request(None, Some("0x01")).binding().is_err());
+
+        let caller = format!("0x{}01", "00".repeat(31));
+        let binding = request(Some("0102"), Some(&caller))
+            .binding()
+            .unwrap()
+            .unwrap();
+
+        // test result should be consistent with test_serde_binding in `move/enclave/sources/enclave.move`.
+        #[derive(Debug, Serialize)]
+        struct SigningPayload {
+            location: String,
+            temperature: u64,
+        }
+        let intent_msg = IntentMessage::new(
+            SigningPayload {
+                location: "San Francisco".to_string(),
+                temperature: 13,
+            },
+            1744038900000,
+            IntentScope::ProcessData,
+        );
+        assert_eq!(
+            signing_payload(None, &intent_msg, None),
+            bcs::to_bytes(&intent_msg).unwrap()
+        );
+        assert_eq!(
+            Hex::encode(signing_payload(None, &intent_msg, Some(&binding))),
+            format!(
+                "0020b1d110960100000d53616e204672616e636973636f0d000000000000000102010201{}",
+                "00".repeat(31) + "01"
+            )
+        );
+    }
+
+    #[test]
+    fn test_bls_signing_domain() {
+        use crate::domain::DomainConfig;
+        use crate::keys::{test_keys, SignatureScheme};
+        use std::time::Duration;
+
+        let config = DomainConfig {
+            chain_id: "4c78adac".to_string(),
+            package_id: format!("0x{}02", "00".repeat(31)),
+            committee_id: Some(format!("0x{}04", "00".repeat(31))),
+        };
+        let keys = test_keys(SignatureScheme::Ed25519, Duration::ZERO, true);
+        let key = keys.current();
+        let sign = || to_signed_response(&key, 7u64, 1744038900000, IntentScope::ProcessData, None);
+        let intent_msg = IntentMessage::new(7u64, 1744038900000, IntentScope::ProcessData);
+
+        // Without domain, both keys sign the same bytes.
+        assert_eq!(
+            sign().bls.unwrap().message,
+            Hex::encode(signing_payload(None, &intent_msg, None))
+        );
+
+        // Bound to a domain only, the BLS key does not sign.
+        key.domain.set(config.separator([3; 32]).unwrap()).unwrap();
+        assert!(sign().bls.is_none());
+
+        // Bound to the committee, the BLS key signs without the Enclave object.
+        let committee = config.committee_separator().unwrap().unwrap();
+        key.committee.set(committee.clone()).unwrap();
+        assert_eq!(
+            sign().bls.unwrap().message,
+            Hex::encode(bls_signing_payload(Some(&committee), &intent_msg, None))
+        );
+    }
+}
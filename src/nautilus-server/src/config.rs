@@ -0,0 +1,335 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::batch::MAX_BATCH_SIZE;
+use crate::domain::DomainConfig;
+use crate::keys::SignatureScheme;
+use crate::EnclaveError;
+use axum::http::HeaderValue;
+use fastcrypto::hash::{HashFunction, Sha256};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// Server configuration baked into the binary, and thus covered by PCR2.
+pub const SERVER_CONFIG_YAML: &str = include_str!("../server_config.yaml");
+
+/// Upper bound for `max_body_bytes`, requests are small JSON payloads.
+const MAX_BODY_BYTES_LIMIT: usize = 16 * 1024 * 1024;
+
+/// Typed server configuration, see `server_config.yaml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Address of the public listener.
+    pub listen_addr: SocketAddr,
+    /// Address of the host-only listener.
+    pub host_listen_addr: SocketAddr,
+    /// CORS policy of the public listener.
+    pub cors: CorsConfig,
+    /// Maximum size of a request body in bytes.
+    pub max_body_bytes: usize,
+    /// Maximum time a handler may take in milliseconds.
+    pub handler_timeout_ms: u64,
+    /// Non-secret env vars measured into the config PCR, see `measurement.rs`.
+    #[serde(default)]
+    pub measured_env: Vec<String>,
+    /// Signature scheme of the ephemeral keys, ed25519 if unset.
+    #[serde(default)]
+    pub signature_scheme: SignatureScheme,
+    /// Rotation of the ephemeral key, the key is never rotated if unset.
+    #[serde(default)]
+    pub key_rotation: Option<KeyRotationConfig>,
+    /// Deployment the keys sign for, signatures carry no domain if unset.
+    #[serde(default)]
+    pub domain: Option<DomainConfig>,
+    /// Sign every response with an additional BLS12-381 key, so that the
+    /// signatures of replicas can be aggregated, see `aggregator.rs`.
+    #[serde(default)]
+    pub bls_signing: bool,
+    /// Sign a failure attestation for requests that are evaluated and found
+    /// invalid, see `failure.rs`.
+    #[serde(default)]
+    pub signed_failures: bool,
+    /// Default timeouts of outbound calls to allowed endpoints, see `egress.rs`.
+    #[serde(default)]
+    pub egress: EgressConfig,
+    /// Processing of batch requests, see `batch.rs`.
+    #[serde(default)]
+    pub batch: BatchConfig,
+    /// Sha256 hash of the raw configuration file.
+    #[serde(skip)]
+    pub hash: [u8; 32],
+}
+
+/// CORS configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CorsConfig {
+    /// Allowed origins, "*" allows any origin.
+    pub allowed_origins: Vec<String>,
+}
+
+/// Ephemeral key rotation, see `keys.rs`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeyRotationConfig {
+    /// Time between two rotations in seconds.
+    pub interval_secs: u64,
+    /// Time the previous key keeps signing after a rotation in seconds.
+    pub overlap_secs: u64,
+}
+
+/// Outbound calls to allowed endpoints, see `egress.rs`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EgressConfig {
+    /// Maximum time of a call in milliseconds, unless the request sets its own.
+    pub timeout_ms: u64,
+    /// Maximum time to connect in milliseconds.
+    pub connect_timeout_ms: u64,
+}
+
+impl Default for EgressConfig {
+    fn default() -> Self {
+        Self {
+            timeout_ms: 10_000,
+            connect_timeout_ms: 3_000,
+        }
+    }
+}
+
+impl EgressConfig {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+
+    pub fn connect_timeout(&self) -> Duration {
+        Duration::from_millis(self.connect_timeout_ms)
+    }
+}
+
+/// Batch requests, see `batch.rs`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BatchConfig {
+    /// Maximum number of requests of a batch processed at once, which bounds
+    /// the upstream calls and forwarder connections a single batch can use.
+    pub concurrency: usize,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self { concurrency: 16 }
+    }
+}
+
+impl KeyRotationConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs)
+    }
+
+    pub fn overlap(&self) -> Duration {
+        Duration::from_secs(self.overlap_secs)
+    }
+}
+
+impl ServerConfig {
+    /// Load and validate the configuration baked into the binary.
+    pub fn load() -> Result<Self, EnclaveError> {
+        Self::from_yaml(SERVER_CONFIG_YAML)
+    }
+
+    /// Parse and validate a configuration, hashing its raw bytes.
+    pub fn from_yaml(yaml: &str) -> Result<Self, EnclaveError> {
+        let mut config: ServerConfig = serde_yaml::from_str(yaml).map_err(|e| {
+            EnclaveError::InternalError(format!("Failed to parse server config: {}", e))
+        })?;
+        config.validate()?;
+        config.hash = Sha256::digest(yaml.as_bytes()).digest;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), EnclaveError> {
+        let invalid = |msg: String| Err(EnclaveError::InternalError(msg));
+
+        if self.listen_addr.port() == self.host_listen_addr.port() {
+            return invalid(format!(
+                "listen_addr and host_listen_addr must use different ports, got {}",
+                self.listen_addr.port()
+            ));
+        }
+        if self.max_body_bytes == 0 || self.max_body_bytes > MAX_BODY_BYTES_LIMIT {
+            return invalid(format!(
+                "max_body_bytes must be in 1..={}, got {}",
+                MAX_BODY_BYTES_LIMIT, self.max_body_bytes
+            ));
+        }
+        if self.handler_timeout_ms == 0 {
+            return invalid("handler_timeout_ms must be positive".to_string());
+        }
+        if self.measured_env.iter().any(|name| name.is_empty()) {
+            return invalid("measured_env cannot contain empty names".to_string());
+        }
+        if let Some(rotation) = &self.key_rotation {
+            if rotation.interval_secs == 0 {
+                return invalid("key_rotation.interval_secs must be positive".to_string());
+            }
+            if rotation.overlap_secs > rotation.interval_secs {
+                return invalid(format!(
+                    "key_rotation.overlap_secs must not exceed interval_secs, got {} > {}",
+                    rotation.overlap_secs, rotation.interval_secs
+                ));
+            }
+        }
+        if let Some(domain) = &self.domain {
+            domain.validate()?;
+            if self.bls_signing && domain.committee_id.is_none() {
+                return invalid(
+                    "domain.committee_id is required with bls_signing and a domain".to_string(),
+                );
+            }
+        }
+        if self.egress.connect_timeout_ms == 0
+            || self.egress.connect_timeout_ms > self.egress.timeout_ms
+        {
+            return invalid(format!(
+                "egress.connect_timeout_ms must be in 1..=timeout_ms, got {} > {}",
+                self.egress.connect_timeout_ms, self.egress.timeout_ms
+            ));
+        }
+        if self.batch.concurrency == 0 || self.batch.concurrency > MAX_BATCH_SIZE {
+            return invalid(format!(
+                "batch.concurrency must be in 1..={}, got {}",
+                MAX_BATCH_SIZE, self.batch.concurrency
+            ));
+        }
+        self.cors.validate()
+    }
+
+    /// Overlap window of the previous key, zero if keys are never rotated.
+    pub fn key_overlap(&self) -> Duration {
+        self.key_rotation
+            .as_ref()
+            .map(KeyRotationConfig::overlap)
+            .unwrap_or_default()
+    }
+
+    pub fn handler_timeout(&self) -> Duration {
+        Duration::from_millis(self.handler_timeout_ms)
+    }
+}
+
+impl CorsConfig {
+    /// Whether any origin is allowed.
+    pub fn allows_any_origin(&self) -> bool {
+        self.allowed_origins.iter().any(|o| o == "*")
+    }
+
+    /// Allowed origins as header values, empty if any origin is allowed.
+    pub fn origin_headers(&self) -> Vec<HeaderValue> {
+        self.allowed_origins
+            .iter()
+            .filter(|o| *o != "*")
+            .filter_map(|o| HeaderValue::from_str(o).ok())
+            .collect()
+    }
+
+    fn validate(&self) -> Result<(), EnclaveError> {
+        if self.allows_any_origin() && self.allowed_origins.len() > 1 {
+            return Err(EnclaveError::InternalError(
+                "cors.allowed_origins cannot mix \"*\" with explicit origins".to_string(),
+            ));
+        }
+        for origin in self.allowed_origins.iter().filter(|o| *o != "*") {
+            let valid = (origin.starts_with("https://") || origin.starts_with("http://"))
+                && !origin.ends_with('/')
+                && HeaderValue::from_str(origin).is_ok();
+            if !valid {
+                return Err(EnclaveError::InternalError(format!(
+                    "Invalid CORS origin {}, expected e.g. https://example.com",
+                    origin
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_baked_config() {
+        let config = ServerConfig::load().unwrap();
+        assert_eq!(config.listen_addr.port(), 3000);
+        assert_eq!(config.host_listen_addr.port(), 3001);
+        assert_eq!(config.hash, Sha256::digest(SERVER_CONFIG_YAML).digest);
+        assert_eq!(config.signature_scheme, SignatureScheme::Ed25519);
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let base = |extra: &str| {
+            format!(
+                "listen_addr: \"0.0.0.0:3000\"\nhost_listen_addr: \"0.0.0.0:3001\"\nmax_body_bytes: 1024\nhandler_timeout_ms: 1000\n{}",
+                extra
+            )
+        };
+        assert!(
+            ServerConfig::from_yaml(&base("cors:\n  allowed_origins: [\"https://a.com\"]")).is_ok()
+        );
+        assert!(ServerConfig::from_yaml(&base("cors:\n  allowed_origins: [\"a.com\"]")).is_err());
+        assert!(ServerConfig::from_yaml(&base(
+            "cors:\n  allowed_origins: [\"*\", \"https://a.com\"]"
+        ))
+        .is_err());
+        assert!(
+            ServerConfig::from_yaml(&base("cors:\n  allowed_origins: []\nunknown: 1")).is_err()
+        );
+        let cors = "cors:\n  allowed_origins: []\n";
+        assert!(ServerConfig::from_yaml(&base(&format!(
+            "{}key_rotation:\n  interval_secs: 3600\n  overlap_secs: 600",
+            cors
+        )))
+        .is_ok());
+        assert!(ServerConfig::from_yaml(&base(&format!(
+            "{}key_rotation:\n  interval_secs: 0\n  overlap_secs: 0",
+            cors
+        )))
+        .is_err());
+        assert!(ServerConfig::from_yaml(&base(&format!(
+            "{}key_rotation:\n  interval_secs: 60\n  overlap_secs: 600",
+            cors
+        )))
+        .is_err());
+        assert!(ServerConfig::from_yaml(&base(&format!(
+            "{}egress:\n  timeout_ms: 1000\n  connect_timeout_ms: 2000",
+            cors
+        )))
+        .is_err());
+        assert!(
+            ServerConfig::from_yaml(&base(&format!("{}batch:\n  concurrency: 0", cors))).is_err()
+        );
+        let domain = format!(
+            "domain:\n  chain_id: \"4c78adac\"\n  package_id: \"0x{}02\"\n",
+            "00".repeat(31)
+        );
+        assert!(
+            ServerConfig::from_yaml(&base(&format!("{}{}bls_signing: true", cors, domain)))
+                .is_err()
+        );
+        assert!(ServerConfig::from_yaml(&base(&format!(
+            "{}{}  committee_id: \"0x{}04\"\nbls_signing: true",
+            cors,
+            domain,
+            "00".repeat(31)
+        )))
+        .is_ok());
+        assert!(ServerConfig::from_yaml(
+            "listen_addr: \"0.0.0.0:3000\"\nhost_listen_addr: \"127.0.0.1:3000\"\nmax_body_bytes: 1024\nhandler_timeout_ms: 1000\ncors:\n  allowed_origins: []"
+        )
+        .is_err());
+    }
+}
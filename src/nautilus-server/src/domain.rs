@@ -0,0 +1,186 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::common::decode_address;
+use crate::AppState;
+use crate::EnclaveError;
+use axum::extract::State;
+use axum::Json;
+use fastcrypto::encoding::{Encoding, Hex};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::info;
+
+pub use nautilus_types::{CommitteeSeparator, DomainSeparator};
+
+/// Domain of the deployment configured at boot, see `server_config.yaml`.
+/// The `Enclave` object id is only known once the key is registered onchain,
+/// so it is bound to each key with `/set_enclave_id` on the host-only listener.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DomainConfig {
+    /// Hex encoded Sui chain identifier, e.g. "4c78adac" for testnet.
+    pub chain_id: String,
+    /// Package id of the app consuming the signatures, e.g. "0x1234…".
+    pub package_id: String,
+    /// Object id of the `bls_committee::Committee` the BLS signatures are
+    /// aggregated for, required with `bls_signing`.
+    #[serde(default)]
+    pub committee_id: Option<String>,
+}
+
+impl DomainConfig {
+    pub fn validate(&self) -> Result<(), EnclaveError> {
+        self.separator([0; 32])?;
+        self.committee_separator().map(|_| ())
+    }
+
+    /// Domain separator of the key registered as the `enclave_id` object.
+    pub fn separator(&self, enclave_id: [u8; 32]) -> Result<DomainSeparator, EnclaveError> {
+        let chain_id = Hex::decode(&self.chain_id)
+            .ok()
+            .filter(|id| !id.is_empty())
+            .ok_or_else(|| {
+                EnclaveError::InternalError(format!(
+                    "domain.chain_id must be hex encoded, got {}",
+                    self.chain_id
+                ))
+            })?;
+        let package_id = decode_address(&self.package_id).ok_or_else(|| {
+            EnclaveError::InternalError(format!(
+                "domain.package_id must be a 32 bytes hex encoded object id, got {}",
+                self.package_id
+            ))
+        })?;
+        Ok(DomainSeparator {
+            chain_id,
+            package_id,
+            enclave_id,
+        })
+    }
+
+    /// Committee separator the BLS keys sign with, if a committee is configured.
+    pub fn committee_separator(&self) -> Result<Option<CommitteeSeparator>, EnclaveError> {
+        let Some(committee_id) = &self.committee_id else {
+            return Ok(None);
+        };
+        let committee_id = decode_address(committee_id).ok_or_else(|| {
+            EnclaveError::InternalError(format!(
+                "domain.committee_id must be a 32 bytes hex encoded object id, got {}",
+                committee_id
+            ))
+        })?;
+        let domain = self.separator([0; 32])?;
+        Ok(Some(CommitteeSeparator {
+            chain_id: domain.chain_id,
+            package_id: domain.package_id,
+            committee_id,
+        }))
+    }
+}
+
+/// Request to bind the `Enclave` object id of a key.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetEnclaveIdRequest {
+    /// Key epoch registered as the `Enclave` object, defaults to the current key.
+    #[serde(default)]
+    pub key_epoch: Option<u64>,
+    /// Object id of the `Enclave` created by `register_enclave`.
+    pub enclave_id: String,
+}
+
+/// Response of set enclave id.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetEnclaveIdResponse {
+    pub key_epoch: u64,
+    /// Hex encoded BCS bytes of the domain separator the key now signs with.
+    pub domain: String,
+}
+
+/// Host-only endpoint that binds the `Enclave` object id of a key once it is
+/// registered onchain. The key refuses to sign until then if a domain is
+/// configured, and the binding cannot be changed afterwards.
+pub async fn set_enclave_id(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<SetEnclaveIdRequest>,
+) -> Result<Json<SetEnclaveIdResponse>, EnclaveError> {
+    let config = state.config.domain.as_ref().ok_or_else(|| {
+        EnclaveError::BadRequest("No domain configured in server_config.yaml".to_string())
+    })?;
+    let enclave_id = decode_address(&request.enclave_id).ok_or_else(|| {
+        EnclaveError::BadRequest("enclave_id must be a 32 bytes hex encoded object id".to_string())
+    })?;
+    let key = state.keys.key(request.key_epoch)?;
+    let domain = config.separator(enclave_id)?;
+    let committee = config.committee_separator()?;
+    key.domain.set(domain.clone()).map_err(|_| {
+        EnclaveError::BadRequest(format!(
+            "Enclave id of key epoch {} is already set",
+            key.epoch
+        ))
+    })?;
+    if let Some(committee) = committee {
+        // Only ever set here, right after the domain.
+        let _ = key.committee.set(committee);
+    }
+    info!(
+        "bound key epoch {} to enclave {}",
+        key.epoch, request.enclave_id
+    );
+    Ok(Json(SetEnclaveIdResponse {
+        key_epoch: key.epoch,
+        domain: Hex::encode(bcs::to_bytes(&domain).expect("should not fail")),
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_domain_separator() {
+        let config = DomainConfig {
+            chain_id: "4c78adac".to_string(),
+            package_id: format!("0x{}02", "00".repeat(31)),
+            committee_id: None,
+        };
+        assert!(config.validate().is_ok());
+        let mut enclave_id = [0; 32];
+        enclave_id[31] = 3;
+        let domain = config.separator(enclave_id).unwrap();
+        // test result should be consistent with test_serde_domain in `move/enclave/sources/enclave.move`.
+        assert_eq!(
+            Hex::encode(bcs::to_bytes(&domain).unwrap()),
+            format!("044c78adac{}02{}03", "00".repeat(31), "00".repeat(31))
+        );
+
+        let invalid = |chain_id: &str, package_id: &str| {
+            DomainConfig {
+                chain_id: chain_id.to_string(),
+                package_id: package_id.to_string(),
+                committee_id: None,
+            }
+            .validate()
+            .is_err()
+        };
+        assert!(invalid("", &config.package_id));
+        assert!(invalid("testnet", &config.package_id));
+        assert!(invalid(&config.chain_id, "0x02"));
+
+        assert_eq!(config.committee_separator().unwrap(), None);
+        let config = DomainConfig {
+            committee_id: Some(format!("0x{}04", "00".repeat(31))),
+            ..config
+        };
+        let committee = config.committee_separator().unwrap().unwrap();
+        assert_eq!(committee.chain_id, domain.chain_id);
+        assert_eq!(committee.package_id, domain.package_id);
+        assert_eq!(committee.committee_id[31], 4);
+        assert!(DomainConfig {
+            committee_id: Some("0x04".to_string()),
+            ..config
+        }
+        .validate()
+        .is_err());
+    }
+}
@@ -0,0 +1,240 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::config::EgressConfig;
+use crate::endpoints::EndpointsConfig;
+use crate::metrics;
+use crate::EnclaveError;
+use reqwest::redirect::{Attempt, Policy};
+use reqwest::{Client, Method, RequestBuilder, Response, Url};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Maximum number of redirects followed by a call, like the reqwest default.
+const MAX_REDIRECTS: usize = 10;
+
+/// HTTP client shared by the apps for outbound calls. It only calls the hosts
+/// of the allowed endpoints of the apps over https on port 443, including on
+/// redirects, pools connections, applies the default timeouts of the server config, and
+/// records every call in the metrics and the `egress` audit log.
+#[derive(Clone)]
+pub struct EgressClient {
+    client: Client,
+    allowed: Arc<BTreeSet<String>>,
+}
+
+impl Default for EgressClient {
+    /// Client that refuses every call.
+    fn default() -> Self {
+        Self::new(&EndpointsConfig::default(), &EgressConfig::default())
+            .expect("empty allowlist should be valid")
+    }
+}
+
+impl EgressClient {
+    /// Client allowed to call the hosts of `endpoints`. Fails if a host is an
+    /// IP address, since the forwarder of the enclave resolves host names and
+    /// certificates are issued for them.
+    pub fn new(endpoints: &EndpointsConfig, config: &EgressConfig) -> Result<Self, EnclaveError> {
+        let mut allowed = BTreeSet::new();
+        for endpoint in &endpoints.endpoints {
+            if endpoint.host.parse::<IpAddr>().is_ok() {
+                return Err(EnclaveError::InternalError(format!(
+                    "Endpoint {} is an IP address, list its host name instead",
+                    endpoint.host
+                )));
+            }
+            allowed.insert(endpoint.host.to_ascii_lowercase());
+        }
+        let allowed = Arc::new(allowed);
+        let redirect_allowed = allowed.clone();
+        let redirect = Policy::custom(move |attempt: Attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else if let Err(e) = check_url(&redirect_allowed, attempt.url()) {
+                attempt.error(e)
+            } else {
+                attempt.follow()
+            }
+        });
+        Ok(Self {
+            client: Client::builder()
+                .timeout(config.timeout())
+                .connect_timeout(config.connect_timeout())
+                .redirect(redirect)
+                // Lets the endpoint probes report certificate expiry.
+                .tls_info(true)
+                .build()
+                .expect("egress client should build"),
+            allowed,
+        })
+    }
+
+    /// Whether calls to `url` are allowed.
+    pub fn is_allowed(&self, url: &str) -> bool {
+        Url::parse(url).is_ok_and(|url| check_url(&self.allowed, &url).is_ok())
+    }
+
+    /// Start a GET request, failing if the URL is not allowed.
+    pub fn get(&self, url: &str) -> Result<EgressRequest, EnclaveError> {
+        self.request(Method::GET, url)
+    }
+
+    /// Start a POST request, failing if the URL is not allowed.
+    pub fn post(&self, url: &str) -> Result<EgressRequest, EnclaveError> {
+        self.request(Method::POST, url)
+    }
+
+    fn request(&self, method: Method, url: &str) -> Result<EgressRequest, EnclaveError> {
+        let url = Url::parse(url)
+            .map_err(|e| EnclaveError::InternalError(format!("Invalid egress URL: {}", e)))?;
+        let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+        if let Err(e) = check_url(&self.allowed, &url) {
+            metrics::observe_egress_denied(&host);
+            warn!(target: "egress", method = %method, host = %host, "refused: {}", e);
+            return Err(EnclaveError::InternalError(e));
+        }
+        Ok(EgressRequest {
+            path: url.path().to_string(),
+            builder: self.client.request(method.clone(), url),
+            method,
+            host,
+        })
+    }
+}
+
+/// Check that `url` is an https URL on port 443 of an allowed host name.
+fn check_url(allowed: &BTreeSet<String>, url: &Url) -> Result<(), String> {
+    let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+    if url.scheme() != "https" {
+        return Err(format!("Egress to {} must use https", host));
+    }
+    if url.port_or_known_default() != Some(443) {
+        return Err(format!("Egress to {} must use port 443", host));
+    }
+    // IP hosts are never allowed, whatever the allowlist.
+    if url.domain().is_none() || !allowed.contains(&host) {
+        return Err(format!(
+            "Egress to {} is not allowed, add it to allowed_endpoints.yaml",
+            host
+        ));
+    }
+    Ok(())
+}
+
+/// Outbound request to an allowed host, see `EgressClient`.
+pub struct EgressRequest {
+    builder: RequestBuilder,
+    method: Method,
+    host: String,
+    path: String,
+}
+
+impl EgressRequest {
+    pub fn header(mut self, key: &'static str, value: impl AsRef<str>) -> Self {
+        self.builder = self.builder.header(key, value.as_ref());
+        self
+    }
+
+    pub fn json<T: Serialize + ?Sized>(mut self, body: &T) -> Self {
+        self.builder = self.builder.json(body);
+        self
+    }
+
+    /// Override the default timeout of the client.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.builder = self.builder.timeout(timeout);
+        self
+    }
+
+    /// Send the request, recording its outcome and latency. The query is left
+    /// out of the audit log since it may carry API keys.
+    pub async fn send(self) -> Result<Response, reqwest::Error> {
+        let start = Instant::now();
+        let result = self.builder.send().await;
+        let latency = start.elapsed();
+        metrics::observe_upstream(&self.host, &result);
+        metrics::UPSTREAM_LATENCY
+            .with_label_values(&[&self.host])
+            .observe(latency.as_secs_f64());
+        let latency_ms = latency.as_millis() as u64;
+        match &result {
+            Ok(response) => info!(
+                target: "egress",
+                method = %self.method,
+                host = %self.host,
+                path = %self.path,
+                status = response.status().as_u16(),
+                latency_ms,
+                "call"
+            ),
+            Err(e) => warn!(
+                target: "egress",
+                method = %self.method,
+                host = %self.host,
+                path = %self.path,
+                latency_ms,
+                "call failed: {}",
+                e
+            ),
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::endpoints::Endpoint;
+
+    #[tokio::test]
+    async fn test_egress_allowlist() {
+        let endpoints =
+            EndpointsConfig::from_yaml("endpoints:\n  - api.weatherapi.com\n  - localhost")
+                .unwrap();
+        let egress = EgressClient::new(&endpoints, &EgressConfig::default()).unwrap();
+        assert!(egress.is_allowed("https://api.weatherapi.com/v1/current.json?q=Paris"));
+        assert!(egress.is_allowed("https://API.weatherapi.com/"));
+        assert!(egress.is_allowed("https://api.weatherapi.com:443/"));
+        assert!(!egress.is_allowed("http://api.weatherapi.com/"));
+        assert!(!egress.is_allowed("https://api.weatherapi.com:8443/"));
+        assert!(!egress.is_allowed("https://127.0.0.1/"));
+        assert!(!egress.is_allowed("https://[::1]/"));
+        assert!(!egress.is_allowed("https://api.weatherapi.com.evil.com/"));
+        assert!(!egress.is_allowed("https://evil.com/?u=https://api.weatherapi.com"));
+        assert!(!egress.is_allowed("not a url"));
+
+        assert!(matches!(
+            egress.get("https://evil.com/"),
+            Err(EnclaveError::InternalError(_))
+        ));
+        assert!(EgressClient::default()
+            .get("https://api.weatherapi.com/")
+            .is_err());
+
+        // IP addresses cannot be allowed.
+        for host in ["127.0.0.1", "::1"] {
+            let endpoints = EndpointsConfig {
+                endpoints: vec![Endpoint {
+                    host: host.to_string(),
+                    probe: Default::default(),
+                }],
+            };
+            assert!(EgressClient::new(&endpoints, &EgressConfig::default()).is_err());
+        }
+
+        // Allowed calls are sent, and fail here since nothing listens on port
+        // 443 of the loopback address.
+        let result = egress
+            .get("https://localhost/")
+            .unwrap()
+            .timeout(Duration::from_secs(1))
+            .send()
+            .await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,255 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::egress::EgressClient;
+use crate::registry::AppRegistry;
+use crate::EnclaveError;
+use reqwest::tls::TlsInfo;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::info;
+use x509_parser::certificate::X509Certificate;
+use x509_parser::prelude::FromDer;
+
+/// Types of `allowed_endpoints.yaml`, shared with the `nautilus-endpoints`
+/// binary, see the nautilus-endpoints crate.
+pub use nautilus_endpoints::{Endpoint, EndpointsConfig, InvalidEndpoints, ProbeConfig};
+
+/// Endpoints of every registered app, see `EndpointsConfig::merge`.
+pub fn collect(registry: &AppRegistry) -> Result<EndpointsConfig, EnclaveError> {
+    let mut config = EndpointsConfig::default();
+    for app in registry.apps() {
+        config.merge(app.allowed_endpoints()?)?;
+    }
+    Ok(config)
+}
+
+/// Result of the last probe of an endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EndpointStatus {
+    /// Whether the endpoint answered as expected by its probe.
+    pub healthy: bool,
+    /// Time until the response headers, or until the failure, in milliseconds.
+    pub latency_ms: u64,
+    /// HTTP status of the response, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// Expiry of the TLS certificate of the host in seconds since the epoch,
+    /// if the TLS handshake succeeded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_expiry_secs: Option<i64>,
+    /// Time of the last healthy probe since boot in milliseconds since the epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_success_ms: Option<u64>,
+    /// Reason the endpoint is unhealthy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Probes the allowed endpoints loaded at boot, and remembers the time of the
+/// last healthy probe of each.
+pub struct EndpointProber {
+    config: EndpointsConfig,
+    egress: EgressClient,
+    last_success: Mutex<HashMap<String, u64>>,
+    /// Time and statuses of the last probe of all endpoints. The lock is held
+    /// while probing, so that concurrent callers wait for a single probe.
+    last_probe: tokio::sync::Mutex<Option<(Instant, BTreeMap<String, EndpointStatus>)>>,
+}
+
+impl Default for EndpointProber {
+    fn default() -> Self {
+        Self::new(EndpointsConfig::default(), EgressClient::default())
+    }
+}
+
+impl EndpointProber {
+    /// Prober of the endpoints of `config`, which `egress` must allow.
+    pub fn new(config: EndpointsConfig, egress: EgressClient) -> Self {
+        Self {
+            config,
+            egress,
+            last_success: Mutex::new(HashMap::new()),
+            last_probe: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    pub fn config(&self) -> &EndpointsConfig {
+        &self.config
+    }
+
+    /// Probe every endpoint concurrently.
+    pub async fn probe_all(&self) -> BTreeMap<String, EndpointStatus> {
+        let mut last_probe = self.last_probe.lock().await;
+        let statuses = self.probe_endpoints().await;
+        *last_probe = Some((Instant::now(), statuses.clone()));
+        statuses
+    }
+
+    /// Statuses of the last probe if it is more recent than `max_age`, so that
+    /// frequent callers such as `/readyz` do not hit the endpoints each time.
+    /// Otherwise probe every endpoint again, once for all concurrent callers:
+    /// they wait for the probe in flight and share its statuses.
+    pub async fn cached_statuses(&self, max_age: Duration) -> BTreeMap<String, EndpointStatus> {
+        let mut last_probe = self.last_probe.lock().await;
+        if let Some((at, statuses)) = &*last_probe {
+            if at.elapsed() <= max_age {
+                return statuses.clone();
+            }
+        }
+        let statuses = self.probe_endpoints().await;
+        *last_probe = Some((Instant::now(), statuses.clone()));
+        statuses
+    }
+
+    async fn probe_endpoints(&self) -> BTreeMap<String, EndpointStatus> {
+        let statuses =
+            futures::future::join_all(self.config.endpoints.iter().map(|e| self.probe(e))).await;
+        self.config
+            .endpoints
+            .iter()
+            .map(|endpoint| endpoint.host.clone())
+            .zip(statuses)
+            .collect()
+    }
+
+    async fn probe(&self, endpoint: &Endpoint) -> EndpointStatus {
+        let Endpoint { host, probe } = endpoint;
+        let start = Instant::now();
+        let result = match self.egress.get(&format!("https://{}{}", host, probe.path)) {
+            Ok(request) => request
+                .timeout(Duration::from_millis(probe.timeout_ms))
+                .send()
+                .await
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        let latency_ms = start.elapsed().as_millis() as u64;
+
+        let mut status = EndpointStatus {
+            healthy: false,
+            latency_ms,
+            status: None,
+            tls_expiry_secs: None,
+            last_success_ms: None,
+            error: None,
+        };
+        match result {
+            Ok(response) => {
+                status.status = Some(response.status().as_u16());
+                status.tls_expiry_secs = response
+                    .extensions()
+                    .get::<TlsInfo>()
+                    .and_then(TlsInfo::peer_certificate)
+                    .and_then(certificate_expiry);
+                status.error = check_response(probe, response).await.err();
+                status.healthy = status.error.is_none();
+            }
+            Err(e) => status.error = Some(e),
+        }
+
+        let mut last_success = self.last_success.lock().unwrap();
+        if status.healthy {
+            let now_ms = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or_default();
+            last_success.insert(host.clone(), now_ms);
+        }
+        status.last_success_ms = last_success.get(host).copied();
+        info!(
+            "Checked endpoint {}: healthy = {}, latency = {}ms",
+            host, status.healthy, latency_ms
+        );
+        status
+    }
+}
+
+/// Check the status and body of a response against the probe.
+async fn check_response(probe: &ProbeConfig, response: reqwest::Response) -> Result<(), String> {
+    let code = response.status();
+    let expected = match probe.expected_status {
+        Some(expected) => code.as_u16() == expected,
+        None => code.is_success(),
+    };
+    if !expected {
+        return Err(format!("unexpected status {}", code.as_u16()));
+    }
+    if let Some(expected_body) = &probe.expected_body {
+        let body = response
+            .text()
+            .await
+            .map_err(|e| format!("failed to read body: {}", e))?;
+        if !body.to_lowercase().contains(&expected_body.to_lowercase()) {
+            return Err(format!("body does not contain {:?}", expected_body));
+        }
+    }
+    Ok(())
+}
+
+/// Expiry of a DER encoded certificate in seconds since the epoch.
+fn certificate_expiry(der: &[u8]) -> Option<i64> {
+    let (_, cert) = X509Certificate::from_der(der).ok()?;
+    Some(cert.validity().not_after.timestamp())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_probe_unreachable() {
+        // Nothing listens on port 443 of the loopback address, the probe fails
+        // without depending on DNS or the network.
+        let config = EndpointsConfig {
+            endpoints: vec![Endpoint {
+                host: "localhost".to_string(),
+                probe: ProbeConfig {
+                    timeout_ms: 1000,
+                    ..Default::default()
+                },
+            }],
+        };
+        let egress = EgressClient::new(&config, &Default::default()).unwrap();
+        let prober = EndpointProber::new(config, egress);
+        let statuses = prober.probe_all().await;
+        let status = &statuses["localhost"];
+        assert!(!status.healthy);
+        assert!(status.error.is_some());
+        assert_eq!(status.status, None);
+        assert_eq!(status.last_success_ms, None);
+    }
+
+    #[tokio::test]
+    async fn test_cached_statuses_single_flight() {
+        let prober = std::sync::Arc::new(EndpointProber::default());
+
+        // A caller arriving while a probe is in flight waits for it and shares
+        // its statuses instead of probing again.
+        let mut in_flight = prober.last_probe.lock().await;
+        let waiter = tokio::spawn({
+            let prober = prober.clone();
+            async move { prober.cached_statuses(Duration::from_secs(30)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiter.is_finished());
+        let status = EndpointStatus {
+            healthy: true,
+            latency_ms: 1,
+            status: Some(200),
+            tls_expiry_secs: None,
+            last_success_ms: None,
+            error: None,
+        };
+        let probed = BTreeMap::from([("api.weatherapi.com".to_string(), status)]);
+        *in_flight = Some((Instant::now(), probed));
+        drop(in_flight);
+        let statuses = waiter.await.unwrap();
+        assert!(statuses["api.weatherapi.com"].healthy);
+
+        // Once stale, the statuses are probed again.
+        assert!(prober.cached_statuses(Duration::ZERO).await.is_empty());
+    }
+}
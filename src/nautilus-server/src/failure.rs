@@ -0,0 +1,159 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::common::{
+    to_signed_response, IntentMessage, IntentScope, ProcessDataRequest, ProcessedDataResponse,
+};
+use crate::AppState;
+use crate::{EnclaveError, ErrorCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Serialize;
+use tracing::warn;
+
+pub use nautilus_types::{request_hash, ErrorResponse, FailureAttestation};
+
+/// Error of a process data handler, with a failure attestation if the enclave
+/// signed one for it, see `attest_failure`.
+#[derive(Debug)]
+pub struct ProcessDataError {
+    pub error: EnclaveError,
+    pub attestation: Option<Box<ProcessedDataResponse<IntentMessage<FailureAttestation>>>>,
+}
+
+impl From<EnclaveError> for ProcessDataError {
+    fn from(error: EnclaveError) -> Self {
+        Self {
+            error,
+            attestation: None,
+        }
+    }
+}
+
+impl From<nautilus_types::InvalidRequest> for ProcessDataError {
+    fn from(e: nautilus_types::InvalidRequest) -> Self {
+        EnclaveError::from(e).into()
+    }
+}
+
+/// Same status and body as `EnclaveError`, with the signed attestation if any.
+impl IntoResponse for ProcessDataError {
+    fn into_response(self) -> Response {
+        let code = self.error.code();
+        let body = Json(ErrorResponse {
+            error: self.error.to_string(),
+            code: Some(code.to_string()),
+            attestation: self.attestation.map(|attestation| *attestation),
+        });
+        let mut response = (self.error.status(), body).into_response();
+        response.extensions_mut().insert(ErrorCode(code));
+        response
+    }
+}
+
+/// Sign a failure attestation for a request that was evaluated and found
+/// invalid, i.e. an `EnclaveError::Unprocessable`, if `signed_failures` is
+/// enabled. It carries the error code and the hash of the request payload
+/// under `IntentScope::Failure`, bound to the nonce and caller of the request
+/// like a response. Other errors, which may not be deterministic, are
+/// returned unsigned.
+pub fn attest_failure<T: Serialize>(
+    state: &AppState,
+    request: &ProcessDataRequest<T>,
+    error: EnclaveError,
+) -> ProcessDataError {
+    if !state.config.signed_failures || !matches!(error, EnclaveError::Unprocessable(_)) {
+        return error.into();
+    }
+    let sign = || -> Result<_, EnclaveError> {
+        let binding = request.binding()?;
+        let key = state.signing_key(request.key_epoch)?;
+        let timestamp_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|e| {
+                EnclaveError::InternalError(format!("Failed to get current timestamp: {}", e))
+            })?
+            .as_millis() as u64;
+        let attestation = FailureAttestation {
+            code: error.code().to_string(),
+            request_hash: request_hash(&request.payload),
+        };
+        Ok(to_signed_response(
+            &key,
+            attestation,
+            timestamp_ms,
+            IntentScope::Failure,
+            binding,
+        ))
+    };
+    match sign() {
+        Ok(attestation) => ProcessDataError {
+            error,
+            attestation: Some(Box::new(attestation)),
+        },
+        Err(e) => {
+            warn!("Failed to sign failure attestation: {}", e);
+            error.into()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::signing_payload;
+    use crate::config::ServerConfig;
+    use fastcrypto::ed25519::{Ed25519PublicKey, Ed25519Signature};
+    use fastcrypto::encoding::{Encoding, Hex};
+    use fastcrypto::traits::{ToFromBytes, VerifyingKey};
+
+    fn test_state(signed_failures: bool) -> AppState {
+        AppState {
+            config: ServerConfig {
+                signed_failures,
+                ..ServerConfig::load().unwrap()
+            },
+            ..crate::test_state()
+        }
+    }
+
+    #[test]
+    fn test_attest_failure() {
+        let request = ProcessDataRequest {
+            nonce: Some("0102".to_string()),
+            ..ProcessDataRequest::new("San Francisco".to_string())
+        };
+        let unprocessable = || EnclaveError::Unprocessable("stale".to_string());
+
+        let state = test_state(true);
+        let failure = attest_failure(&state, &request, unprocessable());
+        let attestation = failure.attestation.unwrap();
+        let message = &attestation.response;
+        assert_eq!(message.intent, IntentScope::Failure);
+        assert_eq!(message.data.code, "unprocessable");
+        assert_eq!(message.data.request_hash, request_hash(&request.payload));
+        assert_eq!(attestation.nonce.as_deref(), Some("0102"));
+
+        let binding = request.binding().unwrap();
+        let pk =
+            Ed25519PublicKey::from_bytes(&Hex::decode(&attestation.public_key).unwrap()).unwrap();
+        let signature =
+            Ed25519Signature::from_bytes(&Hex::decode(&attestation.signature).unwrap()).unwrap();
+        pk.verify(
+            &signing_payload(None, message, binding.as_ref()),
+            &signature,
+        )
+        .unwrap();
+
+        // Only deterministic failures are signed, and only if enabled.
+        let bad_request = EnclaveError::BadRequest("invalid".to_string());
+        assert!(attest_failure(&state, &request, bad_request)
+            .attestation
+            .is_none());
+        assert!(
+            attest_failure(&test_state(false), &request, unprocessable())
+                .attestation
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,61 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::metrics::metrics;
+use crate::middleware::{handle_panic, request_context};
+use crate::supervisor::Shutdown;
+use crate::EnclaveError;
+use axum::{middleware, routing::get, Json, Router};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+use tower_http::catch_panic::CatchPanicLayer;
+use tracing::info;
+
+/// Response for the ping endpoint
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PingResponse {
+    pub message: String,
+}
+
+/// Simple ping handler for host-only access
+pub async fn ping() -> Json<PingResponse> {
+    info!("Host init ping received");
+    Json(PingResponse {
+        message: "pong".to_string(),
+    })
+}
+
+/// Serve a separate server on the host-only address (port 3001 by default),
+/// serving the metrics and the host-only routes of the registered apps (e.g.
+/// the Seal bootstrap). It is not reachable from the public port 3000.
+///
+/// Returns once shutdown is requested, or with an error if the server fails.
+/// Run it under the `Supervisor` so that a failure is restarted and reported
+/// by `/health_check`.
+pub async fn serve_host_init_server(
+    addr: SocketAddr,
+    app_routes: Router,
+    shutdown: Shutdown,
+) -> Result<(), EnclaveError> {
+    let host_app = Router::new()
+        .route("/ping", get(ping))
+        .route("/metrics", get(metrics))
+        .merge(app_routes)
+        .layer(CatchPanicLayer::custom(handle_panic))
+        .layer(middleware::from_fn(request_context));
+
+    let host_listener = TcpListener::bind(addr).await.map_err(|e| {
+        EnclaveError::InternalError(format!("Failed to bind host init server: {}", e))
+    })?;
+
+    info!(
+        "Host-only init server listening on {}",
+        host_listener.local_addr().unwrap()
+    );
+
+    axum::serve(host_listener, host_app.into_make_service())
+        .with_graceful_shutdown(shutdown.requested())
+        .await
+        .map_err(|e| EnclaveError::InternalError(format!("Host init server failed: {}", e)))
+}
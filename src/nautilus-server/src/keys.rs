@@ -0,0 +1,493 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::common::{signing_payload, IntentMessage, IntentScope};
+use crate::domain::{CommitteeSeparator, DomainSeparator};
+use crate::metrics::KEY_EPOCH;
+use crate::nsm::Nsm;
+use crate::supervisor::Shutdown;
+use crate::{AppState, EnclaveError};
+use fastcrypto::bls12381::min_sig::BLS12381KeyPair;
+use fastcrypto::ed25519::Ed25519KeyPair;
+use fastcrypto::encoding::{Encoding, Hex};
+use fastcrypto::hash::Keccak256;
+use fastcrypto::secp256k1::Secp256k1KeyPair;
+use fastcrypto::secp256r1::Secp256r1KeyPair;
+use fastcrypto::traits::{KeyPair, RecoverableSigner, Signer, ToFromBytes};
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+pub use nautilus_types::{BlsKey, SignatureScheme};
+
+/// Keypair of one of the supported signature schemes.
+pub enum SigningKey {
+    Ed25519(Ed25519KeyPair),
+    Secp256k1(Secp256k1KeyPair),
+    Secp256r1(Secp256r1KeyPair),
+    Secp256k1Recoverable(Secp256k1KeyPair),
+}
+
+/// Random generator for the keys of an epoch, seeded with the NSM entropy
+/// source mixed with the OS generator, so that neither alone decides the keys.
+fn key_rng(nsm: &dyn Nsm) -> Result<StdRng, EnclaveError> {
+    let entropy = nsm.get_random()?;
+    let mut seed = [0u8; 32];
+    if entropy.len() < seed.len() {
+        return Err(EnclaveError::NsmError(format!(
+            "Expected at least {} random bytes, got {}",
+            seed.len(),
+            entropy.len()
+        )));
+    }
+    rand::thread_rng().fill_bytes(&mut seed);
+    seed.iter_mut().zip(entropy).for_each(|(s, e)| *s ^= e);
+    Ok(StdRng::from_seed(seed))
+}
+
+impl SigningKey {
+    pub fn generate(scheme: SignatureScheme, rng: &mut StdRng) -> Self {
+        match scheme {
+            SignatureScheme::Ed25519 => SigningKey::Ed25519(Ed25519KeyPair::generate(rng)),
+            SignatureScheme::Secp256k1 => SigningKey::Secp256k1(Secp256k1KeyPair::generate(rng)),
+            SignatureScheme::Secp256r1 => SigningKey::Secp256r1(Secp256r1KeyPair::generate(rng)),
+            SignatureScheme::Secp256k1Recoverable => {
+                SigningKey::Secp256k1Recoverable(Secp256k1KeyPair::generate(rng))
+            }
+        }
+    }
+
+    pub fn scheme(&self) -> SignatureScheme {
+        match self {
+            SigningKey::Ed25519(_) => SignatureScheme::Ed25519,
+            SigningKey::Secp256k1(_) => SignatureScheme::Secp256k1,
+            SigningKey::Secp256r1(_) => SignatureScheme::Secp256r1,
+            SigningKey::Secp256k1Recoverable(_) => SignatureScheme::Secp256k1Recoverable,
+        }
+    }
+
+    /// Public key bytes, compressed for the ECDSA schemes.
+    pub fn public_key_bytes(&self) -> Vec<u8> {
+        match self {
+            SigningKey::Ed25519(kp) => kp.public().as_bytes().to_vec(),
+            SigningKey::Secp256k1(kp) => kp.public().as_bytes().to_vec(),
+            SigningKey::Secp256r1(kp) => kp.public().as_bytes().to_vec(),
+            SigningKey::Secp256k1Recoverable(kp) => kp.public().as_bytes().to_vec(),
+        }
+    }
+
+    /// Sign the message, the ECDSA schemes hash it with SHA-256 and return
+    /// the 64 bytes r || s signature, except the recoverable scheme which
+    /// hashes it with Keccak-256 and appends the recovery id.
+    pub fn sign(&self, msg: &[u8]) -> Vec<u8> {
+        match self {
+            SigningKey::Ed25519(kp) => kp.sign(msg).as_ref().to_vec(),
+            SigningKey::Secp256k1(kp) => kp.sign(msg).as_ref().to_vec(),
+            SigningKey::Secp256r1(kp) => kp.sign(msg).as_ref().to_vec(),
+            SigningKey::Secp256k1Recoverable(kp) => kp
+                .sign_recoverable_with_hash::<Keccak256>(msg)
+                .as_ref()
+                .to_vec(),
+        }
+    }
+}
+
+/// Ephemeral keypair of the enclave with the epoch it was generated in. The
+/// first key has epoch 0 and each rotation increments the epoch.
+pub struct EpochKey {
+    pub epoch: u64,
+    pub kp: SigningKey,
+    /// Domain the key signs for, set once its `Enclave` object is known, see
+    /// `domain.rs`.
+    pub domain: OnceLock<DomainSeparator>,
+    /// Committee the BLS key signs for, set along with `domain` if
+    /// `domain.committee_id` is configured.
+    pub committee: OnceLock<CommitteeSeparator>,
+    /// BLS12-381 keypair signing alongside `kp` if `bls_signing` is enabled,
+    /// so that the signatures of replicas can be aggregated, see `aggregator.rs`.
+    pub bls: Option<BLS12381KeyPair>,
+    /// Whether the NSM produced an attestation of the key, see `mark_attested`.
+    attested: AtomicBool,
+}
+
+impl EpochKey {
+    fn generate(
+        scheme: SignatureScheme,
+        epoch: u64,
+        bls: bool,
+        nsm: &dyn Nsm,
+    ) -> Result<Self, EnclaveError> {
+        let mut rng = key_rng(nsm)?;
+        Ok(Self {
+            epoch,
+            kp: SigningKey::generate(scheme, &mut rng),
+            domain: OnceLock::new(),
+            committee: OnceLock::new(),
+            bls: bls.then(|| BLS12381KeyPair::generate(&mut rng)),
+            attested: AtomicBool::new(false),
+        })
+    }
+
+    /// Record that the NSM produced an attestation of the key, which is needed
+    /// to register it onchain or for clients to trust it, see `/readyz`.
+    pub fn mark_attested(&self) {
+        self.attested.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_attested(&self) -> bool {
+        self.attested.load(Ordering::Relaxed)
+    }
+
+    fn status(&self, expires_at_ms: Option<u64>) -> KeyStatus {
+        KeyStatus {
+            epoch: self.epoch,
+            public_key: self.public_key_hex(),
+            enclave_id: self
+                .domain
+                .get()
+                .map(|domain| format!("0x{}", Hex::encode(domain.enclave_id))),
+            bls_public_key: self.bls_public_key_hex(),
+            bls_key_signature: self.bls_key_signature().map(Hex::encode),
+            expires_at_ms,
+        }
+    }
+
+    /// Signature of the key over its BLS12-381 public key, see `BlsKey`, if
+    /// BLS signing is enabled. It carries no domain separator, the `Enclave`
+    /// object of the key is passed along to `bls_committee::add_member`.
+    pub fn bls_key_signature(&self) -> Option<Vec<u8>> {
+        let kp = self.bls.as_ref()?;
+        let intent_msg = IntentMessage::new(
+            BlsKey {
+                public_key: kp.public().as_bytes().to_vec(),
+            },
+            0,
+            IntentScope::BlsKey,
+        );
+        Some(self.kp.sign(&signing_payload(None, &intent_msg, None)))
+    }
+
+    /// Hex encoded public key.
+    pub fn public_key_hex(&self) -> String {
+        Hex::encode(self.kp.public_key_bytes())
+    }
+
+    /// Hex encoded BLS12-381 public key, if BLS signing is enabled.
+    pub fn bls_public_key_hex(&self) -> Option<String> {
+        self.bls
+            .as_ref()
+            .map(|kp| Hex::encode(kp.public().as_bytes()))
+    }
+}
+
+/// Status of a valid key, reported by `/health_check`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyStatus {
+    pub epoch: u64,
+    /// Hex encoded public key.
+    pub public_key: String,
+    /// `Enclave` object registered with the key, if bound.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enclave_id: Option<String>,
+    /// Hex encoded BLS12-381 public key, if BLS signing is enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bls_public_key: Option<String>,
+    /// Hex encoded signature of the key over the BLS12-381 public key, to add
+    /// it to a `bls_committee::Committee`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bls_key_signature: Option<String>,
+    /// Time after which the key can no longer be used, in milliseconds since
+    /// the Unix epoch. None for the current key.
+    pub expires_at_ms: Option<u64>,
+}
+
+struct KeyRing {
+    current: Arc<EpochKey>,
+    /// Key of the previous epoch and the time it expires.
+    previous: Option<(Arc<EpochKey>, SystemTime)>,
+}
+
+/// Ephemeral keys of the enclave. Rotation generates a new current key and
+/// keeps the previous one usable for the overlap window, so that consumers
+/// can register the new key onchain before the previous one stops signing.
+pub struct KeyManager {
+    ring: RwLock<KeyRing>,
+    scheme: SignatureScheme,
+    overlap: Duration,
+    bls: bool,
+    /// Entropy source of the keys.
+    nsm: Arc<dyn Nsm>,
+}
+
+impl KeyManager {
+    /// Generate the key of epoch 0 with `scheme`, previous keys stay valid for
+    /// `overlap` after a rotation. Every key also has a BLS12-381 keypair if
+    /// `bls` is set. Keys are generated with entropy from `nsm`.
+    pub fn new(
+        scheme: SignatureScheme,
+        overlap: Duration,
+        bls: bool,
+        nsm: Arc<dyn Nsm>,
+    ) -> Result<Self, EnclaveError> {
+        let current = EpochKey::generate(scheme, 0, bls, nsm.as_ref())?;
+        KEY_EPOCH.set(0);
+        Ok(Self {
+            ring: RwLock::new(KeyRing {
+                current: Arc::new(current),
+                previous: None,
+            }),
+            scheme,
+            overlap,
+            bls,
+            nsm,
+        })
+    }
+
+    /// Signature scheme of every key.
+    pub fn scheme(&self) -> SignatureScheme {
+        self.scheme
+    }
+
+    /// The current key, used to sign unless a request asks for another epoch.
+    pub fn current(&self) -> Arc<EpochKey> {
+        self.ring.read().expect("key lock poisoned").current.clone()
+    }
+
+    /// The key of `epoch`, or the current key if None. The previous key is
+    /// only returned within its overlap window.
+    pub fn key(&self, epoch: Option<u64>) -> Result<Arc<EpochKey>, EnclaveError> {
+        let ring = self.ring.read().expect("key lock poisoned");
+        match epoch {
+            None => Ok(ring.current.clone()),
+            Some(epoch) if epoch == ring.current.epoch => Ok(ring.current.clone()),
+            Some(epoch) => match &ring.previous {
+                Some((key, expires_at))
+                    if key.epoch == epoch && SystemTime::now() < *expires_at =>
+                {
+                    Ok(key.clone())
+                }
+                _ => Err(EnclaveError::BadRequest(format!(
+                    "Key epoch {} is unknown or expired, current epoch is {}",
+                    epoch, ring.current.epoch
+                ))),
+            },
+        }
+    }
+
+    /// The previous key, only within its overlap window.
+    pub fn previous(&self) -> Option<Arc<EpochKey>> {
+        let ring = self.ring.read().expect("key lock poisoned");
+        ring.previous
+            .as_ref()
+            .filter(|(_, expires_at)| SystemTime::now() < *expires_at)
+            .map(|(key, _)| key.clone())
+    }
+
+    /// Status of the current key and of the previous key if still valid.
+    pub fn statuses(&self) -> Vec<KeyStatus> {
+        let ring = self.ring.read().expect("key lock poisoned");
+        let mut statuses = vec![ring.current.status(None)];
+        if let Some((key, expires_at)) = &ring.previous {
+            if SystemTime::now() < *expires_at {
+                statuses.push(
+                    key.status(
+                        expires_at
+                            .duration_since(UNIX_EPOCH)
+                            .ok()
+                            .map(|d| d.as_millis() as u64),
+                    ),
+                );
+            }
+        }
+        statuses
+    }
+
+    /// Generate the key of the next epoch, the current key becomes the
+    /// previous key until the overlap window ends.
+    pub fn rotate(&self) -> Result<Arc<EpochKey>, EnclaveError> {
+        let mut ring = self.ring.write().expect("key lock poisoned");
+        let next = Arc::new(EpochKey::generate(
+            self.scheme,
+            ring.current.epoch + 1,
+            self.bls,
+            self.nsm.as_ref(),
+        )?);
+        let previous = std::mem::replace(&mut ring.current, next.clone());
+        ring.previous = Some((previous, SystemTime::now() + self.overlap));
+        KEY_EPOCH.set(next.epoch as i64);
+        info!(
+            "rotated ephemeral key to epoch {} with public key {}",
+            next.epoch,
+            next.public_key_hex()
+        );
+        Ok(next)
+    }
+}
+
+/// Rotate the keys every `interval` until shutdown, run under the `Supervisor`.
+pub async fn run_key_rotation(
+    state: Arc<AppState>,
+    interval: Duration,
+    shutdown: Shutdown,
+) -> Result<(), EnclaveError> {
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {
+                state.keys.rotate()?;
+            }
+            _ = shutdown.clone().requested() => return Ok(()),
+        }
+    }
+}
+
+/// Keys generated with the mock NSM, for tests.
+#[cfg(test)]
+pub(crate) fn test_keys(scheme: SignatureScheme, overlap: Duration, bls: bool) -> KeyManager {
+    KeyManager::new(
+        scheme,
+        overlap,
+        bls,
+        Arc::new(crate::nsm::MockNsm::new().unwrap()),
+    )
+    .unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rotation_overlap() {
+        let keys = test_keys(SignatureScheme::Ed25519, Duration::from_secs(60), false);
+        let first = keys.current();
+        assert_eq!(first.epoch, 0);
+
+        let second = keys.rotate().unwrap();
+        assert_eq!(second.epoch, 1);
+        assert_eq!(keys.current().epoch, 1);
+        assert_eq!(keys.key(None).unwrap().epoch, 1);
+        // The previous key is still valid within the overlap window.
+        assert_eq!(
+            keys.key(Some(0)).unwrap().public_key_hex(),
+            first.public_key_hex()
+        );
+        assert_eq!(keys.statuses().len(), 2);
+        assert_eq!(keys.previous().unwrap().epoch, 0);
+        assert!(keys.key(Some(2)).is_err());
+
+        // Only one previous key is kept.
+        keys.rotate().unwrap();
+        assert!(keys.key(Some(0)).is_err());
+        assert_eq!(keys.key(Some(1)).unwrap().epoch, 1);
+    }
+
+    #[test]
+    fn test_rotation_without_overlap() {
+        let keys = test_keys(SignatureScheme::Ed25519, Duration::ZERO, false);
+        keys.rotate().unwrap();
+        assert!(keys.key(Some(0)).is_err());
+        assert!(keys.previous().is_none());
+        assert_eq!(keys.statuses().len(), 1);
+    }
+
+    #[test]
+    fn test_bls_keys() {
+        use fastcrypto::ed25519::{Ed25519PublicKey, Ed25519Signature};
+        use fastcrypto::traits::VerifyingKey;
+
+        let keys = test_keys(SignatureScheme::Ed25519, Duration::from_secs(60), true);
+        let first = keys.current();
+        let second = keys.rotate().unwrap();
+        assert!(first.bls.is_some());
+        assert_ne!(first.bls_public_key_hex(), second.bls_public_key_hex());
+        // 96 bytes public keys in G2 for min_sig.
+        assert_eq!(second.bls_public_key_hex().unwrap().len(), 192);
+        assert!(keys.statuses().iter().all(|s| s.bls_public_key.is_some()));
+        assert!(test_keys(SignatureScheme::Ed25519, Duration::ZERO, false)
+            .current()
+            .bls
+            .is_none());
+
+        let intent_msg = IntentMessage::new(
+            BlsKey {
+                public_key: second.bls.as_ref().unwrap().public().as_bytes().to_vec(),
+            },
+            0,
+            IntentScope::BlsKey,
+        );
+        let pk = Ed25519PublicKey::from_bytes(&second.kp.public_key_bytes()).unwrap();
+        let sig = Ed25519Signature::from_bytes(&second.bls_key_signature().unwrap()).unwrap();
+        assert!(pk
+            .verify(&signing_payload(None, &intent_msg, None), &sig)
+            .is_ok());
+        assert!(test_keys(SignatureScheme::Ed25519, Duration::ZERO, false)
+            .current()
+            .bls_key_signature()
+            .is_none());
+    }
+
+    #[test]
+    fn test_nsm_entropy() {
+        use crate::nsm::NitroNsm;
+
+        // Keys are not generated without the NSM entropy source, here outside
+        // of an enclave.
+        assert!(matches!(
+            KeyManager::new(
+                SignatureScheme::Ed25519,
+                Duration::ZERO,
+                false,
+                Arc::new(NitroNsm)
+            ),
+            Err(EnclaveError::NsmError(_))
+        ));
+    }
+
+    #[test]
+    fn test_signature_schemes() {
+        use fastcrypto::ed25519::Ed25519PublicKey;
+        use fastcrypto::secp256k1::recoverable::Secp256k1RecoverableSignature;
+        use fastcrypto::secp256k1::Secp256k1PublicKey;
+        use fastcrypto::secp256r1::Secp256r1PublicKey;
+        use fastcrypto::traits::{RecoverableSignature, VerifyingKey};
+
+        fn verify<PK: VerifyingKey>(key: &SigningKey, msg: &[u8]) {
+            let pk = PK::from_bytes(&key.public_key_bytes()).unwrap();
+            let sig = <PK::Sig as ToFromBytes>::from_bytes(&key.sign(msg)).unwrap();
+            pk.verify(msg, &sig).unwrap();
+        }
+
+        let msg = b"nautilus";
+        for scheme in [
+            SignatureScheme::Ed25519,
+            SignatureScheme::Secp256k1,
+            SignatureScheme::Secp256r1,
+            SignatureScheme::Secp256k1Recoverable,
+        ] {
+            let keys = test_keys(scheme, Duration::ZERO, false);
+            assert_eq!(keys.rotate().unwrap().kp.scheme(), scheme);
+            let key = keys.current();
+            let sig = key.kp.sign(msg);
+            match scheme {
+                SignatureScheme::Ed25519 => verify::<Ed25519PublicKey>(&key.kp, msg),
+                SignatureScheme::Secp256k1 => verify::<Secp256k1PublicKey>(&key.kp, msg),
+                SignatureScheme::Secp256r1 => verify::<Secp256r1PublicKey>(&key.kp, msg),
+                SignatureScheme::Secp256k1Recoverable => {
+                    // The public key is recovered from the Keccak-256 digest.
+                    assert_eq!(sig.len(), 65);
+                    let recovered = Secp256k1RecoverableSignature::from_bytes(&sig)
+                        .unwrap()
+                        .recover_with_hash::<Keccak256>(msg)
+                        .unwrap();
+                    assert_eq!(recovered.as_bytes(), key.kp.public_key_bytes());
+                    continue;
+                }
+            }
+            assert_eq!(sig.len(), 64);
+        }
+    }
+}
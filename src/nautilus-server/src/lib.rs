@@ -5,11 +5,18 @@ use axum::http::StatusCode;
 use axum::response::IntoResponse;
 use axum::response::Response;
 use axum::Json;
-use fastcrypto::ed25519::Ed25519KeyPair;
+use config::ServerConfig;
+use egress::EgressClient;
+use endpoints::EndpointProber;
+use keys::{EpochKey, KeyManager};
+use measurement::ConfigMeasurement;
+use nsm::Nsm;
 use serde_json::json;
 use std::fmt;
+use std::sync::Arc;
+use supervisor::Supervisor;
 
-mod apps {
+pub mod apps {
     #[cfg(feature = "twitter-example")]
     #[path = "twitter-example/mod.rs"]
     pub mod twitter_example;
@@ -21,61 +28,302 @@ mod apps {
     #[cfg(feature = "seal-example")]
     #[path = "seal-example/mod.rs"]
     pub mod seal_example;
-    
+
     #[cfg(feature = "random-example")]
     #[path = "random-example/mod.rs"]
     pub mod random_example;
 }
 
-pub mod app {
-    #[cfg(feature = "twitter-example")]
-    pub use crate::apps::twitter_example::*;
-
-    #[cfg(feature = "weather-example")]
-    pub use crate::apps::weather_example::*;
-
-    #[cfg(feature = "seal-example")]
-    pub use crate::apps::seal_example::*;
-
-    #[cfg(feature = "random-example")]
-    pub use crate::apps::random_example::*;
-}
-
+pub mod aggregator;
+pub mod batch;
 pub mod common;
+pub mod config;
+pub mod domain;
+pub mod egress;
+pub mod endpoints;
+pub mod failure;
+pub mod host;
+pub mod keys;
+pub mod logging;
+pub mod measurement;
+pub mod merkle;
+pub mod metrics;
+pub mod middleware;
+pub mod nsm;
+pub mod readiness;
+pub mod registry;
+pub mod supervisor;
 
-/// App state, at minimum needs to maintain the ephemeral keypair.  
+/// Enclave-wide state shared by all apps, at minimum needs to maintain the
+/// ephemeral keys. App specific state lives in the app, see `registry::EnclaveApp`.
 pub struct AppState {
-    /// Ephemeral keypair on boot
-    pub eph_kp: Ed25519KeyPair,
-    /// API key when querying api.weatherapi.com
-    pub api_key: String,
+    /// Ephemeral keys, generated on boot and rotated by epoch if configured
+    pub keys: KeyManager,
+    /// Server configuration validated on boot
+    pub config: ServerConfig,
+    /// Supervisor of background tasks, whose status is reported by health checks
+    pub supervisor: Arc<Supervisor>,
+    /// Names of the registered apps, bound into attestations
+    pub apps: Vec<&'static str>,
+    /// Nitro Secure Module, or a mock of it outside of an enclave
+    pub nsm: Arc<dyn Nsm>,
+    /// Runtime configuration measured into the config PCR on boot
+    pub config_measurement: ConfigMeasurement,
+    /// Allowed endpoints of the apps, probed by health checks
+    pub endpoints: EndpointProber,
+    /// HTTP client for outbound calls, restricted to the allowed endpoints
+    pub egress: EgressClient,
+}
+
+impl AppState {
+    /// Key to sign a response with, the current key if `epoch` is None. If a
+    /// domain is configured, the key must be bound to its `Enclave` object.
+    /// Until the current key of a rotation is bound, requests without an epoch
+    /// are signed by the previous key while it is bound and within its overlap
+    /// window, so that a rotation does not take the enclave out of service.
+    pub fn signing_key(&self, epoch: Option<u64>) -> Result<Arc<EpochKey>, EnclaveError> {
+        let key = self.keys.key(epoch)?;
+        if self.config.domain.is_none() || key.domain.get().is_some() {
+            return Ok(key);
+        }
+        if epoch.is_none() {
+            if let Some(previous) = self.keys.previous().filter(|k| k.domain.get().is_some()) {
+                return Ok(previous);
+            }
+        }
+        Err(EnclaveError::NotReady(format!(
+            "Enclave id of key epoch {} is not set, call /set_enclave_id once registered",
+            key.epoch
+        )))
+    }
 }
 
-/// Implement IntoResponse for EnclaveError.
+/// Error code of a failed request, attached to the response extensions so
+/// middlewares can log it.
+#[derive(Debug, Clone, Copy)]
+pub struct ErrorCode(pub &'static str);
+
+/// Implement IntoResponse for EnclaveError. The body carries the stable
+/// machine-readable `code` next to the human-readable message.
 impl IntoResponse for EnclaveError {
     fn into_response(self) -> Response {
-        let (status, error_message) = match self {
-            EnclaveError::GenericError(e) => (StatusCode::BAD_REQUEST, e),
-        };
         let body = Json(json!({
-            "error": error_message,
+            "error": self.to_string(),
+            "code": self.code(),
         }));
-        (status, body).into_response()
+        let mut response = (self.status(), body).into_response();
+        response.extensions_mut().insert(ErrorCode(self.code()));
+        response
     }
 }
 
 /// Enclave errors enum.
 #[derive(Debug)]
 pub enum EnclaveError {
-    GenericError(String),
+    /// Malformed request, e.g. an unparsable URL or an invalid range.
+    BadRequest(String),
+    /// Well-formed request that was evaluated and found invalid,
+    /// e.g. a tweet without the expected tag or stale upstream data.
+    Unprocessable(String),
+    /// The app is not bootstrapped yet, e.g. Seal secrets not loaded.
+    NotReady(String),
+    /// An upstream endpoint failed or returned an unexpected response.
+    UpstreamError(String),
+    /// An upstream endpoint did not respond in time.
+    UpstreamTimeout(String),
+    /// The handler did not complete within the configured handler timeout,
+    /// whatever it was waiting on.
+    HandlerTimeout(String),
+    /// Internal fault of the enclave server.
+    InternalError(String),
+    /// The Nitro Secure Module failed or returned an unexpected response.
+    NsmError(String),
+}
+
+impl EnclaveError {
+    /// Stable machine-readable error code, safe to match on for retries and alerting.
+    pub fn code(&self) -> &'static str {
+        match self {
+            EnclaveError::BadRequest(_) => "bad_request",
+            EnclaveError::Unprocessable(_) => "unprocessable",
+            EnclaveError::NotReady(_) => "not_ready",
+            EnclaveError::UpstreamError(_) => "upstream_error",
+            EnclaveError::UpstreamTimeout(_) => "upstream_timeout",
+            EnclaveError::HandlerTimeout(_) => "handler_timeout",
+            EnclaveError::InternalError(_) => "internal_error",
+            EnclaveError::NsmError(_) => "nsm_error",
+        }
+    }
+
+    /// HTTP status code the error is returned with.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            EnclaveError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            EnclaveError::Unprocessable(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            EnclaveError::NotReady(_) | EnclaveError::HandlerTimeout(_) => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            EnclaveError::UpstreamError(_) => StatusCode::BAD_GATEWAY,
+            EnclaveError::UpstreamTimeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            EnclaveError::InternalError(_) | EnclaveError::NsmError(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
+    /// Map a failed call to an upstream endpoint, distinguishing timeouts.
+    pub fn upstream(context: &str, e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            EnclaveError::UpstreamTimeout(format!("{}: {}", context, e))
+        } else {
+            EnclaveError::UpstreamError(format!("{}: {}", context, e))
+        }
+    }
 }
 
 impl fmt::Display for EnclaveError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            EnclaveError::GenericError(e) => write!(f, "{}", e),
+            EnclaveError::BadRequest(e)
+            | EnclaveError::Unprocessable(e)
+            | EnclaveError::NotReady(e)
+            | EnclaveError::UpstreamError(e)
+            | EnclaveError::UpstreamTimeout(e)
+            | EnclaveError::HandlerTimeout(e)
+            | EnclaveError::InternalError(e)
+            | EnclaveError::NsmError(e) => write!(f, "{}", e),
         }
     }
 }
 
 impl std::error::Error for EnclaveError {}
+
+impl From<nautilus_types::InvalidRequest> for EnclaveError {
+    fn from(e: nautilus_types::InvalidRequest) -> Self {
+        EnclaveError::BadRequest(e.0)
+    }
+}
+
+impl From<nautilus_endpoints::InvalidEndpoints> for EnclaveError {
+    fn from(e: nautilus_endpoints::InvalidEndpoints) -> Self {
+        EnclaveError::InternalError(e.0)
+    }
+}
+
+/// State for tests, with the mock NSM, the baked server config and an egress
+/// client that refuses every call. Tests override fields with the struct
+/// update syntax, e.g. `AppState { egress, ..test_state() }`.
+#[cfg(test)]
+pub(crate) fn test_state() -> AppState {
+    let nsm: Arc<dyn Nsm> = Arc::new(nsm::MockNsm::new().unwrap());
+    AppState {
+        keys: KeyManager::new(
+            Default::default(),
+            std::time::Duration::ZERO,
+            false,
+            nsm.clone(),
+        )
+        .unwrap(),
+        config: ServerConfig::load().unwrap(),
+        supervisor: Default::default(),
+        apps: vec!["test"],
+        nsm,
+        config_measurement: Default::default(),
+        endpoints: Default::default(),
+        egress: Default::default(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_error_status_and_code() {
+        let cases = [
+            (EnclaveError::BadRequest("".to_string()), 400, "bad_request"),
+            (
+                EnclaveError::Unprocessable("".to_string()),
+                422,
+                "unprocessable",
+            ),
+            (EnclaveError::NotReady("".to_string()), 503, "not_ready"),
+            (
+                EnclaveError::UpstreamError("".to_string()),
+                502,
+                "upstream_error",
+            ),
+            (
+                EnclaveError::UpstreamTimeout("".to_string()),
+                504,
+                "upstream_timeout",
+            ),
+            (
+                EnclaveError::HandlerTimeout("".to_string()),
+                503,
+                "handler_timeout",
+            ),
+            (
+                EnclaveError::InternalError("".to_string()),
+                500,
+                "internal_error",
+            ),
+            (EnclaveError::NsmError("".to_string()), 500, "nsm_error"),
+        ];
+        for (error, status, code) in cases {
+            assert_eq!(error.status().as_u16(), status);
+            assert_eq!(error.code(), code);
+        }
+    }
+
+    #[test]
+    fn test_signing_key_rotation_with_domain() {
+        use crate::domain::DomainConfig;
+        use crate::keys::{test_keys, SignatureScheme};
+        use std::time::Duration;
+
+        let domain = DomainConfig {
+            chain_id: "4c78adac".to_string(),
+            package_id: format!("0x{}02", "00".repeat(31)),
+            committee_id: None,
+        };
+        let state = AppState {
+            keys: test_keys(SignatureScheme::Ed25519, Duration::from_secs(60), false),
+            config: ServerConfig {
+                domain: Some(domain.clone()),
+                ..ServerConfig::load().unwrap()
+            },
+            ..test_state()
+        };
+        let bind = |key: &EpochKey, id: u8| {
+            key.domain.set(domain.separator([id; 32]).unwrap()).unwrap();
+        };
+
+        // Not ready until the first key is bound.
+        assert!(matches!(
+            state.signing_key(None),
+            Err(EnclaveError::NotReady(_))
+        ));
+        bind(&state.keys.current(), 1);
+        assert_eq!(state.signing_key(None).unwrap().epoch, 0);
+
+        // The previous key keeps signing until the new key is bound.
+        let next = state.keys.rotate().unwrap();
+        assert_eq!(state.signing_key(None).unwrap().epoch, 0);
+        assert!(matches!(
+            state.signing_key(Some(1)),
+            Err(EnclaveError::NotReady(_))
+        ));
+        bind(&next, 2);
+        assert_eq!(state.signing_key(None).unwrap().epoch, 1);
+        assert_eq!(state.signing_key(Some(0)).unwrap().epoch, 0);
+
+        // Not ready again when neither the current nor the previous key is bound.
+        state.keys.rotate().unwrap();
+        state.keys.rotate().unwrap();
+        assert!(matches!(
+            state.signing_key(None),
+            Err(EnclaveError::NotReady(_))
+        ));
+    }
+}
@@ -0,0 +1,17 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use tracing_subscriber::EnvFilter;
+
+/// Install the global tracing subscriber, emitting one JSON object per line
+/// with the fields of the current request span. The level is taken from
+/// `RUST_LOG` and defaults to `info`.
+pub fn init_tracing() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    tracing_subscriber::fmt()
+        .json()
+        .with_env_filter(filter)
+        .with_current_span(true)
+        .with_span_list(false)
+        .init();
+}
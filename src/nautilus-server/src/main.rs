@@ -2,54 +2,155 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use anyhow::Result;
-use axum::{routing::get, routing::post, Router};
-use fastcrypto::{ed25519::Ed25519KeyPair, traits::KeyPair};
-use nautilus_server::app::process_data;
+use axum::extract::DefaultBodyLimit;
+use axum::http::{header, HeaderName, Method};
+use axum::routing::{get, post};
+use axum::{middleware, Router};
+use fastcrypto::encoding::{Encoding, Hex};
 use nautilus_server::common::{get_attestation, health_check};
+use nautilus_server::config::ServerConfig;
+use nautilus_server::domain::set_enclave_id;
+use nautilus_server::egress::EgressClient;
+use nautilus_server::endpoints::{self, EndpointProber};
+use nautilus_server::host::serve_host_init_server;
+use nautilus_server::keys::{run_key_rotation, KeyManager};
+use nautilus_server::logging::init_tracing;
+use nautilus_server::measurement::ConfigMeasurement;
+use nautilus_server::middleware::{
+    handle_panic, handler_timeout, request_context, REQUEST_ID_HEADER,
+};
+use nautilus_server::nsm;
+use nautilus_server::readiness;
+use nautilus_server::registry::enabled_apps;
+use nautilus_server::supervisor::{Shutdown, Supervisor};
 use nautilus_server::AppState;
 use std::sync::Arc;
-use tower_http::cors::{Any, CorsLayer};
-use tracing::info;
+use std::time::Duration;
+use tower_http::catch_panic::CatchPanicLayer;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use tracing::{info, warn};
+
+/// Time left to background tasks to stop once the public server is drained.
+const TASK_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let eph_kp = Ed25519KeyPair::generate(&mut rand::thread_rng());
+    init_tracing();
 
-    // This API_KEY value can be stored with secret-manager. To do that, follow the prompt `sh configure_enclave.sh`
-    // Answer `y` to `Do you want to use a secret?` and finish. Otherwise, uncomment this code to use a hardcoded value.
-    // let api_key = "045a27812dbe456392913223221306".to_string();
-    #[cfg(not(feature = "seal-example"))]
-    let api_key = std::env::var("API_KEY").expect("API_KEY must be set");
+    // Fail before serving anything if the baked configuration is invalid.
+    let config = ServerConfig::load()?;
+    info!(
+        "loaded server config with hash {}",
+        Hex::encode(config.hash)
+    );
 
-    // NOTE: if built with `seal-example` flag the `process_data` does not use this api_key from AppState, instead
-    // it uses SEAL_API_KEY initialized with two phase bootstrap. Modify this as needed for your application.
-    #[cfg(feature = "seal-example")]
-    let api_key = String::new();
+    // Apps are enabled with cargo features, e.g. `--features weather-example,random-example`.
+    // Each app loads its own configuration (e.g. WEATHER_API_KEY) when it is registered.
+    let registry = enabled_apps()?;
+    if registry.is_empty() {
+        anyhow::bail!("No app enabled, build with at least one app feature e.g. weather-example");
+    }
 
-    let state = Arc::new(AppState { eph_kp, api_key });
+    // NSM backend, the mock is selected with NSM_BACKEND=mock and the mock-nsm feature.
+    let nsm = nsm::from_env()?;
 
-    // Spawn host-only init server if seal-example feature is enabled
-    #[cfg(feature = "seal-example")]
-    {
-        nautilus_server::app::spawn_host_init_server(state.clone()).await?;
+    // Measure the runtime configuration into PCR16 and lock it before serving,
+    // so that attestations prove the configuration the enclave runs with.
+    let config_measurement = ConfigMeasurement::collect(&config, &registry);
+    config_measurement.extend_and_lock(nsm.as_ref())?;
+
+    // Allowed endpoints of all apps, the only hosts the egress client calls.
+    // They are probed by /health_check.
+    let allowed_endpoints = endpoints::collect(&registry)?;
+    let egress = EgressClient::new(&allowed_endpoints, &config.egress)?;
+    let endpoints = EndpointProber::new(allowed_endpoints, egress.clone());
+
+    // Ephemeral keys, generated with entropy from the NSM.
+    let keys = KeyManager::new(
+        config.signature_scheme,
+        config.key_overlap(),
+        config.bls_signing,
+        nsm.clone(),
+    )?;
+    let supervisor = Arc::new(Supervisor::default());
+    let state = Arc::new(AppState {
+        keys,
+        config: config.clone(),
+        supervisor: supervisor.clone(),
+        apps: registry.apps().iter().map(|app| app.name()).collect(),
+        nsm,
+        config_measurement,
+        endpoints,
+        egress,
+    });
+
+    // SIGTERM drains in-flight requests of both servers before exiting.
+    let shutdown = Shutdown::on_signal();
+
+    // Supervise the host-only server for metrics and the host-only routes of
+    // apps, it is restarted if it fails and its status is reported by /health_check.
+    let host_routes = Router::new()
+        .route("/set_enclave_id", post(set_enclave_id))
+        .with_state(state.clone())
+        .merge(registry.host_router(state.clone()).unwrap_or_default());
+    let host_addr = config.host_listen_addr;
+    let host_task = supervisor.spawn("host_init_server", shutdown.clone(), {
+        let shutdown = shutdown.clone();
+        move || serve_host_init_server(host_addr, host_routes.clone(), shutdown.clone())
+    });
+
+    // Rotate the ephemeral key by epoch if configured, see `keys.rs`.
+    if let Some(rotation) = config.key_rotation.clone() {
+        let state = state.clone();
+        supervisor.spawn("key_rotation", shutdown.clone(), {
+            let shutdown = shutdown.clone();
+            move || run_key_rotation(state.clone(), rotation.interval(), shutdown.clone())
+        });
     }
 
-    // Define your own restricted CORS policy here if needed.
-    let cors = CorsLayer::new().allow_methods(Any).allow_headers(Any);
+    // CORS policy from the server config, see `server_config.yaml`.
+    let allow_origin = if config.cors.allows_any_origin() {
+        AllowOrigin::from(Any)
+    } else {
+        AllowOrigin::list(config.cors.origin_headers())
+    };
+    let cors = CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::POST])
+        .allow_headers([header::CONTENT_TYPE])
+        .expose_headers([HeaderName::from_static(REQUEST_ID_HEADER)]);
 
     let app = Router::new()
         .route("/", get(ping))
         .route("/get_attestation", get(get_attestation))
-        .route("/process_data", post(process_data))
         .route("/health_check", get(health_check))
-        .with_state(state)
-        .layer(cors);
+        .with_state(state.clone())
+        .merge(readiness::router(state.clone(), &registry))
+        .merge(registry.router(state))
+        .layer(middleware::from_fn_with_state(
+            config.handler_timeout(),
+            handler_timeout,
+        ))
+        .layer(CatchPanicLayer::custom(handle_panic))
+        .layer(DefaultBodyLimit::max(config.max_body_bytes))
+        .layer(cors)
+        .layer(middleware::from_fn(request_context));
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await?;
+    let listener = tokio::net::TcpListener::bind(config.listen_addr).await?;
     info!("listening on {}", listener.local_addr().unwrap());
     axum::serve(listener, app.into_make_service())
+        .with_graceful_shutdown(shutdown.clone().requested())
+        .await
+        .map_err(|e| anyhow::anyhow!("Server error: {}", e))?;
+
+    if tokio::time::timeout(TASK_SHUTDOWN_TIMEOUT, host_task)
         .await
-        .map_err(|e| anyhow::anyhow!("Server error: {}", e))
+        .is_err()
+    {
+        warn!("host init server did not stop in time");
+    }
+    info!("shutdown complete");
+    Ok(())
 }
 
 async fn ping() -> &'static str {
@@ -0,0 +1,130 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::config::ServerConfig;
+use crate::nsm::{Nsm, NSM_BACKEND_ENV};
+use crate::registry::AppRegistry;
+use crate::EnclaveError;
+use fastcrypto::encoding::{Encoding, Hex};
+use fastcrypto::hash::{HashFunction, Sha256};
+use std::collections::BTreeMap;
+use tracing::info;
+
+/// PCR extended with the runtime configuration digest, the first PCR that
+/// the NSM leaves to the enclave.
+pub const CONFIG_PCR: u16 = 16;
+
+/// Runtime configuration of the enclave that is not covered by PCR0-2, i.e. the
+/// env vars set from the values pushed by the host and the configuration apps
+/// load after boot. Files baked into the binary such as `server_config.yaml` and
+/// `allowed_endpoints.yaml` are already covered by PCR2 and left out. Its digest
+/// is extended into `CONFIG_PCR`, so attestations prove which configuration the
+/// enclave runs with. The entries are published by `/health_check`, so that a
+/// verifier can recompute the digest.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigMeasurement {
+    /// Measured inputs by name, e.g. `env/RUST_LOG` or `apps/<app>/<name>`.
+    /// Env vars that are not set are omitted.
+    pub entries: BTreeMap<String, Vec<u8>>,
+    /// Sha256 hash of the BCS encoded entries.
+    pub digest: [u8; 32],
+}
+
+impl ConfigMeasurement {
+    /// Collect the non-secret env vars listed in `measured_env` and the
+    /// measured runtime configuration of every app.
+    pub fn collect(config: &ServerConfig, registry: &AppRegistry) -> Self {
+        let mut entries = BTreeMap::new();
+        for name in config
+            .measured_env
+            .iter()
+            .map(String::as_str)
+            .chain([NSM_BACKEND_ENV])
+        {
+            if let Ok(value) = std::env::var(name) {
+                entries.insert(format!("env/{}", name), value.into_bytes());
+            }
+        }
+        for app in registry.apps() {
+            for (key, value) in app.measured_config() {
+                entries.insert(format!("apps/{}/{}", app.name(), key), value);
+            }
+        }
+        Self::from_entries(entries)
+    }
+
+    pub fn from_entries(entries: BTreeMap<String, Vec<u8>>) -> Self {
+        let encoded = bcs::to_bytes(&entries).expect("should not fail");
+        let digest = Sha256::digest(encoded).digest;
+        Self { entries, digest }
+    }
+
+    /// Hex encoded entries, as published by `/health_check`.
+    pub fn hex_entries(&self) -> BTreeMap<String, String> {
+        self.entries
+            .iter()
+            .map(|(name, value)| (name.clone(), Hex::encode(value)))
+            .collect()
+    }
+
+    /// Extend `CONFIG_PCR` with the digest and lock it so that nothing else can
+    /// be measured into it, returning the final PCR value.
+    pub fn extend_and_lock(&self, nsm: &dyn Nsm) -> Result<Vec<u8>, EnclaveError> {
+        let pcr = nsm.describe_pcr(CONFIG_PCR)?;
+        if pcr.locked {
+            return Err(EnclaveError::NsmError(format!(
+                "PCR{} is already locked",
+                CONFIG_PCR
+            )));
+        }
+        nsm.extend_pcr(CONFIG_PCR, &self.digest)?;
+        nsm.lock_pcr(CONFIG_PCR)?;
+        let value = nsm.describe_pcr(CONFIG_PCR)?.data;
+        info!(
+            "measured {:?} into PCR{}: digest {}, PCR value {}",
+            self.entries.keys().collect::<Vec<_>>(),
+            CONFIG_PCR,
+            Hex::encode(self.digest),
+            Hex::encode(&value)
+        );
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_digest() {
+        // Same vector as `config_digest` of nautilus-verifier.
+        let measurement = ConfigMeasurement::from_entries(BTreeMap::from([(
+            "env/RUST_LOG".to_string(),
+            b"info".to_vec(),
+        )]));
+        assert_eq!(
+            Hex::encode(measurement.digest),
+            "f905ccaf77d59f5d9424b8a579d3c6dbb005e46af1c5321650eb517684b37089"
+        );
+        assert_eq!(
+            measurement.hex_entries(),
+            BTreeMap::from([("env/RUST_LOG".to_string(), "696e666f".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_extend_and_lock() {
+        use crate::nsm::MockNsm;
+
+        let nsm = MockNsm::new().unwrap();
+        let measurement = ConfigMeasurement::from_entries(BTreeMap::from([(
+            "env/RUST_LOG".to_string(),
+            b"info".to_vec(),
+        )]));
+        let value = measurement.extend_and_lock(&nsm).unwrap();
+        assert_ne!(value, vec![0; 48]);
+        assert!(nsm.describe_pcr(CONFIG_PCR).unwrap().locked);
+        // A second measurement cannot be added once locked.
+        assert!(measurement.extend_and_lock(&nsm).is_err());
+    }
+}
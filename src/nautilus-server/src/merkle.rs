@@ -0,0 +1,230 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Binary Merkle tree over batch results, matching `move/enclave/sources/merkle.move`.
+//! Leaves are hashed as `H(0x00 || leaf)` and nodes as `H(0x01 || left || right)`.
+//! A node without a sibling is promoted to the next level unchanged, so a proof
+//! is verified from the leaf index and the leaf count.
+
+use fastcrypto::hash::{Blake2b256, HashFunction, Sha256};
+use serde::{Deserialize, Serialize};
+
+const LEAF_PREFIX: u8 = 0;
+const NODE_PREFIX: u8 = 1;
+
+/// Hash function of the tree, both are available in Move.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MerkleHash {
+    /// `std::hash::sha2_256` in Move.
+    #[default]
+    Sha256,
+    /// `sui::hash::blake2b256` in Move.
+    Blake2b256,
+}
+
+impl MerkleHash {
+    /// Identifier of the hash function signed in the batch root.
+    pub fn id(self) -> u8 {
+        match self {
+            MerkleHash::Sha256 => 0,
+            MerkleHash::Blake2b256 => 1,
+        }
+    }
+
+    fn digest(self, prefix: u8, parts: &[&[u8]]) -> [u8; 32] {
+        fn digest<H: HashFunction<32>>(prefix: u8, parts: &[&[u8]]) -> [u8; 32] {
+            let mut hasher = H::default();
+            hasher.update([prefix]);
+            parts.iter().for_each(|part| hasher.update(part));
+            hasher.finalize().digest
+        }
+        match self {
+            MerkleHash::Sha256 => digest::<Sha256>(prefix, parts),
+            MerkleHash::Blake2b256 => digest::<Blake2b256>(prefix, parts),
+        }
+    }
+
+    pub fn leaf(self, leaf: &[u8]) -> [u8; 32] {
+        self.digest(LEAF_PREFIX, &[leaf])
+    }
+
+    pub fn node(self, left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+        self.digest(NODE_PREFIX, &[left.as_slice(), right.as_slice()])
+    }
+}
+
+/// Merkle tree with every level kept to build proofs, from the leaves up to the root.
+pub struct MerkleTree {
+    levels: Vec<Vec<[u8; 32]>>,
+}
+
+impl MerkleTree {
+    /// Build the tree over the leaves, which must not be empty.
+    pub fn new<L: AsRef<[u8]>>(hash: MerkleHash, leaves: &[L]) -> Self {
+        assert!(!leaves.is_empty(), "Merkle tree needs at least one leaf");
+        let mut levels = vec![leaves
+            .iter()
+            .map(|leaf| hash.leaf(leaf.as_ref()))
+            .collect::<Vec<_>>()];
+        while levels.last().expect("not empty").len() > 1 {
+            let next = levels
+                .last()
+                .expect("not empty")
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => hash.node(left, right),
+                    [single] => *single,
+                    _ => unreachable!("chunks of at most 2"),
+                })
+                .collect();
+            levels.push(next);
+        }
+        Self { levels }
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels[0].is_empty()
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.levels.last().expect("not empty")[0]
+    }
+
+    /// Siblings of the leaf at `index` from the bottom up, skipping the levels
+    /// where the node is promoted.
+    pub fn proof(&self, index: usize) -> Vec<[u8; 32]> {
+        let mut siblings = Vec::new();
+        let mut index = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            if let Some(sibling) = level.get(index ^ 1) {
+                siblings.push(*sibling);
+            }
+            index /= 2;
+        }
+        siblings
+    }
+}
+
+/// Verify that `leaf` is at `index` of a tree of `count` leaves with `root`.
+pub fn verify_proof(
+    hash: MerkleHash,
+    root: &[u8; 32],
+    leaf: &[u8],
+    index: u64,
+    count: u64,
+    siblings: &[[u8; 32]],
+) -> bool {
+    if index >= count {
+        return false;
+    }
+    let mut node = hash.leaf(leaf);
+    let mut siblings = siblings.iter();
+    let (mut index, mut width) = (index, count);
+    while width > 1 {
+        if index % 2 == 1 {
+            match siblings.next() {
+                Some(sibling) => node = hash.node(sibling, &node),
+                None => return false,
+            }
+        } else if index + 1 < width {
+            match siblings.next() {
+                Some(sibling) => node = hash.node(&node, sibling),
+                None => return false,
+            }
+        }
+        index /= 2;
+        width = width.div_ceil(2);
+    }
+    siblings.next().is_none() && node == *root
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fastcrypto::encoding::{Encoding, Hex};
+
+    #[test]
+    fn test_merkle_tree() {
+        // test result should be consistent with test_merkle in `move/enclave/sources/merkle.move`.
+        let leaves: [&[u8]; 3] = [b"a", b"b", b"c"];
+        for (hash, root) in [
+            (
+                MerkleHash::Sha256,
+                "36642e73c2540ab121e3a6bf9545b0a24982cd830eb13d3cd19de3ce6c021ec1",
+            ),
+            (
+                MerkleHash::Blake2b256,
+                "17321db51c1ef3ec1f77e271aa300b4e5c6091708bcba37e46025774a26142ee",
+            ),
+        ] {
+            let tree = MerkleTree::new(hash, &leaves);
+            assert_eq!(Hex::encode(tree.root()), root);
+            for (index, leaf) in leaves.iter().enumerate() {
+                let proof = tree.proof(index);
+                assert!(verify_proof(
+                    hash,
+                    &tree.root(),
+                    leaf,
+                    index as u64,
+                    3,
+                    &proof
+                ));
+                assert!(!verify_proof(
+                    hash,
+                    &tree.root(),
+                    b"d",
+                    index as u64,
+                    3,
+                    &proof
+                ));
+                assert!(!verify_proof(
+                    hash,
+                    &tree.root(),
+                    leaf,
+                    index as u64,
+                    4,
+                    &proof
+                ));
+            }
+            // The last leaf is promoted at the first level.
+            assert_eq!(
+                tree.proof(2),
+                vec![hash.node(&hash.leaf(b"a"), &hash.leaf(b"b"))]
+            );
+        }
+
+        let single = MerkleTree::new(MerkleHash::Sha256, &[b"a"]);
+        assert_eq!(
+            Hex::encode(single.root()),
+            "022a6979e6dab7aa5ae4c3e5e45f7e977112a7e63593820dbec1ec738a24f93c"
+        );
+        assert!(single.proof(0).is_empty());
+        assert!(!verify_proof(
+            MerkleHash::Sha256,
+            &single.root(),
+            b"a",
+            1,
+            1,
+            &[]
+        ));
+
+        // Every leaf of a larger tree with an odd level verifies.
+        let leaves: Vec<Vec<u8>> = (0..11u8).map(|i| vec![i]).collect();
+        let tree = MerkleTree::new(MerkleHash::Blake2b256, &leaves);
+        for (index, leaf) in leaves.iter().enumerate() {
+            assert!(verify_proof(
+                MerkleHash::Blake2b256,
+                &tree.root(),
+                leaf,
+                index as u64,
+                11,
+                &tree.proof(index)
+            ));
+        }
+    }
+}
@@ -0,0 +1,117 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::common::IntentScope;
+use axum::http::header;
+use axum::response::IntoResponse;
+use prometheus::{
+    register_histogram_vec, register_int_counter, register_int_counter_vec, register_int_gauge,
+    register_int_gauge_vec, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+    TextEncoder,
+};
+
+lazy_static::lazy_static! {
+    /// Signatures issued by the enclave per intent scope.
+    pub static ref SIGNATURES_ISSUED: IntCounterVec = register_int_counter_vec!(
+        "nautilus_signatures_issued_total",
+        "Signatures issued by the enclave per intent scope",
+        &["scope"]
+    )
+    .unwrap();
+
+    /// Latency of handled requests per route and status.
+    pub static ref REQUEST_LATENCY: HistogramVec = register_histogram_vec!(
+        "nautilus_request_latency_seconds",
+        "Latency of handled requests per route and status",
+        &["route", "status"]
+    )
+    .unwrap();
+
+    /// Outcome of calls to allowed endpoints.
+    pub static ref UPSTREAM_REQUESTS: IntCounterVec = register_int_counter_vec!(
+        "nautilus_upstream_requests_total",
+        "Outcome of calls to allowed endpoints",
+        &["endpoint", "outcome"]
+    )
+    .unwrap();
+
+    /// Latency of calls to allowed endpoints.
+    pub static ref UPSTREAM_LATENCY: HistogramVec = register_histogram_vec!(
+        "nautilus_upstream_latency_seconds",
+        "Latency of calls to allowed endpoints",
+        &["endpoint"]
+    )
+    .unwrap();
+
+    /// Attestation requests sent to the NSM.
+    pub static ref NSM_ATTESTATIONS: IntCounter = register_int_counter!(
+        "nautilus_nsm_attestations_total",
+        "Attestation requests sent to the NSM"
+    )
+    .unwrap();
+
+    /// Attestation requests that the NSM failed.
+    pub static ref NSM_ATTESTATION_FAILURES: IntCounter = register_int_counter!(
+        "nautilus_nsm_attestation_failures_total",
+        "Attestation requests that the NSM failed"
+    )
+    .unwrap();
+
+    /// Bootstrap state per app, e.g. for the Seal example 0 is not started,
+    /// 1 is init_parameter_load done and 2 is complete_parameter_load done.
+    pub static ref BOOTSTRAP_STATE: IntGaugeVec = register_int_gauge_vec!(
+        "nautilus_bootstrap_state",
+        "Bootstrap state per app",
+        &["app"]
+    )
+    .unwrap();
+
+    /// Restarts of supervised background tasks, e.g. the host-only server.
+    pub static ref TASK_RESTARTS: IntCounterVec = register_int_counter_vec!(
+        "nautilus_task_restarts_total",
+        "Restarts of supervised background tasks",
+        &["task"]
+    )
+    .unwrap();
+
+    /// Epoch of the current ephemeral key, incremented by each rotation.
+    pub static ref KEY_EPOCH: IntGauge = register_int_gauge!(
+        "nautilus_key_epoch",
+        "Epoch of the current ephemeral key"
+    )
+    .unwrap();
+}
+
+/// Count a signature issued under the given intent scope.
+pub fn observe_signature(intent: &IntentScope) {
+    SIGNATURES_ISSUED
+        .with_label_values(&[&format!("{:?}", intent)])
+        .inc();
+}
+
+/// Count the outcome of a call to an allowed endpoint.
+pub fn observe_upstream(endpoint: &str, result: &Result<reqwest::Response, reqwest::Error>) {
+    let outcome = match result {
+        Ok(response) if response.status().is_success() => "success",
+        Ok(_) => "http_error",
+        Err(e) if e.is_timeout() => "timeout",
+        Err(_) => "error",
+    };
+    UPSTREAM_REQUESTS
+        .with_label_values(&[endpoint, outcome])
+        .inc();
+}
+
+/// Count an outbound call refused by the egress allowlist.
+pub fn observe_egress_denied(host: &str) {
+    UPSTREAM_REQUESTS.with_label_values(&[host, "denied"]).inc();
+}
+
+/// Endpoint that returns all metrics in the Prometheus text format. Only
+/// served on the host-only listener.
+pub async fn metrics() -> impl IntoResponse {
+    let body = TextEncoder::new()
+        .encode_to_string(&prometheus::gather())
+        .unwrap_or_else(|e| format!("# failed to encode metrics: {}", e));
+    ([(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)], body)
+}
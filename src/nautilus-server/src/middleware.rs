@@ -0,0 +1,90 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::metrics::REQUEST_LATENCY;
+use crate::supervisor::panic_message;
+use crate::{EnclaveError, ErrorCode};
+use axum::extract::{MatchedPath, Request, State};
+use axum::http::HeaderValue;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use std::any::Any;
+use std::time::{Duration, Instant};
+use tracing::field::Empty;
+use tracing::{error, info, info_span, Instrument};
+use uuid::Uuid;
+
+/// Header echoing the request ID, to correlate client reports with enclave logs.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Run each request in a span carrying a generated request ID and its route,
+/// log its status, latency and error code when it completes, and echo the
+/// request ID in the response headers.
+pub async fn request_context(request: Request, next: Next) -> Response {
+    let request_id = Uuid::new_v4().to_string();
+    let matched_path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string());
+    let route = matched_path
+        .clone()
+        .unwrap_or_else(|| request.uri().path().to_string());
+    let span = info_span!(
+        "request",
+        request_id = %request_id,
+        method = %request.method(),
+        route = %route,
+        status = Empty,
+        latency_ms = Empty,
+        error_code = Empty,
+    );
+
+    let start = Instant::now();
+    let mut response = next.run(request).instrument(span.clone()).await;
+
+    let latency = start.elapsed();
+    span.record("status", response.status().as_u16());
+    span.record("latency_ms", latency.as_millis() as u64);
+    // Unmatched paths share one label to keep the metric cardinality bounded.
+    REQUEST_LATENCY
+        .with_label_values(&[
+            matched_path.as_deref().unwrap_or("unmatched"),
+            response.status().as_str(),
+        ])
+        .observe(latency.as_secs_f64());
+    if let Some(ErrorCode(code)) = response.extensions().get::<ErrorCode>() {
+        span.record("error_code", *code);
+    }
+    span.in_scope(|| info!("request completed"));
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// Fail a request that takes longer than the configured handler timeout. The
+/// handler may have been waiting on anything, e.g. the NSM or a batch, so the
+/// error is not reported as an upstream timeout, which is only raised by
+/// calls of the `EgressClient`.
+pub async fn handler_timeout(
+    State(timeout): State<Duration>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match tokio::time::timeout(timeout, next.run(request)).await {
+        Ok(response) => response,
+        Err(_) => EnclaveError::HandlerTimeout(format!(
+            "Request timed out after {} ms",
+            timeout.as_millis()
+        ))
+        .into_response(),
+    }
+}
+
+/// Turn a panicking handler into an internal error response instead of
+/// taking down the process, see `tower_http::catch_panic::CatchPanicLayer`.
+pub fn handle_panic(payload: Box<dyn Any + Send + 'static>) -> Response {
+    error!("handler panicked: {}", panic_message(payload.as_ref()));
+    EnclaveError::InternalError("Handler panicked".to_string()).into_response()
+}
@@ -0,0 +1,149 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::EnclaveError;
+use nsm_api::api::{Request as NsmRequest, Response as NsmResponse};
+use nsm_api::driver;
+use serde_bytes::ByteBuf;
+use std::sync::Arc;
+
+#[cfg(any(test, feature = "mock-nsm"))]
+mod mock;
+#[cfg(any(test, feature = "mock-nsm"))]
+pub use mock::MockNsm;
+
+/// Env var selecting the NSM backend at startup, `nitro` or `mock`.
+pub const NSM_BACKEND_ENV: &str = "NSM_BACKEND";
+
+/// Request for an attestation document, see `Nsm::attestation`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AttestationRequest {
+    pub user_data: Option<Vec<u8>>,
+    pub nonce: Option<Vec<u8>>,
+    pub public_key: Option<Vec<u8>>,
+}
+
+/// State of a PCR returned by `Nsm::describe_pcr`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PcrState {
+    /// Whether the PCR is locked and can no longer be extended.
+    pub locked: bool,
+    /// Current value of the PCR.
+    pub data: Vec<u8>,
+}
+
+/// Operations of the Nitro Secure Module used by the server. The real driver
+/// is `NitroNsm`, and `MockNsm` (feature `mock-nsm`, and always in tests)
+/// allows running the attestation code paths outside of a Nitro enclave.
+pub trait Nsm: Send + Sync {
+    /// Return a COSE_Sign1 attestation document for the given request.
+    fn attestation(&self, request: AttestationRequest) -> Result<Vec<u8>, EnclaveError>;
+
+    /// Return random bytes from the NSM entropy source.
+    fn get_random(&self) -> Result<Vec<u8>, EnclaveError>;
+
+    /// Return the state of the PCR at `index`.
+    fn describe_pcr(&self, index: u16) -> Result<PcrState, EnclaveError>;
+
+    /// Extend the PCR at `index` with `data`, returning its new value.
+    fn extend_pcr(&self, index: u16, data: &[u8]) -> Result<Vec<u8>, EnclaveError>;
+
+    /// Lock the PCR at `index` so that it can no longer be extended.
+    fn lock_pcr(&self, index: u16) -> Result<(), EnclaveError>;
+}
+
+/// NSM backed by the Nitro driver, only available inside an enclave.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NitroNsm;
+
+impl NitroNsm {
+    fn process(&self, request: NsmRequest) -> Result<NsmResponse, EnclaveError> {
+        let fd = driver::nsm_init();
+        if fd < 0 {
+            return Err(EnclaveError::NsmError(
+                "Failed to open the NSM driver, not running in an enclave?".to_string(),
+            ));
+        }
+        let response = driver::nsm_process_request(fd, request);
+        driver::nsm_exit(fd);
+        match response {
+            NsmResponse::Error(code) => Err(EnclaveError::NsmError(format!("{:?}", code))),
+            response => Ok(response),
+        }
+    }
+}
+
+fn unexpected_response() -> EnclaveError {
+    EnclaveError::NsmError("unexpected response".to_string())
+}
+
+impl Nsm for NitroNsm {
+    fn attestation(&self, request: AttestationRequest) -> Result<Vec<u8>, EnclaveError> {
+        match self.process(NsmRequest::Attestation {
+            user_data: request.user_data.map(ByteBuf::from),
+            nonce: request.nonce.map(ByteBuf::from),
+            public_key: request.public_key.map(ByteBuf::from),
+        })? {
+            NsmResponse::Attestation { document } => Ok(document),
+            _ => Err(unexpected_response()),
+        }
+    }
+
+    fn get_random(&self) -> Result<Vec<u8>, EnclaveError> {
+        match self.process(NsmRequest::GetRandom)? {
+            NsmResponse::GetRandom { random } => Ok(random),
+            _ => Err(unexpected_response()),
+        }
+    }
+
+    fn describe_pcr(&self, index: u16) -> Result<PcrState, EnclaveError> {
+        match self.process(NsmRequest::DescribePCR { index })? {
+            NsmResponse::DescribePCR { lock, data } => Ok(PcrState { locked: lock, data }),
+            _ => Err(unexpected_response()),
+        }
+    }
+
+    fn extend_pcr(&self, index: u16, data: &[u8]) -> Result<Vec<u8>, EnclaveError> {
+        match self.process(NsmRequest::ExtendPCR {
+            index,
+            data: data.to_vec(),
+        })? {
+            NsmResponse::ExtendPCR { data } => Ok(data),
+            _ => Err(unexpected_response()),
+        }
+    }
+
+    fn lock_pcr(&self, index: u16) -> Result<(), EnclaveError> {
+        match self.process(NsmRequest::LockPCR { index })? {
+            NsmResponse::LockPCR => Ok(()),
+            _ => Err(unexpected_response()),
+        }
+    }
+}
+
+/// Select the NSM backend from `NSM_BACKEND`. It defaults to `mock` when
+/// built with the `mock-nsm` feature and to `nitro` otherwise.
+pub fn from_env() -> Result<Arc<dyn Nsm>, EnclaveError> {
+    let default = if cfg!(feature = "mock-nsm") {
+        "mock"
+    } else {
+        "nitro"
+    };
+    let backend = std::env::var(NSM_BACKEND_ENV).unwrap_or_else(|_| default.to_string());
+    match backend.as_str() {
+        "nitro" => Ok(Arc::new(NitroNsm)),
+        #[cfg(feature = "mock-nsm")]
+        "mock" => {
+            tracing::warn!("using the mock NSM, attestations are signed by a local test CA and are not trusted onchain");
+            Ok(Arc::new(MockNsm::new()?))
+        }
+        #[cfg(not(feature = "mock-nsm"))]
+        "mock" => Err(EnclaveError::InternalError(
+            "The mock NSM requires building with the mock-nsm feature".to_string(),
+        )),
+        other => Err(EnclaveError::InternalError(format!(
+            "Unknown {} {}, expected nitro or mock",
+            NSM_BACKEND_ENV, other
+        ))),
+    }
+}
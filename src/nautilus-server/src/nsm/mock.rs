@@ -0,0 +1,246 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{AttestationRequest, Nsm, PcrState};
+use crate::EnclaveError;
+use nsm_api::api::{AttestationDoc, Digest};
+use p384::ecdsa::signature::Signer;
+use p384::ecdsa::{Signature, SigningKey};
+use p384::pkcs8::DecodePrivateKey;
+use rand::RngCore;
+use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, KeyPair, PKCS_ECDSA_P384_SHA384};
+use serde_bytes::ByteBuf;
+use sha2::{Digest as _, Sha384};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Number of PCRs, as reported by the Nitro NSM.
+const PCR_COUNT: u16 = 32;
+/// PCRs below this index are set at boot and locked, the others can be
+/// extended by the enclave as on Nitro.
+const FIRST_USER_PCR: u16 = 16;
+/// Length of a SHA384 PCR value.
+const PCR_LEN: usize = 48;
+/// Number of random bytes returned by `get_random`, as on Nitro.
+const RANDOM_LEN: usize = 256;
+/// COSE algorithm identifier of ECDSA P-384 with SHA-384.
+const COSE_ALG_ES384: i64 = -35;
+
+/// NSM mock that produces structurally valid COSE_Sign1 attestation
+/// documents signed by a test CA generated at startup. Its PCRs start at zero,
+/// as for an enclave in debug mode. Documents are not trusted onchain.
+pub struct MockNsm {
+    root_certificate: Vec<u8>,
+    certificate: Vec<u8>,
+    signing_key: SigningKey,
+    pcrs: Mutex<Vec<PcrState>>,
+}
+
+fn mock_error(context: &str, e: impl std::fmt::Display) -> EnclaveError {
+    EnclaveError::NsmError(format!("{}: {}", context, e))
+}
+
+impl MockNsm {
+    /// Generate a test root CA and a leaf certificate signing the documents.
+    pub fn new() -> Result<Self, EnclaveError> {
+        let root_key = KeyPair::generate_for(&PKCS_ECDSA_P384_SHA384)
+            .map_err(|e| mock_error("Failed to generate test CA key", e))?;
+        let mut root_params = CertificateParams::new(Vec::<String>::new())
+            .map_err(|e| mock_error("Invalid test CA parameters", e))?;
+        root_params
+            .distinguished_name
+            .push(DnType::CommonName, "nautilus mock nsm root");
+        root_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let root = root_params
+            .self_signed(&root_key)
+            .map_err(|e| mock_error("Failed to sign test CA", e))?;
+
+        let leaf_key = KeyPair::generate_for(&PKCS_ECDSA_P384_SHA384)
+            .map_err(|e| mock_error("Failed to generate test leaf key", e))?;
+        let mut leaf_params = CertificateParams::new(Vec::<String>::new())
+            .map_err(|e| mock_error("Invalid test leaf parameters", e))?;
+        leaf_params
+            .distinguished_name
+            .push(DnType::CommonName, "nautilus mock nsm");
+        let leaf = leaf_params
+            .signed_by(&leaf_key, &root, &root_key)
+            .map_err(|e| mock_error("Failed to sign test leaf", e))?;
+        let signing_key = SigningKey::from_pkcs8_der(&leaf_key.serialize_der())
+            .map_err(|e| mock_error("Failed to load test leaf key", e))?;
+
+        let pcrs = (0..PCR_COUNT)
+            .map(|index| PcrState {
+                locked: index < FIRST_USER_PCR,
+                data: vec![0; PCR_LEN],
+            })
+            .collect();
+
+        Ok(Self {
+            root_certificate: root.der().to_vec(),
+            certificate: leaf.der().to_vec(),
+            signing_key,
+            pcrs: Mutex::new(pcrs),
+        })
+    }
+
+    /// DER encoded root certificate of the test CA, to be trusted by verifiers
+    /// in place of the AWS Nitro root.
+    pub fn root_certificate(&self) -> &[u8] {
+        &self.root_certificate
+    }
+
+    fn pcrs(&self) -> std::sync::MutexGuard<'_, Vec<PcrState>> {
+        self.pcrs.lock().expect("mock nsm lock poisoned")
+    }
+
+    fn pcr_index(index: u16) -> Result<usize, EnclaveError> {
+        if index < PCR_COUNT {
+            Ok(index as usize)
+        } else {
+            Err(EnclaveError::NsmError(format!("InvalidIndex {}", index)))
+        }
+    }
+
+    /// Wrap the payload in a COSE_Sign1 structure signed with ES384.
+    fn sign_cose(&self, payload: Vec<u8>) -> Result<Vec<u8>, EnclaveError> {
+        let protected = serde_cbor::to_vec(&BTreeMap::from([(1i64, COSE_ALG_ES384)]))
+            .map_err(|e| mock_error("Failed to encode COSE header", e))?;
+        let sig_structure = serde_cbor::to_vec(&(
+            "Signature1",
+            ByteBuf::from(protected.clone()),
+            ByteBuf::new(),
+            ByteBuf::from(payload.clone()),
+        ))
+        .map_err(|e| mock_error("Failed to encode COSE signature structure", e))?;
+        let signature: Signature = self.signing_key.sign(&sig_structure);
+
+        serde_cbor::to_vec(&(
+            ByteBuf::from(protected),
+            BTreeMap::<i64, i64>::new(),
+            ByteBuf::from(payload),
+            ByteBuf::from(signature.to_bytes().to_vec()),
+        ))
+        .map_err(|e| mock_error("Failed to encode COSE_Sign1", e))
+    }
+}
+
+impl Nsm for MockNsm {
+    fn attestation(&self, request: AttestationRequest) -> Result<Vec<u8>, EnclaveError> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| mock_error("Invalid system time", e))?
+            .as_millis() as u64;
+        let pcrs = self
+            .pcrs()
+            .iter()
+            .enumerate()
+            .map(|(index, pcr)| (index, ByteBuf::from(pcr.data.clone())))
+            .collect();
+
+        let document = AttestationDoc {
+            module_id: "mock-nsm".to_string(),
+            digest: Digest::SHA384,
+            timestamp,
+            pcrs,
+            certificate: ByteBuf::from(self.certificate.clone()),
+            cabundle: vec![ByteBuf::from(self.root_certificate.clone())],
+            public_key: request.public_key.map(ByteBuf::from),
+            user_data: request.user_data.map(ByteBuf::from),
+            nonce: request.nonce.map(ByteBuf::from),
+        };
+        let payload = serde_cbor::to_vec(&document)
+            .map_err(|e| mock_error("Failed to encode attestation document", e))?;
+        self.sign_cose(payload)
+    }
+
+    fn get_random(&self) -> Result<Vec<u8>, EnclaveError> {
+        let mut random = vec![0; RANDOM_LEN];
+        rand::thread_rng().fill_bytes(&mut random);
+        Ok(random)
+    }
+
+    fn describe_pcr(&self, index: u16) -> Result<PcrState, EnclaveError> {
+        Ok(self.pcrs()[Self::pcr_index(index)?].clone())
+    }
+
+    fn extend_pcr(&self, index: u16, data: &[u8]) -> Result<Vec<u8>, EnclaveError> {
+        let mut pcrs = self.pcrs();
+        let pcr = &mut pcrs[Self::pcr_index(index)?];
+        if pcr.locked {
+            return Err(EnclaveError::NsmError(format!("ReadOnlyIndex {}", index)));
+        }
+        let mut hasher = Sha384::new();
+        hasher.update(&pcr.data);
+        hasher.update(data);
+        pcr.data = hasher.finalize().to_vec();
+        Ok(pcr.data.clone())
+    }
+
+    fn lock_pcr(&self, index: u16) -> Result<(), EnclaveError> {
+        self.pcrs()[Self::pcr_index(index)?].locked = true;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use p384::ecdsa::signature::Verifier;
+
+    #[test]
+    fn test_attestation_document() {
+        let nsm = MockNsm::new().unwrap();
+        let document = nsm
+            .attestation(AttestationRequest {
+                user_data: Some(b"user data".to_vec()),
+                nonce: Some(vec![1, 2, 3]),
+                public_key: Some(vec![7; 32]),
+            })
+            .unwrap();
+
+        let (protected, _unprotected, payload, signature): (
+            ByteBuf,
+            BTreeMap<i64, i64>,
+            ByteBuf,
+            ByteBuf,
+        ) = serde_cbor::from_slice(&document).unwrap();
+        let sig_structure = serde_cbor::to_vec(&(
+            "Signature1",
+            protected.clone(),
+            ByteBuf::new(),
+            payload.clone(),
+        ))
+        .unwrap();
+        let signature = Signature::from_slice(&signature).unwrap();
+        nsm.signing_key
+            .verifying_key()
+            .verify(&sig_structure, &signature)
+            .unwrap();
+
+        let document: AttestationDoc = serde_cbor::from_slice(&payload).unwrap();
+        assert_eq!(document.nonce.unwrap().into_vec(), vec![1, 2, 3]);
+        assert_eq!(document.public_key.unwrap().into_vec(), vec![7; 32]);
+        assert_eq!(document.user_data.unwrap().into_vec(), b"user data");
+        assert_eq!(document.pcrs.len(), PCR_COUNT as usize);
+        assert_eq!(document.cabundle[0].as_ref(), nsm.root_certificate());
+    }
+
+    #[test]
+    fn test_pcrs() {
+        let nsm = MockNsm::new().unwrap();
+        assert!(nsm.extend_pcr(0, b"data").is_err());
+
+        let value = nsm.extend_pcr(16, b"data").unwrap();
+        let mut hasher = Sha384::new();
+        hasher.update([0u8; PCR_LEN]);
+        hasher.update(b"data");
+        assert_eq!(value, hasher.finalize().to_vec());
+        assert_eq!(nsm.describe_pcr(16).unwrap().data, value);
+
+        nsm.lock_pcr(16).unwrap();
+        assert!(nsm.describe_pcr(16).unwrap().locked);
+        assert!(nsm.extend_pcr(16, b"data").is_err());
+        assert!(nsm.describe_pcr(PCR_COUNT).is_err());
+    }
+}
@@ -0,0 +1,290 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Liveness and readiness of the enclave for orchestrators and load balancers.
+//!
+//! `/livez` only tells that the server is up. It must not depend on upstreams
+//! or bootstrap, since restarting the enclave loses its ephemeral keys and any
+//! secret loaded after boot. `/readyz` tells whether the enclave can actually
+//! produce signatures, by aggregating enclave-wide conditions and the ones
+//! declared by each app with `EnclaveApp::readiness`.
+
+use crate::registry::{AppRegistry, EnclaveApp};
+use crate::AppState;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Maximum age of the endpoint statuses used by `/readyz`, so that frequent
+/// load balancer checks do not probe the endpoints on every call.
+const ENDPOINTS_MAX_AGE: Duration = Duration::from_secs(30);
+
+/// Timestamps signed before this time (2025-01-01) mean that the clock of the
+/// enclave is not synced.
+const MIN_SANE_TIME_MS: u64 = 1_735_689_600_000;
+
+/// A condition the enclave needs to serve requests.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReadinessCheck {
+    /// Name of the condition, prefixed with the app name for app conditions,
+    /// e.g. "seal.secrets_loaded".
+    pub name: String,
+    pub ready: bool,
+    /// Why the condition does not hold.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl ReadinessCheck {
+    /// Condition `name` that holds if `result` is Ok, with the error as reason.
+    pub fn new(name: impl Into<String>, result: Result<(), String>) -> Self {
+        Self {
+            name: name.into(),
+            ready: result.is_ok(),
+            reason: result.err(),
+        }
+    }
+}
+
+/// Response of `/readyz`, with status 200 if ready and 503 otherwise.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadinessResponse {
+    /// Whether every condition holds.
+    pub ready: bool,
+    pub checks: Vec<ReadinessCheck>,
+}
+
+impl IntoResponse for ReadinessResponse {
+    fn into_response(self) -> Response {
+        let status = if self.ready {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        };
+        (status, Json(self)).into_response()
+    }
+}
+
+#[derive(Clone)]
+struct Readiness {
+    state: Arc<AppState>,
+    apps: Vec<Arc<dyn EnclaveApp>>,
+}
+
+/// Router serving `/livez` and `/readyz` for the apps of the registry.
+pub fn router(state: Arc<AppState>, registry: &AppRegistry) -> Router {
+    Router::new()
+        .route("/livez", get(livez))
+        .route("/readyz", get(readyz))
+        .with_state(Readiness {
+            state,
+            apps: registry.apps().to_vec(),
+        })
+}
+
+/// Endpoint that tells the server is up, whatever the state of the apps.
+async fn livez() -> &'static str {
+    "ok"
+}
+
+/// Endpoint that tells whether the enclave can serve requests.
+async fn readyz(State(readiness): State<Readiness>) -> ReadinessResponse {
+    check(&readiness.state, &readiness.apps).await
+}
+
+/// Check the enclave-wide conditions and the conditions of every app.
+pub async fn check(state: &AppState, apps: &[Arc<dyn EnclaveApp>]) -> ReadinessResponse {
+    let mut checks = vec![
+        // Fails until the key is bound to its Enclave object if a domain is configured.
+        ReadinessCheck::new(
+            "signing_key",
+            state
+                .signing_key(None)
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+        ),
+        ReadinessCheck::new("key_attested", check_key_attested(state)),
+        ReadinessCheck::new("clock", check_clock(now_ms())),
+        ReadinessCheck::new("endpoints", check_endpoints(state).await),
+    ];
+    for app in apps {
+        checks.extend(app.readiness().into_iter().map(|check| ReadinessCheck {
+            name: format!("{}.{}", app.name(), check.name),
+            ..check
+        }));
+    }
+    ReadinessResponse {
+        ready: checks.iter().all(|check| check.ready),
+        checks,
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Fails until the NSM produced an attestation of the key signing requests
+/// without an epoch, since nothing can be trusted from an unattested key.
+fn check_key_attested(state: &AppState) -> Result<(), String> {
+    let key = state
+        .signing_key(None)
+        .unwrap_or_else(|_| state.keys.current());
+    if !key.is_attested() {
+        return Err(format!(
+            "Key epoch {} is not attested, call /get_attestation",
+            key.epoch
+        ));
+    }
+    Ok(())
+}
+
+fn check_clock(now_ms: u64) -> Result<(), String> {
+    if now_ms < MIN_SANE_TIME_MS {
+        return Err(format!(
+            "System time {}ms is before {}ms, the clock is not synced",
+            now_ms, MIN_SANE_TIME_MS
+        ));
+    }
+    Ok(())
+}
+
+async fn check_endpoints(state: &AppState) -> Result<(), String> {
+    let unhealthy: Vec<_> = state
+        .endpoints
+        .cached_statuses(ENDPOINTS_MAX_AGE)
+        .await
+        .into_iter()
+        .filter(|(_, status)| !status.healthy)
+        .map(|(host, _)| host)
+        .collect();
+    if !unhealthy.is_empty() {
+        return Err(format!("Unhealthy endpoints: {}", unhealthy.join(", ")));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::{get_attestation, IntentScope};
+    use crate::config::ServerConfig;
+    use crate::domain::DomainConfig;
+    use axum::extract::Query;
+
+    struct TestApp {
+        loaded: bool,
+    }
+
+    impl EnclaveApp for TestApp {
+        fn name(&self) -> &'static str {
+            "test"
+        }
+
+        fn intent_scopes(&self) -> &'static [IntentScope] {
+            &[IntentScope::ProcessData]
+        }
+
+        fn routes(self: Arc<Self>, _state: Arc<AppState>) -> Router {
+            Router::new()
+        }
+
+        fn readiness(&self) -> Vec<ReadinessCheck> {
+            let loaded = if self.loaded {
+                Ok(())
+            } else {
+                Err("not loaded".to_string())
+            };
+            vec![ReadinessCheck::new("secrets_loaded", loaded)]
+        }
+    }
+
+    fn test_state(config: ServerConfig) -> AppState {
+        AppState {
+            config,
+            ..crate::test_state()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_readiness() {
+        let state = Arc::new(test_state(ServerConfig::load().unwrap()));
+        let ready: Arc<dyn EnclaveApp> = Arc::new(TestApp { loaded: true });
+
+        // Not ready until the NSM attested the key.
+        let response = check(&state, &[ready.clone()]).await;
+        assert!(!response.ready);
+        assert_eq!(
+            response.checks[1],
+            ReadinessCheck {
+                name: "key_attested".to_string(),
+                ready: false,
+                reason: Some("Key epoch 0 is not attested, call /get_attestation".to_string()),
+            }
+        );
+        get_attestation(State(state.clone()), Query(Default::default()))
+            .await
+            .unwrap();
+
+        let response = check(&state, &[ready]).await;
+        assert!(response.ready);
+        let names: Vec<_> = response.checks.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "signing_key",
+                "key_attested",
+                "clock",
+                "endpoints",
+                "test.secrets_loaded"
+            ]
+        );
+        assert_eq!(response.into_response().status(), StatusCode::OK);
+
+        // A new key must be attested again.
+        state.keys.rotate().unwrap();
+        assert!(!check(&state, &[]).await.checks[1].ready);
+
+        let not_ready: Arc<dyn EnclaveApp> = Arc::new(TestApp { loaded: false });
+        let response = check(&state, &[not_ready]).await;
+        assert!(!response.ready);
+        assert_eq!(
+            response.checks[4],
+            ReadinessCheck {
+                name: "test.secrets_loaded".to_string(),
+                ready: false,
+                reason: Some("not loaded".to_string()),
+            }
+        );
+        assert_eq!(
+            response.into_response().status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+
+        // Not ready until the key is bound to its Enclave object.
+        let state = test_state(ServerConfig {
+            domain: Some(DomainConfig {
+                chain_id: "4c78adac".to_string(),
+                package_id: "0x2".to_string(),
+                committee_id: None,
+            }),
+            ..ServerConfig::load().unwrap()
+        });
+        let response = check(&state, &[]).await;
+        assert!(!response.ready);
+        assert!(!response.checks[0].ready);
+    }
+
+    #[test]
+    fn test_check_clock() {
+        assert!(check_clock(0).is_err());
+        assert!(check_clock(MIN_SANE_TIME_MS).is_ok());
+    }
+}
@@ -0,0 +1,154 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::common::IntentScope;
+use crate::endpoints::EndpointsConfig;
+use crate::readiness::ReadinessCheck;
+use crate::AppState;
+use crate::EnclaveError;
+use axum::Router;
+use std::sync::Arc;
+use tracing::info;
+
+/// An application served by nautilus-server. Each app owns its state, its
+/// routes and the intent scopes it signs under. Apps are mounted by the
+/// [`AppRegistry`] under `/apps/{name}`, so several apps can be served from
+/// one attested binary.
+pub trait EnclaveApp: Send + Sync + 'static {
+    /// Unique name of the app, used as the path segment it is mounted under.
+    fn name(&self) -> &'static str;
+
+    /// Intent scopes this app signs messages under.
+    fn intent_scopes(&self) -> &'static [IntentScope];
+
+    /// Public routes of the app, relative to `/apps/{name}`.
+    fn routes(self: Arc<Self>, state: Arc<AppState>) -> Router;
+
+    /// Routes served on the host-only listener, if the app needs any. These are
+    /// merged at the root of the host-only router, so apps should namespace them.
+    fn host_routes(self: Arc<Self>, _state: Arc<AppState>) -> Option<Router> {
+        None
+    }
+
+    /// Configuration the app loads at runtime, e.g. from env vars, measured into
+    /// the config PCR at boot as (name, content) pairs. Files baked into the
+    /// binary such as `allowed_endpoints.yaml` are covered by PCR2, do not
+    /// return them. The entries are published, never return secrets.
+    fn measured_config(&self) -> Vec<(&'static str, Vec<u8>)> {
+        Vec::new()
+    }
+
+    /// External endpoints the app calls, usually parsed from its baked
+    /// `allowed_endpoints.yaml`. They are probed by `/health_check`.
+    fn allowed_endpoints(&self) -> Result<EndpointsConfig, EnclaveError> {
+        Ok(EndpointsConfig::default())
+    }
+
+    /// Conditions the app needs to serve requests, e.g. secrets loaded after
+    /// boot, reported by `/readyz`. The enclave is only ready once they all hold.
+    fn readiness(&self) -> Vec<ReadinessCheck> {
+        Vec::new()
+    }
+}
+
+/// State handed to an app's handlers, containing the enclave-wide state
+/// (ephemeral keypair) and the app's own state.
+pub struct AppContext<A> {
+    pub enclave: Arc<AppState>,
+    pub app: Arc<A>,
+}
+
+impl<A> Clone for AppContext<A> {
+    fn clone(&self) -> Self {
+        Self {
+            enclave: self.enclave.clone(),
+            app: self.app.clone(),
+        }
+    }
+}
+
+impl<A> AppContext<A> {
+    pub fn new(enclave: Arc<AppState>, app: Arc<A>) -> Self {
+        Self { enclave, app }
+    }
+}
+
+/// Registry of the apps served by this enclave.
+#[derive(Default)]
+pub struct AppRegistry {
+    apps: Vec<Arc<dyn EnclaveApp>>,
+}
+
+impl AppRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register an app, failing if another app with the same name exists.
+    pub fn register<A: EnclaveApp>(&mut self, app: A) -> Result<(), EnclaveError> {
+        if self.apps.iter().any(|a| a.name() == app.name()) {
+            return Err(EnclaveError::InternalError(format!(
+                "App {} registered twice",
+                app.name()
+            )));
+        }
+        info!(
+            "Registered app {} with intent scopes {:?}",
+            app.name(),
+            app.intent_scopes()
+        );
+        self.apps.push(Arc::new(app));
+        Ok(())
+    }
+
+    pub fn apps(&self) -> &[Arc<dyn EnclaveApp>] {
+        &self.apps
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.apps.is_empty()
+    }
+
+    /// Router mounting every registered app under `/apps/{name}`. When exactly
+    /// one app is registered its routes are also served at the root, so existing
+    /// clients calling `/process_data` keep working.
+    pub fn router(&self, state: Arc<AppState>) -> Router {
+        let mut router = Router::new();
+        for app in &self.apps {
+            let routes = app.clone().routes(state.clone());
+            if self.apps.len() == 1 {
+                router = router.merge(routes.clone());
+            }
+            router = router.nest(&format!("/apps/{}", app.name()), routes);
+        }
+        router
+    }
+
+    /// Router merging the host-only routes of all apps, None if no app has any.
+    pub fn host_router(&self, state: Arc<AppState>) -> Option<Router> {
+        self.apps
+            .iter()
+            .filter_map(|app| app.clone().host_routes(state.clone()))
+            .reduce(|acc, routes| acc.merge(routes))
+    }
+}
+
+/// Build the registry with every app enabled through cargo features.
+#[allow(unused_mut)]
+pub fn enabled_apps() -> Result<AppRegistry, EnclaveError> {
+    let mut registry = AppRegistry::new();
+
+    #[cfg(feature = "weather-example")]
+    registry.register(crate::apps::weather_example::WeatherApp::from_env()?)?;
+
+    #[cfg(feature = "twitter-example")]
+    registry.register(crate::apps::twitter_example::TwitterApp::from_env()?)?;
+
+    #[cfg(feature = "seal-example")]
+    registry.register(crate::apps::seal_example::SealApp::new())?;
+
+    #[cfg(feature = "random-example")]
+    registry.register(crate::apps::random_example::RandomApp::new())?;
+
+    Ok(registry)
+}
@@ -0,0 +1,235 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::metrics::TASK_RESTARTS;
+use crate::EnclaveError;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+/// Delay before the first restart of a failed task, doubled on each
+/// consecutive failure up to `MAX_BACKOFF`.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+/// A task running longer than this before failing restarts with the initial backoff.
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+
+/// Shutdown notification shared by the servers and the supervised tasks.
+#[derive(Clone)]
+pub struct Shutdown(watch::Receiver<bool>);
+
+impl Shutdown {
+    /// Create a shutdown notification triggered by sending `true`.
+    pub fn channel() -> (watch::Sender<bool>, Self) {
+        let (tx, rx) = watch::channel(false);
+        (tx, Shutdown(rx))
+    }
+
+    /// Create a shutdown notification triggered by SIGTERM or Ctrl-C.
+    pub fn on_signal() -> Self {
+        let (tx, shutdown) = Self::channel();
+        tokio::spawn(async move {
+            wait_for_signal().await;
+            info!("shutdown requested, draining in-flight requests");
+            let _ = tx.send(true);
+        });
+        shutdown
+    }
+
+    pub fn is_requested(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Resolve once shutdown is requested, e.g. for `with_graceful_shutdown`.
+    pub async fn requested(mut self) {
+        // An error means the sender is gone, which can only happen on shutdown.
+        let _ = self.0.wait_for(|requested| *requested).await;
+    }
+}
+
+async fn wait_for_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                error!("failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// State of a supervised task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    Running,
+    /// The task failed and is waiting to be restarted.
+    Restarting,
+    /// The task exited on shutdown.
+    Stopped,
+}
+
+/// Status of a supervised task, reported by `/health_check`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskStatus {
+    pub state: TaskState,
+    /// Number of restarts since boot.
+    pub restarts: u64,
+    /// Error of the last failure, if any.
+    pub last_error: Option<String>,
+}
+
+/// Runs background tasks, such as the host-only server, restarting them with
+/// a backoff when they fail or panic instead of letting them die silently.
+#[derive(Default)]
+pub struct Supervisor {
+    tasks: RwLock<BTreeMap<&'static str, TaskStatus>>,
+}
+
+impl Supervisor {
+    /// Status of all supervised tasks by name.
+    pub fn statuses(&self) -> BTreeMap<String, TaskStatus> {
+        self.tasks
+            .read()
+            .expect("supervisor lock poisoned")
+            .iter()
+            .map(|(name, status)| (name.to_string(), status.clone()))
+            .collect()
+    }
+
+    /// Spawn a task built by `task`, rebuilding and restarting it whenever it
+    /// returns or panics before shutdown is requested.
+    pub fn spawn<F, Fut>(
+        self: &Arc<Self>,
+        name: &'static str,
+        shutdown: Shutdown,
+        task: F,
+    ) -> JoinHandle<()>
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), EnclaveError>> + Send + 'static,
+    {
+        let supervisor = self.clone();
+        tokio::spawn(async move {
+            let mut backoff = INITIAL_BACKOFF;
+            loop {
+                supervisor.update(name, |status| status.state = TaskState::Running);
+                let started = Instant::now();
+                // Run in its own task so that a panic is caught as a join error.
+                let failure = match tokio::spawn(task()).await {
+                    Ok(Ok(())) => "exited unexpectedly".to_string(),
+                    Ok(Err(e)) => e.to_string(),
+                    Err(e) if e.is_panic() => {
+                        format!("panicked: {}", panic_message(e.into_panic().as_ref()))
+                    }
+                    Err(e) => e.to_string(),
+                };
+                if shutdown.is_requested() {
+                    break;
+                }
+
+                error!(task = name, "supervised task failed: {}", failure);
+                TASK_RESTARTS.with_label_values(&[name]).inc();
+                supervisor.update(name, |status| {
+                    status.state = TaskState::Restarting;
+                    status.restarts += 1;
+                    status.last_error = Some(failure);
+                });
+
+                if started.elapsed() >= STABLE_AFTER {
+                    backoff = INITIAL_BACKOFF;
+                }
+                tokio::select! {
+                    _ = tokio::time::sleep(backoff) => {},
+                    _ = shutdown.clone().requested() => break,
+                }
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+            supervisor.update(name, |status| status.state = TaskState::Stopped);
+            info!(task = name, "supervised task stopped");
+        })
+    }
+
+    fn update(&self, name: &'static str, f: impl FnOnce(&mut TaskStatus)) {
+        let mut tasks = self.tasks.write().expect("supervisor lock poisoned");
+        let status = tasks.entry(name).or_insert(TaskStatus {
+            state: TaskState::Running,
+            restarts: 0,
+            last_error: None,
+        });
+        f(status);
+    }
+}
+
+/// Best effort description of a panic payload.
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[tokio::test]
+    async fn test_restart_after_failure_and_panic() {
+        let supervisor = Arc::new(Supervisor::default());
+        let (tx, shutdown) = Shutdown::channel();
+        let runs = Arc::new(AtomicU64::new(0));
+
+        let handle = supervisor.spawn("test", shutdown.clone(), {
+            let runs = runs.clone();
+            move || {
+                let runs = runs.clone();
+                let shutdown = shutdown.clone();
+                async move {
+                    match runs.fetch_add(1, Ordering::SeqCst) {
+                        0 => Err(EnclaveError::InternalError("bind failed".to_string())),
+                        1 => panic!("boom"),
+                        _ => {
+                            shutdown.requested().await;
+                            Ok(())
+                        }
+                    }
+                }
+            }
+        });
+
+        while runs.load(Ordering::SeqCst) < 3 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let status = supervisor.statuses()["test"].clone();
+        assert_eq!(status.state, TaskState::Running);
+        assert_eq!(status.restarts, 2);
+        assert_eq!(status.last_error.as_deref(), Some("panicked: boom"));
+
+        tx.send(true).unwrap();
+        handle.await.unwrap();
+        assert_eq!(supervisor.statuses()["test"].state, TaskState::Stopped);
+    }
+}
@@ -0,0 +1,120 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Payload types of the example apps, signed as the data of `IntentMessage<T>`.
+
+/// Types of the weather-example app, matching `move/weather-example`.
+pub mod weather {
+    use crate::MovePayload;
+    use serde::{Deserialize, Serialize};
+
+    /// Inner type T for IntentMessage<T>
+    #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, MovePayload)]
+    #[move_payload(intent = ProcessData, source = "../../move/weather-example/sources/weather.move")]
+    pub struct WeatherResponse {
+        pub location: String,
+        pub temperature: u64,
+    }
+
+    /// Inner type T for ProcessDataRequest<T>
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct WeatherRequest {
+        pub location: String,
+    }
+}
+
+/// Types of the random-example app, matching `move/random-example`.
+pub mod random {
+    use crate::MovePayload;
+    use serde::{Deserialize, Serialize};
+
+    /// Inner type T for IntentMessage<T>
+    #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, MovePayload)]
+    #[move_payload(intent = ProcessData, source = "../../move/random-example/sources/random.move")]
+    pub struct RandomResponse {
+        pub random_number: u64,
+        pub min: u64,
+        pub max: u64,
+    }
+
+    /// Inner type T for ProcessDataRequest<T>
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct RandomRequest {
+        pub min: u64,
+        pub max: u64,
+    }
+}
+
+/// Types of the twitter-example app, matching `move/twitter-example`.
+pub mod twitter {
+    use crate::MovePayload;
+    use serde::{Deserialize, Serialize};
+
+    /// Inner type for IntentMessage<T>
+    #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, MovePayload)]
+    #[move_payload(intent = ProcessData, source = "../../move/twitter-example/sources/twitter.move")]
+    pub struct UserData {
+        pub twitter_name: Vec<u8>,
+        pub sui_address: Vec<u8>,
+    }
+
+    /// Inner type for ProcessDataRequest<T>
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct UserRequest {
+        pub user_url: String,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::random::RandomResponse;
+    use super::twitter::UserData;
+    use super::weather::WeatherResponse;
+    use crate::MovePayload;
+
+    /// The generated tests are pasted into the Move modules, so that a change
+    /// of the BCS bytes in Rust fails until the Move test is regenerated.
+    #[test]
+    fn test_move_golden_vectors() {
+        let weather = WeatherResponse {
+            location: "San Francisco".to_string(),
+            temperature: 13,
+        }
+        .move_test(1744038900000);
+        assert!(
+            weather.contains("x\"0020b1d110960100000d53616e204672616e636973636f0d00000000000000\"")
+        );
+        assert!(
+            include_str!("../../../move/weather-example/sources/weather.move").contains(&weather)
+        );
+
+        let random = RandomResponse {
+            random_number: 42,
+            min: 1,
+            max: 100,
+        }
+        .move_test(1744038900000);
+        assert!(include_str!("../../../move/random-example/sources/random.move").contains(&random));
+
+        let twitter = UserData {
+            twitter_name: b"mystenintern".to_vec(),
+            sui_address: hex::decode(
+                "101ce8865558e08408b83f60ee9e78843d03d547c850cbe12cb599e17833dd3e",
+            )
+            .unwrap(),
+        }
+        .move_test(1743989326143);
+        assert!(twitter.contains("x\"003f41dd0d960100000c6d797374656e696e7465726e20101ce8865558e08408b83f60ee9e78843d03d547c850cbe12cb599e17833dd3e\""));
+        assert!(
+            include_str!("../../../move/twitter-example/sources/twitter.move").contains(&twitter)
+        );
+    }
+
+    #[test]
+    fn test_move_struct() {
+        assert_eq!(
+            WeatherResponse::move_struct(),
+            "public struct WeatherResponse has copy, drop {\n    location: String,\n    temperature: u64,\n}"
+        );
+    }
+}
@@ -0,0 +1,551 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Types of the Nautilus server API shared by the server and its clients: the
+//! request and response wrappers, the intent messages the enclave signs and
+//! the exact bytes it signs for them, and the payload types of the example
+//! apps. The BCS layouts match the structs of `move/enclave/sources/enclave.move`.
+
+// The `MovePayload` derive refers to this crate by name, also in `apps`.
+extern crate self as nautilus_types;
+
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+pub mod apps;
+mod payload;
+
+pub use nautilus_derive::MovePayload;
+pub use payload::{MovePayload, MoveValue};
+
+/// Intent message wrapper struct containing the intent scope and timestamp.
+/// This standardizes the serialized payload for signing.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IntentMessage<T: Serialize> {
+    pub intent: IntentScope,
+    pub timestamp_ms: u64,
+    pub data: T,
+}
+
+/// Intent scope enum. Add new scope here if needed, each corresponds to a
+/// scope for signing. Replace in with your own intent per message type being signed by the enclave.
+#[derive(Serialize_repr, Deserialize_repr, Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum IntentScope {
+    ProcessData = 0,
+    /// Merkle root over the results of a batch, see `batch.rs`.
+    BatchRoot = 1,
+    /// Request evaluated and found invalid, see `FailureAttestation`.
+    Failure = 2,
+    /// BLS12-381 public key of a key epoch, see `BlsKey`.
+    BlsKey = 3,
+}
+
+impl<T: Serialize + fmt::Debug> IntentMessage<T> {
+    pub fn new(data: T, timestamp_ms: u64, intent: IntentScope) -> Self {
+        Self {
+            data,
+            timestamp_ms,
+            intent,
+        }
+    }
+}
+
+/// Maximum length in bytes of the client nonce of a process data request.
+pub const MAX_CLIENT_NONCE_LEN: usize = 64;
+
+/// Client nonce and caller address of a request, signed along with the
+/// intent message so that a response can only be used once and by the caller.
+/// Its BCS layout matches `RequestBinding` in `move/enclave/sources/enclave.move`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RequestBinding {
+    pub nonce: Option<Vec<u8>>,
+    /// Sui address of the caller.
+    pub caller: Option<[u8; 32]>,
+}
+
+/// Deployment of the enclave, signed before the intent message so that a
+/// signature from one chain, package or `Enclave` object cannot be replayed
+/// against another. Its BCS layout matches `DomainSeparator` in
+/// `move/enclave/sources/enclave.move`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DomainSeparator {
+    /// Sui chain identifier, e.g. 4c78adac for testnet.
+    pub chain_id: Vec<u8>,
+    /// Package of the app consuming the signatures.
+    pub package_id: [u8; 32],
+    /// `Enclave` object registered with the public key of the signing key.
+    pub enclave_id: [u8; 32],
+}
+
+/// Deployment of the replicas, signed before the intent message in the BLS
+/// signature of a response when a domain is configured. It names the
+/// `Committee` object rather than the `Enclave` object, so that all replicas
+/// sign the same bytes. Its BCS layout matches `CommitteeSeparator` in
+/// `move/enclave/sources/bls_committee.move`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommitteeSeparator {
+    /// Sui chain identifier, e.g. 4c78adac for testnet.
+    pub chain_id: Vec<u8>,
+    /// Package of the app consuming the signatures.
+    pub package_id: [u8; 32],
+    /// `Committee` object the aggregate signatures are verified against.
+    pub committee_id: [u8; 32],
+}
+
+/// Signed by the enclave under `IntentScope::Failure` for a request it
+/// evaluated and found invalid, e.g. stale upstream data, so that consumers
+/// can prove it onchain. Its BCS layout matches `FailureAttestation` in
+/// `move/enclave/sources/enclave.move`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, MovePayload)]
+#[move_payload(intent = Failure, source = "../../move/enclave/sources/enclave.move")]
+pub struct FailureAttestation {
+    /// Stable error code, e.g. "unprocessable".
+    pub code: String,
+    /// Sha256 hash of the BCS bytes of the request payload, see `request_hash`.
+    pub request_hash: Vec<u8>,
+}
+
+/// BLS12-381 public key of a key epoch, signed by the enclave key of the same
+/// epoch under `IntentScope::BlsKey` with timestamp 0. A committee only admits
+/// BLS keys signed by a registered `Enclave`, so that only keys generated in
+/// attested enclaves are aggregated. Its BCS layout matches `BlsKey` in
+/// `move/enclave/sources/bls_committee.move`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, MovePayload)]
+#[move_payload(intent = BlsKey, source = "../../move/enclave/sources/bls_committee.move")]
+pub struct BlsKey {
+    /// Public key of 96 bytes.
+    pub public_key: Vec<u8>,
+}
+
+/// Sha256 hash of the BCS bytes of a request payload, signed in a failure
+/// attestation to identify the request that failed.
+pub fn request_hash<T: Serialize>(payload: &T) -> Vec<u8> {
+    Sha256::digest(bcs::to_bytes(payload).expect("should not fail")).to_vec()
+}
+
+/// Error body of a failed request, with the signed failure attestation if the
+/// enclave is configured to sign failures.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    /// Human-readable message.
+    pub error: String,
+    /// Stable machine-readable error code.
+    #[serde(default)]
+    pub code: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attestation: Option<ProcessedDataResponse<IntentMessage<FailureAttestation>>>,
+}
+
+/// Wrapper struct containing the response (the intent message) and signature,
+/// with the key epoch and public key that signed it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProcessedDataResponse<T> {
+    pub response: T,
+    pub signature: String,
+    /// Epoch of the ephemeral key that signed the response.
+    pub key_epoch: u64,
+    /// Hex encoded public key that signed the response.
+    pub public_key: String,
+    /// Hex encoded client nonce signed with the response, if requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    /// Caller address signed with the response, if requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub caller: Option<String>,
+    /// BLS12-381 signature of the response, if BLS signing is enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bls: Option<BlsSignature>,
+}
+
+/// BLS12-381 min_sig signature of a response. It signs the committee separator
+/// instead of the domain separator, which is specific to each `Enclave` object,
+/// so that replicas sign the same bytes and their signatures can be aggregated,
+/// see `bls_signing_payload`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlsSignature {
+    /// Hex encoded public key of 96 bytes.
+    pub public_key: String,
+    /// Hex encoded signature of 48 bytes.
+    pub signature: String,
+    /// Hex encoded signed bytes, see `bls_signing_payload`.
+    pub message: String,
+}
+
+/// Wrapper struct containing the request payload.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProcessDataRequest<T> {
+    pub payload: T,
+    /// Epoch of the key to sign with, defaults to the current key. The
+    /// previous key can be used during its overlap window after a rotation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_epoch: Option<u64>,
+    /// Hex encoded client nonce to sign with the response, e.g. for the
+    /// consumer contract to accept the response only once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    /// Sui address of the caller to sign with the response, e.g. for the
+    /// consumer contract to only accept it from that sender.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub caller: Option<String>,
+}
+
+/// A request field that cannot be decoded, e.g. a nonce that is not hex.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidRequest(pub String);
+
+impl fmt::Display for InvalidRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidRequest {}
+
+impl<T> ProcessDataRequest<T> {
+    /// Request without nonce, caller or key epoch.
+    pub fn new(payload: T) -> Self {
+        Self {
+            payload,
+            key_epoch: None,
+            nonce: None,
+            caller: None,
+        }
+    }
+
+    /// Decode the client nonce and caller address, None if neither is set so
+    /// that the signing payload stays the plain intent message.
+    pub fn binding(&self) -> Result<Option<RequestBinding>, InvalidRequest> {
+        let nonce = match &self.nonce {
+            Some(nonce) => {
+                let nonce = hex::decode(nonce)
+                    .map_err(|_| InvalidRequest("nonce must be hex encoded".to_string()))?;
+                if nonce.is_empty() || nonce.len() > MAX_CLIENT_NONCE_LEN {
+                    return Err(InvalidRequest(format!(
+                        "nonce must be 1 to {} bytes, got {}",
+                        MAX_CLIENT_NONCE_LEN,
+                        nonce.len()
+                    )));
+                }
+                Some(nonce)
+            }
+            None => None,
+        };
+        let caller = match &self.caller {
+            Some(caller) => Some(decode_address(caller).ok_or_else(|| {
+                InvalidRequest("caller must be a 32 bytes hex encoded Sui address".to_string())
+            })?),
+            None => None,
+        };
+        Ok((nonce.is_some() || caller.is_some()).then_some(RequestBinding { nonce, caller }))
+    }
+}
+
+/// Decode a hex encoded Sui address or object id, with or without 0x prefix.
+pub fn decode_address(value: &str) -> Option<[u8; 32]> {
+    hex::decode(value.strip_prefix("0x").unwrap_or(value))
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+}
+
+/// Bytes signed for an intent message: the bcs bytes of the domain separator
+/// if any, the bcs bytes of the intent message, then the bcs bytes of the
+/// binding if any.
+pub fn signing_payload<T: Serialize>(
+    domain: Option<&DomainSeparator>,
+    intent_msg: &IntentMessage<T>,
+    binding: Option<&RequestBinding>,
+) -> Vec<u8> {
+    let mut bytes = match domain {
+        Some(domain) => bcs::to_bytes(domain).expect("should not fail"),
+        None => Vec::new(),
+    };
+    bytes.extend(bcs::to_bytes(intent_msg).expect("should not fail"));
+    if let Some(binding) = binding {
+        bytes.extend(bcs::to_bytes(binding).expect("should not fail"));
+    }
+    bytes
+}
+
+/// Bytes signed by the BLS key for an intent message: the bcs bytes of the
+/// committee separator if any, then `signing_payload` without domain.
+pub fn bls_signing_payload<T: Serialize>(
+    committee: Option<&CommitteeSeparator>,
+    intent_msg: &IntentMessage<T>,
+    binding: Option<&RequestBinding>,
+) -> Vec<u8> {
+    let mut bytes = match committee {
+        Some(committee) => bcs::to_bytes(committee).expect("should not fail"),
+        None => Vec::new(),
+    };
+    bytes.extend(signing_payload(None, intent_msg, binding));
+    bytes
+}
+
+/// Signature scheme of the ephemeral keys, selected at startup with
+/// `signature_scheme` in `server_config.yaml`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureScheme {
+    /// Verified in Move with `sui::ed25519::ed25519_verify`.
+    #[default]
+    Ed25519,
+    /// ECDSA over secp256k1 with SHA-256, e.g. `sui::ecdsa_k1::secp256k1_verify`.
+    Secp256k1,
+    /// ECDSA over secp256r1 with SHA-256, e.g. `sui::ecdsa_r1::secp256r1_verify`
+    /// or WebAuthn verifiers.
+    Secp256r1,
+    /// ECDSA over secp256k1 with Keccak-256, signed as 65 bytes r || s || v
+    /// with a recovery id v of 0 or 1, e.g. for EVM `ecrecover` with v + 27.
+    Secp256k1Recoverable,
+}
+
+impl SignatureScheme {
+    /// Flag of the scheme passed to `enclave::register_enclave_with_scheme`,
+    /// the Sui signature flag for the schemes Sui defines.
+    pub fn flag(&self) -> u8 {
+        match self {
+            SignatureScheme::Ed25519 => 0,
+            SignatureScheme::Secp256k1 => 1,
+            SignatureScheme::Secp256r1 => 2,
+            SignatureScheme::Secp256k1Recoverable => 3,
+        }
+    }
+}
+
+impl fmt::Display for SignatureScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureScheme::Ed25519 => write!(f, "ed25519"),
+            SignatureScheme::Secp256k1 => write!(f, "secp256k1"),
+            SignatureScheme::Secp256r1 => write!(f, "secp256r1"),
+            SignatureScheme::Secp256k1Recoverable => write!(f, "secp256k1_recoverable"),
+        }
+    }
+}
+
+/// Version of the `AttestationUserData` layout, bumped on incompatible changes.
+pub const ATTESTATION_USER_DATA_VERSION: u8 = 1;
+
+/// Context of the enclave bound into the user_data of the attestation
+/// document, serialized as JSON.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AttestationUserData {
+    /// Layout version, see `ATTESTATION_USER_DATA_VERSION`.
+    pub version: u8,
+    /// Names of the apps served by the enclave.
+    pub apps: Vec<String>,
+    /// Signature scheme of the attested public key.
+    pub key_scheme: SignatureScheme,
+    /// Epoch of the attested public key.
+    pub key_epoch: u64,
+    /// Hex encoded BLS12-381 public key of the epoch, if BLS signing is enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bls_public_key: Option<String>,
+    /// Hex encoded sha256 hash of the server configuration.
+    pub config_hash: String,
+    /// Build information of the server binary.
+    pub build: BuildInfo,
+    /// Hex encoded data supplied by the verifier, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_data: Option<String>,
+}
+
+/// Build information of the server binary.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BuildInfo {
+    /// Version of the nautilus-server package.
+    pub package_version: String,
+    /// Git revision, if the `GIT_REVISION` env var was set at build time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_revision: Option<String>,
+}
+
+/// Query parameters for get attestation.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct GetAttestationQuery {
+    /// Hex encoded challenge of the verifier, put in the nonce of the document.
+    pub nonce: Option<String>,
+    /// Hex encoded data of the verifier, bound in the user_data of the document.
+    pub user_data: Option<String>,
+    /// Epoch of the key to attest, defaults to the current key.
+    pub epoch: Option<u64>,
+}
+
+/// Response for get attestation.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GetAttestationResponse {
+    /// Attestation document serialized in Hex.
+    pub attestation: String,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_signing_payload() {
+        let intent_msg = IntentMessage::new(7u64, 1744038900000, IntentScope::ProcessData);
+        assert_eq!(
+            hex::encode(signing_payload(None, &intent_msg, None)),
+            "0020b1d110960100000700000000000000"
+        );
+
+        let request = ProcessDataRequest {
+            nonce: Some("0102".to_string()),
+            caller: Some(format!("0x{}01", "00".repeat(31))),
+            ..ProcessDataRequest::new(())
+        };
+        let binding = request.binding().unwrap();
+        let domain = DomainSeparator {
+            chain_id: vec![0x4c, 0x78, 0xad, 0xac],
+            package_id: [2; 32],
+            enclave_id: [3; 32],
+        };
+        let bytes = signing_payload(Some(&domain), &intent_msg, binding.as_ref());
+        let domain_len = bcs::to_bytes(&domain).unwrap().len();
+        assert_eq!(domain_len, 1 + 4 + 32 + 32);
+        assert_eq!(
+            hex::encode(&bytes[domain_len..]),
+            format!(
+                "0020b1d1109601000007000000000000000102010201{}01",
+                "00".repeat(31)
+            )
+        );
+
+        assert!(ProcessDataRequest::new(()).binding().unwrap().is_none());
+        let invalid = |nonce: &str| {
+            ProcessDataRequest {
+                nonce: Some(nonce.to_string()),
+                ..ProcessDataRequest::new(())
+            }
+            .binding()
+            .is_err()
+        };
+        assert!(invalid("zz"));
+        assert!(invalid(""));
+        assert!(invalid(&"00".repeat(MAX_CLIENT_NONCE_LEN + 1)));
+    }
+
+    #[test]
+    fn test_request_binding() {
+        // test result should be consistent with test_serde_binding in `move/enclave/sources/enclave.move`.
+        #[derive(Debug, Serialize)]
+        struct SigningPayload {
+            location: String,
+            temperature: u64,
+        }
+        let intent_msg = IntentMessage::new(
+            SigningPayload {
+                location: "San Francisco".to_string(),
+                temperature: 13,
+            },
+            1744038900000,
+            IntentScope::ProcessData,
+        );
+        let mut caller = [0; 32];
+        caller[31] = 1;
+        let binding = RequestBinding {
+            nonce: Some(vec![1, 2]),
+            caller: Some(caller),
+        };
+        assert_eq!(
+            hex::encode(signing_payload(None, &intent_msg, Some(&binding))),
+            format!(
+                "0020b1d110960100000d53616e204672616e636973636f0d000000000000000102010201{}01",
+                "00".repeat(31)
+            )
+        );
+    }
+
+    #[test]
+    fn test_domain_separator() {
+        // test result should be consistent with test_serde_domain in `move/enclave/sources/enclave.move`.
+        let mut package_id = [0; 32];
+        package_id[31] = 2;
+        let mut enclave_id = [0; 32];
+        enclave_id[31] = 3;
+        let domain = DomainSeparator {
+            chain_id: vec![0x4c, 0x78, 0xad, 0xac],
+            package_id,
+            enclave_id,
+        };
+        assert_eq!(
+            hex::encode(bcs::to_bytes(&domain).unwrap()),
+            format!("044c78adac{}02{}03", "00".repeat(31), "00".repeat(31))
+        );
+    }
+
+    #[test]
+    fn test_bls_signing_payload() {
+        let intent_msg = IntentMessage::new(7u64, 1744038900000, IntentScope::ProcessData);
+        assert_eq!(
+            bls_signing_payload(None, &intent_msg, None),
+            signing_payload(None, &intent_msg, None)
+        );
+        let mut package_id = [0; 32];
+        package_id[31] = 2;
+        let mut committee_id = [0; 32];
+        committee_id[31] = 4;
+        let committee = CommitteeSeparator {
+            chain_id: vec![0x4c, 0x78, 0xad, 0xac],
+            package_id,
+            committee_id,
+        };
+        // test result should be consistent with test_committee_signing_payload in `move/enclave/sources/bls_committee.move`.
+        assert_eq!(
+            hex::encode(bls_signing_payload(Some(&committee), &intent_msg, None)),
+            format!(
+                "044c78adac{}02{}040020b1d110960100000700000000000000",
+                "00".repeat(31),
+                "00".repeat(31)
+            )
+        );
+    }
+
+    #[test]
+    fn test_failure_attestation() {
+        let request_hash = request_hash(&apps::weather::WeatherRequest {
+            location: "San Francisco".to_string(),
+        });
+        assert_eq!(request_hash.len(), 32);
+        let attestation = FailureAttestation {
+            code: "unprocessable".to_string(),
+            request_hash,
+        };
+        let test = attestation.move_test(1744038900000);
+        assert!(include_str!("../../../move/enclave/sources/enclave.move").contains(&test));
+    }
+
+    #[test]
+    fn test_bls_key() {
+        // Compressed generator of G2, the public key of secret key 1.
+        let key = BlsKey {
+            public_key: hex::decode(
+                "93e02b6052719f607dacd3a088274f65596bd0d09920b61ab5da61bbdc7f5049334cf11213945d57e5ac7d055d042b7e024aa2b2f08f0a91260805272dc51051c6e47ad4fa403b02b4510b647ae3d1770bac0326a805bbefd48056c8c121bdb8",
+            )
+            .unwrap(),
+        };
+        let test = key.move_test(0);
+        assert!(include_str!("../../../move/enclave/sources/bls_committee.move").contains(&test));
+    }
+
+    #[test]
+    fn test_signature_scheme() {
+        // Flags of `enclave::enclave`, e.g. `SECP256K1_RECOVERABLE`.
+        let move_source = include_str!("../../../move/enclave/sources/enclave.move");
+        for (scheme, constant) in [
+            (SignatureScheme::Ed25519, "ED25519"),
+            (SignatureScheme::Secp256k1, "SECP256K1"),
+            (SignatureScheme::Secp256r1, "SECP256R1"),
+            (
+                SignatureScheme::Secp256k1Recoverable,
+                "SECP256K1_RECOVERABLE",
+            ),
+        ] {
+            assert!(move_source.contains(&format!("const {}: u8 = {};", constant, scheme.flag())));
+        }
+    }
+}
@@ -0,0 +1,183 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Move counterparts of the payload types, implemented with
+//! `#[derive(MovePayload)]` from `nautilus-derive`. The derive checks the
+//! fields against the Move struct at build time and `MovePayload::move_test`
+//! generates a golden BCS test to paste into the Move module.
+
+use crate::{IntentMessage, IntentScope};
+use serde::Serialize;
+
+/// Move expression of a value, used to write the golden Move tests.
+pub trait MoveValue {
+    /// Move expression evaluating to the value, e.g. `b"abc".to_string()`.
+    fn move_value(&self) -> String;
+
+    /// Move expression of a vector of values, overridden for bytes to use a
+    /// hex literal.
+    fn move_vector(values: &[Self]) -> String
+    where
+        Self: Sized,
+    {
+        let values: Vec<_> = values.iter().map(MoveValue::move_value).collect();
+        format!("vector[{}]", values.join(", "))
+    }
+}
+
+/// Payload type signed as the data of an `IntentMessage<T>`, with the Move
+/// struct it is decoded as onchain.
+pub trait MovePayload: Serialize + MoveValue {
+    /// Intent scope the payload is signed with.
+    const INTENT: IntentScope;
+    /// Name of the Move struct.
+    const MOVE_NAME: &'static str;
+    /// Names and Move types of the fields, in BCS order.
+    const MOVE_FIELDS: &'static [(&'static str, &'static str)];
+
+    /// Move expressions of the field values, in the order of `MOVE_FIELDS`.
+    fn move_field_values(&self) -> Vec<String>;
+
+    /// Move definition of the struct.
+    fn move_struct() -> String {
+        let mut definition = format!("public struct {} has copy, drop {{\n", Self::MOVE_NAME);
+        for (name, ty) in Self::MOVE_FIELDS {
+            definition.push_str(&format!("    {}: {},\n", name, ty));
+        }
+        definition.push('}');
+        definition
+    }
+
+    /// Move unit test checking that the payload, wrapped in an intent message
+    /// with its intent scope and `timestamp_ms`, serializes to the same bytes
+    /// as in Rust.
+    fn move_test(&self, timestamp_ms: u64) -> String
+    where
+        Self: Sized,
+    {
+        let bytes = bcs::to_bytes(&IntentMessage {
+            intent: Self::INTENT,
+            timestamp_ms,
+            data: self,
+        })
+        .expect("should not fail");
+        let mut fields = String::new();
+        for ((name, _), value) in Self::MOVE_FIELDS.iter().zip(self.move_field_values()) {
+            fields.push_str(&format!("        {}: {},\n", name, value));
+        }
+        format!(
+            "#[test]\n\
+             fun test_serde_{test_name}() {{\n    \
+             // Generated by `MovePayload::move_test` for `{name}` in nautilus-types.\n    \
+             let mut bytes = vector[{intent}u8];\n    \
+             bytes.append(std::bcs::to_bytes(&{timestamp_ms}u64));\n    \
+             bytes.append(std::bcs::to_bytes(&{name} {{\n{fields}    }}));\n    \
+             assert!(bytes == x\"{bytes}\", 0);\n\
+             }}\n",
+            test_name = snake_case(Self::MOVE_NAME),
+            name = Self::MOVE_NAME,
+            intent = Self::INTENT as u8,
+            bytes = hex::encode(bytes),
+        )
+    }
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            snake.push('_');
+        }
+        snake.push(c.to_ascii_lowercase());
+    }
+    snake
+}
+
+macro_rules! impl_move_value_for_int {
+    ($($ty:ident),*) => {
+        $(impl MoveValue for $ty {
+            fn move_value(&self) -> String {
+                format!("{}{}", self, stringify!($ty))
+            }
+        })*
+    };
+}
+
+impl_move_value_for_int!(u16, u32, u64, u128);
+
+impl MoveValue for u8 {
+    fn move_value(&self) -> String {
+        format!("{}u8", self)
+    }
+
+    fn move_vector(values: &[Self]) -> String {
+        format!("x\"{}\"", hex::encode(values))
+    }
+}
+
+impl MoveValue for bool {
+    fn move_value(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl MoveValue for String {
+    fn move_value(&self) -> String {
+        let mut literal = String::from("b\"");
+        for byte in self.bytes() {
+            match byte {
+                b'"' | b'\\' => {
+                    literal.push('\\');
+                    literal.push(byte as char);
+                }
+                0x20..=0x7e => literal.push(byte as char),
+                _ => literal.push_str(&format!("\\x{:02x}", byte)),
+            }
+        }
+        literal.push_str("\".to_string()");
+        literal
+    }
+}
+
+/// Sui address or object id.
+impl MoveValue for [u8; 32] {
+    fn move_value(&self) -> String {
+        format!("@0x{}", hex::encode(self))
+    }
+}
+
+impl<T: MoveValue> MoveValue for Vec<T> {
+    fn move_value(&self) -> String {
+        T::move_vector(self)
+    }
+}
+
+impl<T: MoveValue> MoveValue for Option<T> {
+    fn move_value(&self) -> String {
+        match self {
+            Some(value) => format!("option::some({})", value.move_value()),
+            None => "option::none()".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_move_value() {
+        assert_eq!(13u64.move_value(), "13u64");
+        assert_eq!(vec![1u8, 2].move_value(), "x\"0102\"");
+        assert_eq!(vec![1u16, 2].move_value(), "vector[1u16, 2u16]");
+        assert_eq!(
+            Some("a\"b\n".to_string()).move_value(),
+            "option::some(b\"a\\\"b\\x0a\".to_string())"
+        );
+        assert_eq!(None::<bool>.move_value(), "option::none()");
+        let mut address = [0u8; 32];
+        address[31] = 1;
+        assert_eq!(address.move_value(), format!("@0x{}01", "00".repeat(31)));
+        assert_eq!(snake_case("WeatherResponse"), "weather_response");
+    }
+}
@@ -0,0 +1,398 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::VerifyError;
+use p384::ecdsa::signature::Verifier;
+use p384::ecdsa::{Signature, VerifyingKey};
+use serde::Deserialize;
+use serde_bytes::ByteBuf;
+use sha2::{Digest, Sha256, Sha384};
+use std::collections::BTreeMap;
+use x509_parser::certificate::X509Certificate;
+use x509_parser::prelude::FromDer;
+use x509_parser::time::ASN1Time;
+
+/// COSE algorithm identifier of ECDSA P-384 with SHA-384, the only algorithm
+/// used by the NSM.
+const COSE_ALG_ES384: i128 = -35;
+/// COSE header label of the algorithm.
+const COSE_HEADER_ALG: i64 = 1;
+
+/// Limits of the attestation document fields, see the AWS Nitro Enclaves
+/// attestation document specification.
+const MAX_PCRS: usize = 32;
+const MAX_CERT_LEN: usize = 1024;
+const MAX_PUBLIC_KEY_LEN: usize = 1024;
+const MAX_USER_DATA_LEN: usize = 512;
+const MAX_NONCE_LEN: usize = 512;
+
+/// PCR that the Nautilus server extends with the digest of its runtime
+/// configuration at boot before locking it.
+pub const CONFIG_PCR: u16 = 16;
+
+/// Attestation document as encoded in the COSE_Sign1 payload.
+#[derive(Debug, Deserialize)]
+struct AttestationDoc {
+    module_id: String,
+    digest: String,
+    timestamp: u64,
+    pcrs: BTreeMap<u16, ByteBuf>,
+    certificate: ByteBuf,
+    cabundle: Vec<ByteBuf>,
+    public_key: Option<ByteBuf>,
+    user_data: Option<ByteBuf>,
+    nonce: Option<ByteBuf>,
+}
+
+/// Content of an attestation document whose signature and certificate chain
+/// were verified.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedDocument {
+    /// Identifier of the NSM that issued the document.
+    pub module_id: String,
+    /// Creation time of the document in milliseconds since the Unix epoch.
+    pub timestamp_ms: u64,
+    /// PCR values by index.
+    pub pcrs: BTreeMap<u16, Vec<u8>>,
+    /// Public key of the enclave, if any.
+    pub public_key: Option<Vec<u8>>,
+    /// User data bound by the enclave, if any.
+    pub user_data: Option<Vec<u8>>,
+    /// Nonce supplied by the verifier, if any.
+    pub nonce: Option<Vec<u8>>,
+    /// DER encoded leaf certificate that signed the document.
+    pub certificate: Vec<u8>,
+}
+
+fn malformed(msg: impl Into<String>) -> VerifyError {
+    VerifyError::Malformed(msg.into())
+}
+
+/// Verify a COSE_Sign1 attestation document: its structure, its certificate
+/// chain up to the DER encoded `root_certificate` with every certificate
+/// valid at `time_ms` (milliseconds since the Unix epoch), and its ES384
+/// signature by the leaf certificate.
+pub fn verify_attestation(
+    document: &[u8],
+    root_certificate: &[u8],
+    time_ms: u64,
+) -> Result<VerifiedDocument, VerifyError> {
+    // COSE_Sign1 = [protected, unprotected, payload, signature]
+    let (protected, _unprotected, payload, signature): (
+        ByteBuf,
+        serde_cbor::Value,
+        ByteBuf,
+        ByteBuf,
+    ) = serde_cbor::from_slice(document)
+        .map_err(|e| malformed(format!("invalid COSE_Sign1: {}", e)))?;
+
+    let header: BTreeMap<i64, serde_cbor::Value> = serde_cbor::from_slice(&protected)
+        .map_err(|e| malformed(format!("invalid protected header: {}", e)))?;
+    if header.get(&COSE_HEADER_ALG) != Some(&serde_cbor::Value::Integer(COSE_ALG_ES384)) {
+        return Err(malformed("unsupported COSE algorithm, expected ES384"));
+    }
+
+    let doc: AttestationDoc = serde_cbor::from_slice(&payload)
+        .map_err(|e| malformed(format!("invalid document payload: {}", e)))?;
+    check_fields(&doc)?;
+
+    let leaf = verify_chain(&doc, root_certificate, time_ms)?;
+
+    // Sig_structure = ["Signature1", protected, external_aad, payload]
+    let sig_structure =
+        serde_cbor::to_vec(&("Signature1", &protected, ByteBuf::new(), &payload))
+            .map_err(|e| malformed(format!("failed to encode signature structure: {}", e)))?;
+    let key = VerifyingKey::from_sec1_bytes(&leaf.public_key().subject_public_key.data)
+        .map_err(|_| VerifyError::Signature("leaf key is not a P-384 key".to_string()))?;
+    let signature = Signature::from_slice(&signature)
+        .map_err(|_| VerifyError::Signature("invalid ES384 signature encoding".to_string()))?;
+    key.verify(&sig_structure, &signature)
+        .map_err(|_| VerifyError::Signature("signature does not verify".to_string()))?;
+
+    Ok(VerifiedDocument {
+        module_id: doc.module_id,
+        timestamp_ms: doc.timestamp,
+        pcrs: doc
+            .pcrs
+            .into_iter()
+            .map(|(index, value)| (index, value.into_vec()))
+            .collect(),
+        public_key: doc.public_key.map(ByteBuf::into_vec),
+        user_data: doc.user_data.map(ByteBuf::into_vec),
+        nonce: doc.nonce.map(ByteBuf::into_vec),
+        certificate: doc.certificate.into_vec(),
+    })
+}
+
+/// Check the syntactic requirements of the document fields.
+fn check_fields(doc: &AttestationDoc) -> Result<(), VerifyError> {
+    if doc.module_id.is_empty() {
+        return Err(malformed("empty module_id"));
+    }
+    if doc.digest != "SHA384" {
+        return Err(malformed(format!(
+            "unsupported digest {}, expected SHA384",
+            doc.digest
+        )));
+    }
+    if doc.timestamp == 0 {
+        return Err(malformed("missing timestamp"));
+    }
+    if doc.pcrs.is_empty() || doc.pcrs.len() > MAX_PCRS {
+        return Err(malformed(format!(
+            "expected 1 to {} PCRs, got {}",
+            MAX_PCRS,
+            doc.pcrs.len()
+        )));
+    }
+    for (index, value) in &doc.pcrs {
+        if *index as usize >= MAX_PCRS || ![32, 48, 64].contains(&value.len()) {
+            return Err(malformed(format!("invalid PCR{}", index)));
+        }
+    }
+    if doc.cabundle.is_empty() {
+        return Err(malformed("empty cabundle"));
+    }
+    let mut certificates = doc.cabundle.iter().chain(std::iter::once(&doc.certificate));
+    if certificates.any(|cert| cert.is_empty() || cert.len() > MAX_CERT_LEN) {
+        return Err(malformed("invalid certificate length"));
+    }
+    let too_long =
+        |value: &Option<ByteBuf>, max: usize| value.as_ref().is_some_and(|v| v.len() > max);
+    if too_long(&doc.public_key, MAX_PUBLIC_KEY_LEN)
+        || too_long(&doc.user_data, MAX_USER_DATA_LEN)
+        || too_long(&doc.nonce, MAX_NONCE_LEN)
+    {
+        return Err(malformed("public_key, user_data or nonce too long"));
+    }
+    Ok(())
+}
+
+/// Verify the chain root, intermediates, leaf of the document, returning the
+/// parsed leaf certificate.
+fn verify_chain<'a>(
+    doc: &'a AttestationDoc,
+    root_certificate: &[u8],
+    time_ms: u64,
+) -> Result<X509Certificate<'a>, VerifyError> {
+    let invalid = |msg: String| VerifyError::Certificate(msg);
+
+    // The first certificate of the bundle must be the trusted root.
+    if doc.cabundle[0].as_ref() != root_certificate {
+        return Err(invalid(
+            "the cabundle root does not match the supplied root certificate".to_string(),
+        ));
+    }
+    let time = ASN1Time::from_timestamp((time_ms / 1000) as i64)
+        .map_err(|e| invalid(format!("invalid verification time: {}", e)))?;
+
+    let mut issuer: Option<X509Certificate<'a>> = None;
+    let chain = doc.cabundle.iter().chain(std::iter::once(&doc.certificate));
+    let chain_len = doc.cabundle.len() + 1;
+    for (depth, der) in chain.enumerate() {
+        let (_, cert) = X509Certificate::from_der(der)
+            .map_err(|e| invalid(format!("failed to parse certificate {}: {}", depth, e)))?;
+        if !cert.validity().is_valid_at(time) {
+            return Err(invalid(format!(
+                "certificate {} ({}) is expired or not yet valid",
+                depth,
+                cert.subject()
+            )));
+        }
+        let is_leaf = depth + 1 == chain_len;
+        if !is_leaf && !cert.is_ca() {
+            return Err(invalid(format!(
+                "certificate {} ({}) is not a CA",
+                depth,
+                cert.subject()
+            )));
+        }
+        // The root is self-signed, the others are signed by their predecessor.
+        let issuer_key = issuer.as_ref().unwrap_or(&cert).public_key();
+        cert.verify_signature(Some(issuer_key)).map_err(|e| {
+            invalid(format!(
+                "certificate {} ({}) has an invalid signature: {}",
+                depth,
+                cert.subject(),
+                e
+            ))
+        })?;
+        issuer = Some(cert);
+    }
+    Ok(issuer.expect("the chain has at least the root and the leaf"))
+}
+
+/// Compare the PCRs of a verified document against their expected values.
+pub fn check_pcrs(
+    document: &VerifiedDocument,
+    expected: &BTreeMap<u16, Vec<u8>>,
+) -> Result<(), VerifyError> {
+    for (index, value) in expected {
+        let actual = document.pcrs.get(index);
+        if actual != Some(value) {
+            return Err(VerifyError::PcrMismatch {
+                index: *index,
+                expected: value.clone(),
+                actual: actual.cloned(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Expected value of `CONFIG_PCR` for a runtime configuration digest, as
+/// reported by `/health_check`: the PCR starts at zero and is extended once.
+pub fn expected_config_pcr(digest: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha384::new();
+    hasher.update([0u8; 48]);
+    hasher.update(digest);
+    hasher.finalize().to_vec()
+}
+
+/// Digest of the runtime configuration entries reported as `config_entries` by
+/// `/health_check`, computed like the server: the Sha256 hash of the BCS
+/// encoded map from entry name to content.
+pub fn config_digest(entries: &BTreeMap<String, Vec<u8>>) -> [u8; 32] {
+    Sha256::digest(bcs::to_bytes(entries).expect("should not fail")).into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use p384::ecdsa::signature::Signer;
+    use p384::ecdsa::SigningKey;
+    use p384::pkcs8::DecodePrivateKey;
+    use rcgen::{
+        BasicConstraints, CertificateParams, DnType, IsCa, KeyPair, PKCS_ECDSA_P384_SHA384,
+    };
+    use serde::Serialize;
+
+    const NOW_MS: u64 = 1_744_041_600_000;
+
+    #[derive(Serialize)]
+    struct TestDoc {
+        module_id: String,
+        digest: String,
+        timestamp: u64,
+        pcrs: BTreeMap<u16, ByteBuf>,
+        certificate: ByteBuf,
+        cabundle: Vec<ByteBuf>,
+        public_key: Option<ByteBuf>,
+        user_data: Option<ByteBuf>,
+        nonce: Option<ByteBuf>,
+    }
+
+    /// Return a document signed by a fresh test CA, and the CA root certificate.
+    fn test_document(pcr0: u8) -> (Vec<u8>, Vec<u8>) {
+        let root_key = KeyPair::generate_for(&PKCS_ECDSA_P384_SHA384).unwrap();
+        let mut root_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        root_params
+            .distinguished_name
+            .push(DnType::CommonName, "test root");
+        root_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let root = root_params.self_signed(&root_key).unwrap();
+
+        let leaf_key = KeyPair::generate_for(&PKCS_ECDSA_P384_SHA384).unwrap();
+        let mut leaf_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        leaf_params
+            .distinguished_name
+            .push(DnType::CommonName, "test leaf");
+        let leaf = leaf_params.signed_by(&leaf_key, &root, &root_key).unwrap();
+
+        let payload = serde_cbor::to_vec(&TestDoc {
+            module_id: "test-module".to_string(),
+            digest: "SHA384".to_string(),
+            timestamp: NOW_MS,
+            pcrs: (0..16)
+                .map(|i| (i, ByteBuf::from(vec![if i == 0 { pcr0 } else { 0 }; 48])))
+                .collect(),
+            certificate: ByteBuf::from(leaf.der().to_vec()),
+            cabundle: vec![ByteBuf::from(root.der().to_vec())],
+            public_key: Some(ByteBuf::from(vec![7; 32])),
+            user_data: Some(ByteBuf::from(b"{}".to_vec())),
+            nonce: Some(ByteBuf::from(vec![1, 2, 3])),
+        })
+        .unwrap();
+        let protected = serde_cbor::to_vec(&BTreeMap::from([(1i64, -35i64)])).unwrap();
+        let sig_structure = serde_cbor::to_vec(&(
+            "Signature1",
+            ByteBuf::from(protected.clone()),
+            ByteBuf::new(),
+            ByteBuf::from(payload.clone()),
+        ))
+        .unwrap();
+        let signing_key = SigningKey::from_pkcs8_der(&leaf_key.serialize_der()).unwrap();
+        let signature: Signature = signing_key.sign(&sig_structure);
+        let document = serde_cbor::to_vec(&(
+            ByteBuf::from(protected),
+            BTreeMap::<i64, i64>::new(),
+            ByteBuf::from(payload),
+            ByteBuf::from(signature.to_bytes().to_vec()),
+        ))
+        .unwrap();
+        (document, root.der().to_vec())
+    }
+
+    #[test]
+    fn test_verify_attestation() {
+        let (document, root) = test_document(0xaa);
+        let verified = verify_attestation(&document, &root, NOW_MS).unwrap();
+        assert_eq!(verified.module_id, "test-module");
+        assert_eq!(verified.pcrs[&0], vec![0xaa; 48]);
+        assert_eq!(verified.public_key, Some(vec![7; 32]));
+        assert_eq!(verified.nonce, Some(vec![1, 2, 3]));
+
+        assert!(check_pcrs(&verified, &BTreeMap::from([(0, vec![0xaa; 48])])).is_ok());
+        assert!(matches!(
+            check_pcrs(&verified, &BTreeMap::from([(0, vec![0xbb; 48])])),
+            Err(VerifyError::PcrMismatch { index: 0, .. })
+        ));
+        assert!(matches!(
+            check_pcrs(&verified, &BTreeMap::from([(20, vec![0; 48])])),
+            Err(VerifyError::PcrMismatch {
+                index: 20,
+                actual: None,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_config_digest() {
+        // Same vector as the server's ConfigMeasurement test.
+        let entries = BTreeMap::from([("env/RUST_LOG".to_string(), b"info".to_vec())]);
+        assert_eq!(
+            hex::encode(config_digest(&entries)),
+            "f905ccaf77d59f5d9424b8a579d3c6dbb005e46af1c5321650eb517684b37089"
+        );
+    }
+
+    #[test]
+    fn test_reject_invalid_documents() {
+        let (document, root) = test_document(0);
+        let (_, other_root) = test_document(0);
+        assert!(matches!(
+            verify_attestation(&document, &other_root, NOW_MS),
+            Err(VerifyError::Certificate(_))
+        ));
+
+        // rcgen certificates are valid until 4096.
+        assert!(matches!(
+            verify_attestation(&document, &root, 100_000_000_000_000),
+            Err(VerifyError::Certificate(_))
+        ));
+
+        let mut tampered = document.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert!(matches!(
+            verify_attestation(&tampered, &root, NOW_MS),
+            Err(VerifyError::Signature(_))
+        ));
+
+        assert!(matches!(
+            verify_attestation(&document[..10], &root, NOW_MS),
+            Err(VerifyError::Malformed(_))
+        ));
+    }
+}
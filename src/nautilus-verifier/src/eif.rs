@@ -0,0 +1,305 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Parser of the Enclave Image Format (EIF) built by `make`, recomputing the
+//! PCR0, PCR1 and PCR2 values that nitro-cli reports for the image.
+
+use crate::VerifyError;
+use sha2::{Digest, Sha384};
+use std::io::{Read, Seek, SeekFrom};
+
+/// Magic bytes at the start of an EIF.
+const EIF_MAGIC: [u8; 4] = *b".eif";
+/// Maximum number of sections in the header.
+const MAX_NUM_SECTIONS: usize = 32;
+/// Size of the big-endian EIF header.
+const EIF_HEADER_SIZE: usize = 4 + 2 + 2 + 8 + 8 + 2 + 2 + 8 * MAX_NUM_SECTIONS * 2 + 4 + 4;
+/// Size of the big-endian header preceding each section.
+const SECTION_HEADER_SIZE: usize = 2 + 2 + 8;
+
+/// Type of an EIF section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectionType {
+    Kernel,
+    Cmdline,
+    Ramdisk,
+    Signature,
+    Metadata,
+}
+
+impl SectionType {
+    fn from_u16(value: u16) -> Result<Self, VerifyError> {
+        match value {
+            1 => Ok(SectionType::Kernel),
+            2 => Ok(SectionType::Cmdline),
+            3 => Ok(SectionType::Ramdisk),
+            4 => Ok(SectionType::Signature),
+            5 => Ok(SectionType::Metadata),
+            other => Err(VerifyError::Malformed(format!(
+                "unknown EIF section type {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Section of an EIF, the data starts right after its section header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub section_type: SectionType,
+    /// Offset of the section data in the file.
+    pub data_offset: u64,
+    /// Size of the section data.
+    pub size: u64,
+}
+
+/// Header fields and section table of an EIF.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EifInfo {
+    pub version: u16,
+    pub default_mem: u64,
+    pub default_cpus: u64,
+    pub sections: Vec<Section>,
+}
+
+/// PCRs of an EIF, each 48 bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EifPcrs {
+    /// Enclave image: kernel, cmdline and all ramdisks.
+    pub pcr0: Vec<u8>,
+    /// Linux kernel and bootstrap: kernel, cmdline and the first ramdisk.
+    pub pcr1: Vec<u8>,
+    /// Application: the ramdisks after the first one.
+    pub pcr2: Vec<u8>,
+}
+
+fn malformed(msg: impl Into<String>) -> VerifyError {
+    VerifyError::Malformed(msg.into())
+}
+
+fn io_error(e: std::io::Error) -> VerifyError {
+    malformed(format!("failed to read EIF: {}", e))
+}
+
+/// Parse the EIF header and the section table.
+pub fn parse_eif<R: Read + Seek>(reader: &mut R) -> Result<EifInfo, VerifyError> {
+    let file_len = reader.seek(SeekFrom::End(0)).map_err(io_error)?;
+    reader.seek(SeekFrom::Start(0)).map_err(io_error)?;
+
+    let mut header = [0u8; EIF_HEADER_SIZE];
+    reader.read_exact(&mut header).map_err(io_error)?;
+    let mut fields = Fields(&header);
+    if fields.take::<4>() != EIF_MAGIC {
+        return Err(malformed("not an EIF, invalid magic"));
+    }
+    let version = fields.u16();
+    let _flags = fields.u16();
+    let default_mem = fields.u64();
+    let default_cpus = fields.u64();
+    let _reserved = fields.u16();
+    let num_sections = fields.u16() as usize;
+    if num_sections == 0 || num_sections > MAX_NUM_SECTIONS {
+        return Err(malformed(format!(
+            "invalid number of sections {}",
+            num_sections
+        )));
+    }
+    let offsets: Vec<u64> = (0..MAX_NUM_SECTIONS).map(|_| fields.u64()).collect();
+    let sizes: Vec<u64> = (0..MAX_NUM_SECTIONS).map(|_| fields.u64()).collect();
+
+    let mut sections = Vec::with_capacity(num_sections);
+    for (offset, size) in offsets.into_iter().zip(sizes).take(num_sections) {
+        let data_offset = offset
+            .checked_add(SECTION_HEADER_SIZE as u64)
+            .filter(|start| start.checked_add(size).is_some_and(|end| end <= file_len))
+            .ok_or_else(|| malformed("section out of bounds"))?;
+
+        let mut section_header = [0u8; SECTION_HEADER_SIZE];
+        reader.seek(SeekFrom::Start(offset)).map_err(io_error)?;
+        reader.read_exact(&mut section_header).map_err(io_error)?;
+        let mut fields = Fields(&section_header);
+        let section_type = SectionType::from_u16(fields.u16())?;
+        let _flags = fields.u16();
+        if fields.u64() != size {
+            return Err(malformed("section size does not match the header"));
+        }
+        sections.push(Section {
+            section_type,
+            data_offset,
+            size,
+        });
+    }
+
+    Ok(EifInfo {
+        version,
+        default_mem,
+        default_cpus,
+        sections,
+    })
+}
+
+/// Recompute PCR0, PCR1 and PCR2 as nitro-cli does: each PCR is extended once
+/// from zero with the SHA384 hash of the concatenation of its sections.
+pub fn compute_pcrs<R: Read + Seek>(
+    reader: &mut R,
+    info: &EifInfo,
+) -> Result<EifPcrs, VerifyError> {
+    let mut image = Sha384::new();
+    let mut bootstrap = Sha384::new();
+    let mut app = Sha384::new();
+    let mut ramdisks = 0;
+
+    let mut buf = vec![0u8; 64 * 1024];
+    for section in &info.sections {
+        let in_bootstrap = match section.section_type {
+            SectionType::Kernel | SectionType::Cmdline => true,
+            SectionType::Ramdisk => {
+                ramdisks += 1;
+                ramdisks == 1
+            }
+            SectionType::Signature | SectionType::Metadata => continue,
+        };
+
+        reader
+            .seek(SeekFrom::Start(section.data_offset))
+            .map_err(io_error)?;
+        let mut remaining = section.size;
+        while remaining > 0 {
+            let len = remaining.min(buf.len() as u64) as usize;
+            reader.read_exact(&mut buf[..len]).map_err(io_error)?;
+            image.update(&buf[..len]);
+            if in_bootstrap {
+                bootstrap.update(&buf[..len]);
+            } else {
+                app.update(&buf[..len]);
+            }
+            remaining -= len as u64;
+        }
+    }
+    if ramdisks == 0 {
+        return Err(malformed("EIF without ramdisk"));
+    }
+
+    Ok(EifPcrs {
+        pcr0: extend_from_zero(image),
+        pcr1: extend_from_zero(bootstrap),
+        pcr2: extend_from_zero(app),
+    })
+}
+
+fn extend_from_zero(hasher: Sha384) -> Vec<u8> {
+    let mut pcr = Sha384::new();
+    pcr.update([0u8; 48]);
+    pcr.update(hasher.finalize());
+    pcr.finalize().to_vec()
+}
+
+/// Big-endian field reader over a header, whose size is checked by the caller.
+struct Fields<'a>(&'a [u8]);
+
+impl Fields<'_> {
+    fn take<const N: usize>(&mut self) -> [u8; N] {
+        let (head, rest) = self.0.split_at(N);
+        self.0 = rest;
+        head.try_into().expect("split at N")
+    }
+
+    fn u16(&mut self) -> u16 {
+        u16::from_be_bytes(self.take())
+    }
+
+    fn u64(&mut self) -> u64 {
+        u64::from_be_bytes(self.take())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Build an EIF with the given sections, packed after the header.
+    fn build_eif(sections: &[(u16, &[u8])]) -> Vec<u8> {
+        let mut offsets = [0u64; MAX_NUM_SECTIONS];
+        let mut sizes = [0u64; MAX_NUM_SECTIONS];
+        let mut body = Vec::new();
+        for (i, (section_type, data)) in sections.iter().enumerate() {
+            offsets[i] = (EIF_HEADER_SIZE + body.len()) as u64;
+            sizes[i] = data.len() as u64;
+            body.extend_from_slice(&section_type.to_be_bytes());
+            body.extend_from_slice(&0u16.to_be_bytes());
+            body.extend_from_slice(&(data.len() as u64).to_be_bytes());
+            body.extend_from_slice(data);
+        }
+
+        let mut eif = EIF_MAGIC.to_vec();
+        eif.extend_from_slice(&4u16.to_be_bytes());
+        eif.extend_from_slice(&0u16.to_be_bytes());
+        eif.extend_from_slice(&0u64.to_be_bytes());
+        eif.extend_from_slice(&0u64.to_be_bytes());
+        eif.extend_from_slice(&0u16.to_be_bytes());
+        eif.extend_from_slice(&(sections.len() as u16).to_be_bytes());
+        offsets
+            .iter()
+            .for_each(|o| eif.extend_from_slice(&o.to_be_bytes()));
+        sizes
+            .iter()
+            .for_each(|s| eif.extend_from_slice(&s.to_be_bytes()));
+        eif.extend_from_slice(&[0u8; 8]);
+        assert_eq!(eif.len(), EIF_HEADER_SIZE);
+        eif.extend_from_slice(&body);
+        eif
+    }
+
+    fn pcrs(eif: Vec<u8>) -> Result<EifPcrs, VerifyError> {
+        let mut reader = Cursor::new(eif);
+        let info = parse_eif(&mut reader)?;
+        compute_pcrs(&mut reader, &info)
+    }
+
+    #[test]
+    fn test_compute_pcrs() {
+        let single_ramdisk = pcrs(build_eif(&[
+            (1, b"kernel"),
+            (2, b"cmdline"),
+            (3, b"rootfs"),
+            (4, b"signature"),
+        ]))
+        .unwrap();
+        // With a single ramdisk PCR0 equals PCR1, and PCR2 is the extension
+        // of the empty hash, as in the PCRs of `make` in UsingNautilus.md.
+        assert_eq!(single_ramdisk.pcr0, single_ramdisk.pcr1);
+        assert_eq!(
+            hex::encode(&single_ramdisk.pcr2),
+            "21b9efbc184807662e966d34f390821309eeac6802309798826296bf3e8bec7c10edb30948c90ba67310f7b964fc500a"
+        );
+        let mut hasher = Sha384::new();
+        hasher.update(b"kernelcmdlinerootfs");
+        assert_eq!(single_ramdisk.pcr0, extend_from_zero(hasher));
+
+        let two_ramdisks = pcrs(build_eif(&[
+            (1, b"kernel"),
+            (2, b"cmdline"),
+            (3, b"rootfs"),
+            (3, b"app"),
+        ]))
+        .unwrap();
+        assert_eq!(two_ramdisks.pcr1, single_ramdisk.pcr1);
+        assert_ne!(two_ramdisks.pcr0, two_ramdisks.pcr1);
+        let mut hasher = Sha384::new();
+        hasher.update(b"app");
+        assert_eq!(two_ramdisks.pcr2, extend_from_zero(hasher));
+    }
+
+    #[test]
+    fn test_reject_malformed() {
+        let mut eif = build_eif(&[(1, b"kernel"), (3, b"rootfs")]);
+        assert!(pcrs(build_eif(&[(1, b"kernel")])).is_err());
+        assert!(pcrs(build_eif(&[(9, b"kernel")])).is_err());
+        assert!(pcrs(eif[..EIF_HEADER_SIZE - 1].to_vec()).is_err());
+        eif.truncate(eif.len() - 1);
+        assert!(pcrs(eif.clone()).is_err());
+        eif[0] = b'x';
+        assert!(pcrs(eif).is_err());
+    }
+}
@@ -0,0 +1,60 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Offline verification of the attestation documents returned by the
+//! `/get_attestation` endpoint of a Nautilus server, so that an operator can
+//! check an enclave before registering it onchain, and recomputation of the
+//! PCRs of a built enclave image.
+
+use std::fmt;
+
+pub mod attestation;
+pub mod eif;
+
+pub use attestation::{
+    check_pcrs, config_digest, expected_config_pcr, verify_attestation, VerifiedDocument,
+    CONFIG_PCR,
+};
+
+/// Verification errors enum.
+#[derive(Debug)]
+pub enum VerifyError {
+    /// The input is not a well-formed COSE_Sign1 attestation document.
+    Malformed(String),
+    /// The certificate chain does not verify against the root certificate.
+    Certificate(String),
+    /// The COSE signature does not verify against the leaf certificate.
+    Signature(String),
+    /// A PCR does not match its expected value.
+    PcrMismatch {
+        index: u16,
+        expected: Vec<u8>,
+        actual: Option<Vec<u8>>,
+    },
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::Malformed(e) => write!(f, "Malformed attestation document: {}", e),
+            VerifyError::Certificate(e) => write!(f, "Invalid certificate chain: {}", e),
+            VerifyError::Signature(e) => write!(f, "Invalid document signature: {}", e),
+            VerifyError::PcrMismatch {
+                index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "PCR{} mismatch: expected {}, got {}",
+                index,
+                hex::encode(expected),
+                actual
+                    .as_ref()
+                    .map(hex::encode)
+                    .unwrap_or_else(|| "none".to_string())
+            ),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
@@ -0,0 +1,213 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Parser, Subcommand};
+use nautilus_verifier::eif::{compute_pcrs, parse_eif};
+use nautilus_verifier::{
+    check_pcrs, config_digest, expected_config_pcr, verify_attestation, VerifiedDocument,
+    CONFIG_PCR,
+};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Parser)]
+#[command(
+    name = "nautilus-verifier",
+    about = "Offline checks of Nautilus enclaves"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Verify an attestation document returned by /get_attestation.
+    Attestation {
+        /// File with the hex encoded document, or the JSON response of
+        /// /get_attestation. Reads stdin if omitted or "-".
+        input: Option<PathBuf>,
+        /// AWS Nitro Enclaves root certificate, PEM or DER encoded.
+        #[arg(long)]
+        root_cert: PathBuf,
+        /// Expected PCR value as INDEX=HEX, e.g. 0=abcd..., can be repeated.
+        #[arg(long = "expected-pcr", value_parser = parse_pcr)]
+        expected_pcrs: Vec<(u16, Vec<u8>)>,
+        /// Expected hex encoded digest of the runtime configuration, as reported
+        /// by /health_check, checked against PCR16.
+        #[arg(long)]
+        expected_config_digest: Option<String>,
+        /// JSON response of /health_check, whose measured `config_entries` are
+        /// checked against PCR16 and printed.
+        #[arg(long, conflicts_with = "expected_config_digest")]
+        expected_config: Option<PathBuf>,
+        /// Expected hex encoded nonce, as passed to /get_attestation.
+        #[arg(long)]
+        expected_nonce: Option<String>,
+        /// Verification time in milliseconds since the Unix epoch, defaults to now.
+        #[arg(long)]
+        time_ms: Option<u64>,
+    },
+    /// Recompute PCR0, PCR1 and PCR2 of an enclave image, e.g. out/nitro.eif.
+    Eif {
+        /// Path of the EIF.
+        path: PathBuf,
+        /// Print `PCR0=<hex>` lines to set env vars for `update_pcrs`, instead
+        /// of the `<hex> PCR0` lines of out/nitro.pcrs.
+        #[arg(long)]
+        env: bool,
+    },
+}
+
+fn main() -> Result<()> {
+    match Cli::parse().command {
+        Command::Attestation {
+            input,
+            root_cert,
+            expected_pcrs,
+            expected_config_digest,
+            expected_config,
+            expected_nonce,
+            time_ms,
+        } => {
+            let document = read_document(input.as_deref())?;
+            let root = read_certificate(&root_cert)?;
+            let time_ms = match time_ms {
+                Some(time_ms) => time_ms,
+                None => SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64,
+            };
+
+            let verified = verify_attestation(&document, &root, time_ms)?;
+            print_document(&verified);
+
+            let mut expected_pcrs: BTreeMap<_, _> = expected_pcrs.into_iter().collect();
+            if let Some(digest) = expected_config_digest {
+                let digest = hex::decode(digest).context("expected config digest must be hex")?;
+                expected_pcrs.insert(CONFIG_PCR, expected_config_pcr(&digest));
+            }
+            if let Some(path) = expected_config {
+                let entries = read_config_entries(&path)?;
+                for (name, value) in &entries {
+                    println!("config {}: {}", name, String::from_utf8_lossy(value));
+                }
+                expected_pcrs.insert(CONFIG_PCR, expected_config_pcr(&config_digest(&entries)));
+            }
+            check_pcrs(&verified, &expected_pcrs)?;
+            if let Some(expected) = expected_nonce {
+                let expected = hex::decode(expected).context("expected nonce must be hex")?;
+                if verified.nonce.as_ref() != Some(&expected) {
+                    bail!("nonce mismatch: expected {}", hex::encode(expected));
+                }
+            }
+            println!("attestation verified");
+            Ok(())
+        }
+        Command::Eif { path, env } => {
+            let file =
+                File::open(&path).with_context(|| format!("failed to open {}", path.display()))?;
+            let mut reader = BufReader::new(file);
+            let info = parse_eif(&mut reader)?;
+            let pcrs = compute_pcrs(&mut reader, &info)?;
+            for (index, pcr) in [pcrs.pcr0, pcrs.pcr1, pcrs.pcr2].iter().enumerate() {
+                if env {
+                    println!("PCR{}={}", index, hex::encode(pcr));
+                } else {
+                    println!("{} PCR{}", hex::encode(pcr), index);
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+fn parse_pcr(value: &str) -> Result<(u16, Vec<u8>)> {
+    let (index, pcr) = value
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected INDEX=HEX"))?;
+    let index = index.trim_start_matches("PCR").parse()?;
+    Ok((index, hex::decode(pcr)?))
+}
+
+/// Read a hex document, either raw or in the JSON response of /get_attestation.
+fn read_document(input: Option<&Path>) -> Result<Vec<u8>> {
+    let mut content = String::new();
+    match input {
+        Some(path) if path != Path::new("-") => {
+            content = std::fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+        }
+        _ => {
+            std::io::stdin().read_to_string(&mut content)?;
+        }
+    }
+    let content = content.trim();
+    let hex_document = if content.starts_with('{') {
+        let response: serde_json::Value = serde_json::from_str(content)?;
+        response["attestation"]
+            .as_str()
+            .ok_or_else(|| anyhow!("missing attestation field"))?
+            .to_string()
+    } else {
+        content.to_string()
+    };
+    hex::decode(hex_document).context("attestation must be hex encoded")
+}
+
+/// Read the hex encoded `config_entries` of a /health_check response.
+fn read_config_entries(path: &Path) -> Result<BTreeMap<String, Vec<u8>>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    let response: serde_json::Value = serde_json::from_str(&content)?;
+    let entries: BTreeMap<String, String> =
+        serde_json::from_value(response["config_entries"].clone())
+            .context("missing or invalid config_entries field")?;
+    entries
+        .into_iter()
+        .map(|(name, value)| {
+            let value = hex::decode(&value)
+                .with_context(|| format!("config entry {} must be hex", name))?;
+            Ok((name, value))
+        })
+        .collect()
+}
+
+fn read_certificate(path: &Path) -> Result<Vec<u8>> {
+    let bytes =
+        std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    if bytes.starts_with(b"-----BEGIN") {
+        let (_, pem) = x509_parser::pem::parse_x509_pem(&bytes)
+            .map_err(|e| anyhow!("invalid PEM certificate: {}", e))?;
+        Ok(pem.contents)
+    } else {
+        Ok(bytes)
+    }
+}
+
+fn print_document(document: &VerifiedDocument) {
+    let optional = |value: &Option<Vec<u8>>| {
+        value
+            .as_ref()
+            .map(hex::encode)
+            .unwrap_or_else(|| "none".to_string())
+    };
+    println!("module_id: {}", document.module_id);
+    println!("timestamp_ms: {}", document.timestamp_ms);
+    for (index, value) in &document.pcrs {
+        println!("PCR{}: {}", index, hex::encode(value));
+    }
+    println!("public_key: {}", optional(&document.public_key));
+    println!("nonce: {}", optional(&document.nonce));
+    // The Nautilus server binds a JSON object, print it as such when possible.
+    match document
+        .user_data
+        .as_deref()
+        .and_then(|data| serde_json::from_slice::<serde_json::Value>(data).ok())
+    {
+        Some(json) => println!("user_data: {}", json),
+        None => println!("user_data: {}", optional(&document.user_data)),
+    }
+}
@@ -129,8 +129,13 @@ pub fn insmod(path: &str) -> Result<(), SystemError> {
 
 // Instantiate a socket
 pub fn socket_connect(family: c_int, port: u32, cid: u32) -> Result<c_int, SystemError> {
-    use libc::{connect, sockaddr, sockaddr_vm, socket, SOCK_STREAM};
+    use libc::{close, connect, sockaddr, sockaddr_vm, socket, SOCK_STREAM};
     let fd = unsafe { socket(family, SOCK_STREAM, 0) };
+    if fd < 0 {
+        return Err(SystemError {
+            message: format!("Failed to create socket: {}", family),
+        });
+    }
     if unsafe {
         let mut sa: sockaddr_vm = zeroed();
         sa.svm_family = family as _;
@@ -143,6 +148,8 @@ pub fn socket_connect(family: c_int, port: u32, cid: u32) -> Result<c_int, Syste
         )
     } < 0
     {
+        // Close the socket, callers retrying the connection would leak it
+        unsafe { close(fd) };
         Err(SystemError {
             message: format!("Failed to connect to socket: {}", family),
         })